@@ -1,11 +1,12 @@
 use std::{any::TypeId, collections::HashMap, path::PathBuf};
 
 use anyhow::{Context, Result};
-use bevy::{asset::{LoadState, LoadedFolder}, prelude::*, window::PrimaryWindow};
-use serde::Deserialize;
+use bevy::{asset::{LoadState, LoadedFolder}, math::curve::{Curve, easing::EaseFunction}, prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
 
-use crate::{VisualNovelState, actor::operations::{apply_alpha, change_character_emotion, move_characters, position_relative_to_center, spawn_actor}, compiler::controller::{Controller, ControllerReadyMessage, ControllersSetStateMessage, SabiState}};
+use crate::{VisualNovelState, actor::operations::{Actor, CharacterLayer, CharacterZoom, animate_speaking, apply_alpha, change_character_emotion, change_layer, crossfade_sprite, focus_characters, move_characters, node_position, position_relative_to_center, spawn_actor, start_fade, start_movement, start_zoom, sync_character_layers, validate_outfit, zoom_characters}, compiler::{controller::{Controller, ControllerReadyMessage, ControllersSetStateMessage, PauseState, SabiState}, rollback::StageRestoreMessage}};
 use crate::compiler::controller::UiRoot;
+use crate::chat::ABOVE_TEXTBOX_Z_INDEX;
 
 pub const INVISIBLE_LEFT_PERCENTAGE: f32 = -40.;
 pub const FAR_LEFT_PERCENTAGE: f32 = 5.;
@@ -14,6 +15,8 @@ pub const LEFT_PERCENTAGE: f32 = 20.;
 pub const CENTER_PERCENTAGE: f32 = 35.;
 pub const RIGHT_PERCENTAGE: f32 = 50.;
 pub const INVISIBLE_RIGHT_PERCENTAGE: f32 = 140.;
+/// Z-index of the stage the actors are put on, between the background and the chat
+const CHARACTERS_Z_INDEX: i32 = 3;
 const CHARACTERS_ASSET_PATH: &str = "sabi/characters";
 const ANIMATIONS_ASSET_PATH: &str = "sabi/animations";
 
@@ -45,6 +48,32 @@ pub(crate) struct CharacterConfig {
     pub description: String,
     pub emotions: Vec<String>,
     pub outfits: Vec<String>,
+    /// GUI sprite of the textbox while the character speaks
+    pub textbox: Option<String>,
+    /// GUI sprite of the namebox while the character speaks
+    pub namebox: Option<String>,
+    /// Hex colour of the name, e.g. `"#FFB7C5"`
+    pub name_color: Option<String>,
+    /// Hex colour of the dialogue text
+    pub text_color: Option<String>,
+    /// Font asset path, e.g. `"sabi/fonts/ALLER.ttf"`
+    pub font: Option<String>,
+    /// Multiplier of the player's text speed
+    pub text_speed: Option<f32>,
+    /// Seconds the previous emotion takes to dissolve into the new one, `0` swaps sprites instantly
+    pub crossfade: Option<f32>,
+    /// Sprites stacked to compose the character, from the bottom one up.
+    /// Their images are read from `layers/<layer>/<variant>.png` instead of the outfit folders
+    #[serde(default)]
+    pub layers: Vec<LayerConfig>,
+    /// Layer switched by emotion changes on layered characters, e.g. the face
+    pub emotion_layer: Option<String>,
+    /// Layer switched by outfit changes on layered characters, e.g. the body
+    pub outfit_layer: Option<String>,
+    /// Whether the sprite switches to its `<emotion>speaking` variant while the character's lines reveal
+    pub speaking: Option<SpeakingStyle>,
+    /// Height of the sprite in percentage of the screen, `75` when unset
+    pub height: Option<f32>,
 }
 #[derive(Component, Debug, Default, Asset, TypePath, Deserialize, Clone)]
 pub(crate) struct AnimationConfig {
@@ -57,19 +86,56 @@ pub(crate) struct AnimationConfig {
     pub start_index: usize,
     pub end_index: usize,
 }
+/// Parsed from the `.json` files of the characters and animations folders, a file is
+/// read as an animation when it doesn't describe a character
 #[derive(Component, Debug, Asset, TypePath, Deserialize, Clone)]
+#[serde(untagged)]
 pub enum ActorConfig {
-    Character(CharacterConfig),
+    Character(Box<CharacterConfig>),
     Animation(AnimationConfig),
 }
 
-#[derive(Component, Debug, Clone, PartialEq)]
+impl CharacterConfig {
+    /// Checks the chatbox colours once the config is loaded, instead of when the character first speaks.
+    /// The GUI sprites are checked by the chat once its own assets are loaded
+    pub(crate) fn validate(&self) -> Result<()> {
+        for (field, color) in [("name_color", &self.name_color), ("text_color", &self.text_color)] {
+            if let Some(hex) = color {
+                Srgba::hex(hex)
+                    .with_context(|| format!("Character '{}': {} '{}' is not a valid hex colour", self.name, field, hex))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ActorConfig {
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            ActorConfig::Character(config) => &config.name,
+            ActorConfig::Animation(config) => &config.name,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) enum ActorPosition {
     Character(CharacterPosition),
     Animation(AnimationPosition),
 }
 
-#[derive(Component, Default, Debug, Clone, PartialEq)]
+impl ActorPosition {
+    /// Resolves the position against the project's named positions, failing on a name none of them defines
+    pub(crate) fn validate(&self, named_positions: &NamedPositions) -> Result<(), BevyError> {
+        match self {
+            ActorPosition::Character(position) => position.to_percentage_value(named_positions).map(|_| ()),
+            ActorPosition::Animation(position) => position.coordinates(named_positions).map(|_| ()),
+        }
+    }
+}
+
+/// Horizontal position of the left edge of a character sprite
+#[derive(Component, Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) enum CharacterPosition {
     #[default]
     Center,
@@ -79,9 +145,15 @@ pub(crate) enum CharacterPosition {
     Right,
     InvisibleLeft,
     InvisibleRight,
+    /// Percentage of the screen width
+    Percentage(f32),
+    /// Position defined in a `.positions.json` file
+    Named(String),
+    /// Position moved by a percentage of the screen width
+    Offset(Box<CharacterPosition>, f32),
 }
 
-#[derive(Component, Default, Debug, Clone, PartialEq)]
+#[derive(Component, Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) enum AnimationPosition {
     #[default]
     Center,
@@ -105,11 +177,18 @@ pub(crate) enum AnimationPosition {
     OuterLeftBottom,
     OuterLeft,
     OuterLeftTop,
+    /// Percentages of the screen width and height
+    Percentage(f32, f32),
+    /// Position defined in a `.positions.json` file
+    Named(String),
+    /// Position moved by percentages of the screen width and height
+    Offset(Box<AnimationPosition>, f32, f32),
 }
 
-impl From<AnimationPosition> for (f32, f32) {
-    fn from(value: AnimationPosition) -> Self {
-        match value {
+impl AnimationPosition {
+    /// Center of the animation, in percentages of the screen from its bottom left corner
+    pub fn coordinates(&self, named_positions: &NamedPositions) -> Result<(f32, f32), BevyError> {
+        Ok(match self {
             AnimationPosition::TopLeft          => { (15., 85.) },
             AnimationPosition::Top              => { (50., 85.) },
             AnimationPosition::TopRight         => { (85., 85.) },
@@ -131,6 +210,22 @@ impl From<AnimationPosition> for (f32, f32) {
             AnimationPosition::OuterLeftBottom  => { (-30., 15.) },
             AnimationPosition::OuterLeft        => { (-30., 50.) },
             AnimationPosition::OuterLeftTop     => { (-30., 85.) },
+            AnimationPosition::Percentage(x, y) => { (*x, *y) },
+            AnimationPosition::Named(name) => {
+                named_positions.0.animations.get(name).copied()
+                    .context(format!("Animation position '{}' is not defined", name))?
+            },
+            AnimationPosition::Offset(base, x, y) => {
+                let (base_x, base_y) = base.coordinates(named_positions)?;
+                (base_x + x, base_y + y)
+            },
+        })
+    }
+
+    fn offset_by(self, x: f32, y: f32) -> Self {
+        match self {
+            AnimationPosition::Offset(base, base_x, base_y) => AnimationPosition::Offset(base, base_x + x, base_y + y),
+            other => AnimationPosition::Offset(Box::new(other), x, y),
         }
     }
 }
@@ -170,15 +265,28 @@ impl TryFrom<&str> for AnimationPosition {
 }
 
 impl CharacterPosition {
-    pub fn to_percentage_value(&self) -> f32 {
-        match &self {
+    pub fn to_percentage_value(&self, named_positions: &NamedPositions) -> Result<f32, BevyError> {
+        Ok(match &self {
             CharacterPosition::Center => CENTER_PERCENTAGE,
             CharacterPosition::FarLeft => FAR_LEFT_PERCENTAGE,
             CharacterPosition::FarRight => FAR_RIGHT_PERCENTAGE,
             CharacterPosition::Left => LEFT_PERCENTAGE,
             CharacterPosition::Right => RIGHT_PERCENTAGE,
             CharacterPosition::InvisibleLeft => INVISIBLE_LEFT_PERCENTAGE,
-            CharacterPosition::InvisibleRight => INVISIBLE_RIGHT_PERCENTAGE
+            CharacterPosition::InvisibleRight => INVISIBLE_RIGHT_PERCENTAGE,
+            CharacterPosition::Percentage(value) => *value,
+            CharacterPosition::Named(name) => {
+                named_positions.0.characters.get(name).copied()
+                    .context(format!("Character position '{}' is not defined", name))?
+            },
+            CharacterPosition::Offset(base, offset) => base.to_percentage_value(named_positions)? + offset,
+        })
+    }
+
+    fn offset_by(self, offset: f32) -> Self {
+        match self {
+            CharacterPosition::Offset(base, base_offset) => CharacterPosition::Offset(base, base_offset + offset),
+            other => CharacterPosition::Offset(Box::new(other), offset),
         }
     }
 }
@@ -200,6 +308,42 @@ impl TryFrom<&str> for CharacterPosition {
     }
 }
 
+/// Full-screen container of the actors, their `ZIndex` orders them among each other
+#[derive(Component)]
+pub(crate) struct ActorStage;
+
+/// Where an actor is drawn, actors on a higher stage layer cover the lower ones
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum ActorDepth {
+    Layer(i32),
+    /// Over the textbox and the rest of the chat, for full-screen effects
+    AboveTextbox,
+}
+
+impl Default for ActorDepth {
+    fn default() -> Self {
+        ActorDepth::Layer(0)
+    }
+}
+
+impl ActorDepth {
+    /// Actors above the textbox sit between the chat and the menus
+    pub(crate) fn z_index(&self) -> ZIndex {
+        match self {
+            ActorDepth::Layer(layer) => ZIndex(*layer),
+            ActorDepth::AboveTextbox => ZIndex(ABOVE_TEXTBOX_Z_INDEX),
+        }
+    }
+
+    /// Entity the actor is put under, the UI root for actors above the textbox
+    pub(crate) fn parent(&self, stage: Entity, ui_root: Entity) -> Entity {
+        match self {
+            ActorDepth::Layer(_) => stage,
+            ActorDepth::AboveTextbox => ui_root,
+        }
+    }
+}
+
 #[derive(Component)]
 pub(crate) struct AnimationTimer(pub Timer);
 #[derive(Component)]
@@ -215,7 +359,10 @@ struct HandleToAnimationsFolder(Handle<LoadedFolder>);
 pub(crate) struct ActorsResource(pub ActorSprites);
 
 #[derive(Resource, Default, Debug)]
-struct ActorsConfigs(ActorsConfig);
+pub(crate) struct ActorsConfigs(pub ActorsConfig);
+/// Character configs as read from their files, the outfits and emotions of [ActorsConfigs] are reset from them on restore
+#[derive(Resource, Default, Debug)]
+pub(crate) struct LoadedCharacterConfigs(pub ActorsConfig);
 
 #[derive(Resource, Default)]
 struct CharFolderLoaded(pub bool);
@@ -223,15 +370,24 @@ struct CharFolderLoaded(pub bool);
 struct AnimFolderLoaded(pub bool);
 
 #[derive(Resource, Default)]
-pub(crate) struct FadingActors(pub Vec<(Entity, f32, bool)>); // entity, alpha_step, to_despawn
+pub(crate) struct FadingActors(pub Vec<ActorFade>);
+/// Positions of every `.positions.json` file in the characters folder
+#[derive(Resource, Default)]
+pub(crate) struct NamedPositions(pub PositionsConfig);
+#[derive(Resource, Default)]
+pub(crate) struct MovingActors(pub Vec<ActorMovement>);
 #[derive(Resource, Default)]
-pub(crate) struct MovingActors(pub Vec<(Entity, (f32, f32))>); // entity, target_position
+pub(crate) struct ZoomingActors(pub Vec<ActorZoom>);
+/// Characters highlighted by the auto-focus, those who said the last line, everyone when unset
+#[derive(Resource, Default)]
+pub(crate) struct FocusedCharacters(pub Option<Vec<String>>);
 
 /* Custom types */
 #[derive(Hash, Eq, PartialEq, Debug)]
 pub(crate) enum SpriteIdentifier {
     Character(SpriteKey),
     Animation(String),
+    Layer(LayerKey),
 }
 pub(crate) type ActorSprites = HashMap<SpriteIdentifier, Handle<Image>>;
 #[derive(Hash, Eq, PartialEq, Debug, Clone)]
@@ -240,11 +396,18 @@ pub(crate) struct SpriteKey {
     pub outfit: String,
     pub emotion: String,
 }
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+pub(crate) struct LayerKey {
+    pub character: String,
+    pub layer: String,
+    pub variant: String,
+}
 type CharacterSprites = HashMap<SpriteKey, Handle<Image>>;
+type LayerSprites = HashMap<LayerKey, Handle<Image>>;
 type AnimationSprites = HashMap<String, Handle<Image>>;
-type ActorsConfig = HashMap<String, ActorConfig>;
+pub(crate) type ActorsConfig = HashMap<String, ActorConfig>;
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub enum ActorDirection {
     Left,
     #[default]
@@ -266,22 +429,269 @@ impl TryFrom<&str> for ActorDirection {
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SpawnInfo {
     pub emotion: Option<String>,
+    pub outfit: Option<String>,
     pub position: Option<ActorPosition>,
     pub direction: ActorDirection,
     pub fading: bool,
+    /// Timing of the fade in
+    #[serde(skip)]
+    pub fade: Tween,
     pub scale: Option<f32>,
+    /// Layers changed by the script since the character appeared
+    #[serde(default)]
+    pub layers: HashMap<String, LayerSetting>,
+    #[serde(default)]
+    pub depth: ActorDepth,
+    /// Scale of the character set by `zooms`, `1` when unset
+    pub zoom: Option<f32>,
+    /// Height of the character above the ground, in percentage of the screen
+    #[serde(default)]
+    pub elevation: f32,
+}
+
+/// How the sprite of a character follows their lines while they reveal
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SpeakingStyle {
+    /// Shows the speaking variant until the line is fully revealed
+    Hold,
+    /// Alternates between the emotion and its speaking variant
+    Flap,
+}
+
+/// Sprite layer of a layered character, e.g. the base pose, the face or a blush
+#[derive(Debug, Default, Clone, Deserialize)]
+pub(crate) struct LayerConfig {
+    pub name: String,
+    /// Variant shown when the character appears
+    pub default: String,
+    /// Whether the layer stays hidden until the script turns it on
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// Change of a character layer written in the script, e.g. `(Nayu layer blush on)` or `(Nayu layer face smiling)`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum LayerChange {
+    Show,
+    Hide,
+    Variant(String),
+}
+
+/// State of a layer changed by the script, replayed when the character is restored
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct LayerSetting {
+    /// The default variant of the layer when unset
+    pub variant: Option<String>,
+    pub visible: bool,
+}
+
+impl LayerSetting {
+    pub(crate) fn apply(&mut self, change: &LayerChange) {
+        match change {
+            LayerChange::Show => self.visible = true,
+            LayerChange::Hide => self.visible = false,
+            LayerChange::Variant(variant) => {
+                self.variant = Some(variant.clone());
+                self.visible = true;
+            },
+        }
+    }
+}
+
+/// Project-defined positions, read from a `<name>.positions.json` in the characters folder
+#[derive(Asset, TypePath, Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct PositionsConfig {
+    /// Left edge of the sprite, in percentage of the screen width
+    pub characters: HashMap<String, f32>,
+    /// Center of the animation, written `[x, y]` in percentages of the screen
+    pub animations: HashMap<String, (f32, f32)>,
+}
+
+/// Movement of an actor from one position to another, in percentages of the screen
+#[derive(Debug, Clone)]
+pub(crate) struct ActorMovement {
+    pub entity: Entity,
+    pub from: (f32, f32),
+    pub to: (f32, f32),
+    /// Seconds since the movement started
+    pub elapsed: f32,
+    pub duration: f32,
+    pub easing: Easing,
+    /// Whether the script waits for the movement to end
+    pub blocking: bool,
+}
+
+/// Zoom of a character from one scale to another
+#[derive(Debug, Clone)]
+pub(crate) struct ActorZoom {
+    pub entity: Entity,
+    pub from: f32,
+    pub to: f32,
+    /// Seconds since the zoom started
+    pub elapsed: f32,
+    pub duration: f32,
+    pub easing: Easing,
+    /// Whether the script waits for the zoom to end
+    pub blocking: bool,
+}
+
+/// Fade of an actor, or of the previous sprite of a character changing emotion
+#[derive(Debug, Clone)]
+pub(crate) struct ActorFade {
+    pub entity: Entity,
+    pub from: f32,
+    pub to: f32,
+    /// Seconds since the fade started
+    pub elapsed: f32,
+    pub duration: f32,
+    pub easing: Easing,
+    /// Whether the entity is despawned once faded
+    pub despawn: bool,
+    /// Whether the script waits for the fade to end
+    pub blocking: bool,
+}
+
+/// Timing of a transition written in the script, e.g. `over 1.5s ease-out without waiting`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Tween {
+    /// Seconds, the default duration of the transition when unset
+    pub duration: Option<f32>,
+    pub easing: Easing,
+    pub blocking: bool,
+}
+
+impl Default for Tween {
+    fn default() -> Self {
+        Self { duration: None, easing: Easing::default(), blocking: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    Bounce,
+    Elastic,
+    Back,
+}
+
+impl Easing {
+    /// Eased progress of a transition, for a progress between 0 and 1
+    pub(crate) fn sample(self, progress: f32) -> f32 {
+        let function = match self {
+            Easing::Linear => EaseFunction::Linear,
+            Easing::EaseIn => EaseFunction::CubicIn,
+            Easing::EaseOut => EaseFunction::CubicOut,
+            Easing::EaseInOut => EaseFunction::CubicInOut,
+            Easing::Bounce => EaseFunction::BounceOut,
+            Easing::Elastic => EaseFunction::ElasticOut,
+            Easing::Back => EaseFunction::BackOut,
+        };
+        function.sample_clamped(progress)
+    }
+}
+
+impl TryFrom<&str> for Easing {
+    type Error = std::io::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            "linear" => Ok(Easing::Linear),
+            "ease-in" => Ok(Easing::EaseIn),
+            "ease-out" => Ok(Easing::EaseOut),
+            "ease-in-out" => Ok(Easing::EaseInOut),
+            "bounce" => Ok(Easing::Bounce),
+            "elastic" => Ok(Easing::Elastic),
+            "back" => Ok(Easing::Back),
+            other => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unexpected easing: {:?}", other),
+            ))
+        }
+    }
+}
+
+/// Drawing order written in the script, e.g. `moves to front` or `moves behind Kiyomi`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum DepthTarget {
+    Front,
+    Back,
+    Layer(i32),
+    Behind(String),
+    InFrontOf(String),
+    AboveTextbox,
+}
+
+impl DepthTarget {
+    /// Depth reached among the other actors on stage, given by name
+    pub(crate) fn resolve<'a>(&self, others: impl Iterator<Item = (&'a str, ActorDepth)>) -> Result<ActorDepth> {
+        let others: Vec<(&str, ActorDepth)> = others.collect();
+        let layers = others.iter().filter_map(|(_, depth)| match depth {
+            ActorDepth::Layer(layer) => Some(*layer),
+            ActorDepth::AboveTextbox => None,
+        });
+        let depth_of = |name: &str| others.iter().find(|(other, _)| *other == name).map(|(_, depth)| *depth)
+            .context(format!("Actor {} is not on stage", name));
+        Ok(match self {
+            DepthTarget::Front => ActorDepth::Layer(layers.max().map_or(0, |layer| layer + 1)),
+            DepthTarget::Back => ActorDepth::Layer(layers.min().map_or(0, |layer| layer - 1)),
+            DepthTarget::Layer(layer) => ActorDepth::Layer(*layer),
+            DepthTarget::Behind(name) => match depth_of(name)? {
+                ActorDepth::Layer(layer) => ActorDepth::Layer(layer - 1),
+                ActorDepth::AboveTextbox => anyhow::bail!("Actor {} is drawn above the textbox, move it back on stage first", name),
+            },
+            DepthTarget::InFrontOf(name) => match depth_of(name)? {
+                ActorDepth::Layer(layer) => ActorDepth::Layer(layer + 1),
+                ActorDepth::AboveTextbox => ActorDepth::AboveTextbox,
+            },
+            DepthTarget::AboveTextbox => ActorDepth::AboveTextbox,
+        })
+    }
+}
+
+/// Distance an actor moves from where it stands, in percentages of the screen
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ActorOffset {
+    /// Vertical offsets lift the character from the ground without changing their position
+    Character(f32, f32),
+    Animation(f32, f32),
+}
+
+impl ActorOffset {
+    /// Position reached from `base`, which is the default position of the actor when unset
+    pub(crate) fn moved_from(&self, base: Option<ActorPosition>) -> ActorPosition {
+        match (self, base) {
+            (ActorOffset::Character(x, _), Some(ActorPosition::Character(base))) => ActorPosition::Character(base.offset_by(*x)),
+            (ActorOffset::Character(x, _), _) => ActorPosition::Character(CharacterPosition::default().offset_by(*x)),
+            (ActorOffset::Animation(x, y), Some(ActorPosition::Animation(base))) => ActorPosition::Animation(base.offset_by(*x, *y)),
+            (ActorOffset::Animation(x, y), _) => ActorPosition::Animation(AnimationPosition::default().offset_by(*x, *y)),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum ActorOperation {
     Spawn(SpawnInfo), 
-    EmotionChange(String),
-    Despawn(bool), // fading
+    /// Crossfades over the given seconds when set, otherwise over the character's own duration
+    EmotionChange(String, Option<f32>),
+    OutfitChange(String),
+    /// Fades out with the given timing when set
+    Despawn(Option<Tween>),
     Look(ActorDirection),
-    Move(ActorPosition),
+    Move(ActorPosition, Tween),
+    MoveBy(ActorOffset, Tween),
+    LayerChange(String, LayerChange),
+    Reorder(DepthTarget),
+    /// Scales a character, `1` being their size from `character.json`
+    Zoom(f32, Tween),
 }
 pub(crate) enum ActorType {
     Character,
@@ -289,6 +699,12 @@ pub(crate) enum ActorType {
 }
 
 /* Messages */
+/// Sent by the chat when a character's line starts revealing in the textbox, and once it is fully shown
+#[derive(Message)]
+pub(crate) struct CharacterSpeakingMessage {
+    pub name: String,
+    pub speaking: bool,
+}
 #[derive(Message)]
 pub(crate) struct ActorChangeMessage {
     pub name: String,
@@ -299,18 +715,26 @@ pub(crate) struct CharacterController;
 impl Plugin for CharacterController {
     fn build(&self, app: &mut App) {
         app.insert_resource(MovingActors::default())
+            .insert_resource(ZoomingActors::default())
             .insert_resource(FadingActors::default())
             .insert_resource(CharFolderLoaded::default())
             .insert_resource(AnimFolderLoaded::default())
             .insert_resource(ActorsConfigs::default())
+            .insert_resource(LoadedCharacterConfigs::default())
             .insert_resource(ActorsResource::default())
+            .insert_resource(NamedPositions::default())
+            .insert_resource(FocusedCharacters::default())
             .add_message::<ActorChangeMessage>()
+            .add_message::<CharacterSpeakingMessage>()
             .init_state::<CharacterControllerState>()
             .add_systems(Update, wait_trigger)
             .add_systems(OnEnter(CharacterControllerState::Loading), import_assets)
+            .add_systems(OnEnter(CharacterControllerState::Running), spawn_actor_stage)
             .add_systems(Update, setup.run_if(in_state(CharacterControllerState::Loading)))
-            .add_systems(Update, (update_actors, apply_alpha, move_characters)
+            .add_systems(Update, (restore_actors.before(update_actors), update_actors)
                 .run_if(in_state(CharacterControllerState::Running)))
+            .add_systems(Update, (apply_alpha, move_characters, zoom_characters, run_sprite_animations, animate_speaking, focus_characters.after(apply_alpha), sync_character_layers.after(focus_characters))
+                .run_if(in_state(CharacterControllerState::Running).and(in_state(PauseState::Playing))))
             .add_systems(OnExit(CharacterControllerState::Running), clean_resources);
     }
 }
@@ -324,17 +748,27 @@ fn clean_resources(
 fn define_characters_map(
     commands: &mut Commands,
     actor_config_assets: &Res<Assets<ActorConfig>>,
+    positions_assets: &Res<Assets<PositionsConfig>>,
     loaded_folder: &LoadedFolder,
     actual_configs: &ResMut<ActorsConfigs>,
     sprite_resource: &mut ResMut<ActorsResource>,
 ) -> Result<(), BevyError> {
     
     let mut characters_sprites = CharacterSprites::new();
+    let mut layers_sprites = LayerSprites::new();
     let mut characters_configs = ActorsConfig::new();
+    let mut named_positions = PositionsConfig::default();
     
     let expected_len = PathBuf::from(CHARACTERS_ASSET_PATH).iter().count() + 3;
     
     for handle in &loaded_folder.handles {
+        if handle.type_id() == TypeId::of::<PositionsConfig>() {
+            let positions = positions_assets.get(&handle.clone().typed::<PositionsConfig>())
+                .context("Could not find named positions")?;
+            named_positions.characters.extend(positions.characters.clone());
+            named_positions.animations.extend(positions.animations.clone());
+            continue;
+        }
         let path = handle.path().context("Error retrieving character asset path")?.path();
         let name: String = match path.iter().nth(expected_len - 3).map(|s| s.to_string_lossy().into()) {
             Some(name) => name,
@@ -361,20 +795,35 @@ fn define_characters_map(
             
             characters_sprites.insert(key, handle.clone().typed());
             
+        } else if path.iter().count() == expected_len + 1 && path.iter().nth(expected_len - 2).is_some_and(|s| s == "layers") {
+            let layer = match path.iter().nth(expected_len - 1).map(|s| s.to_string_lossy().into()) {
+                Some(layer) => layer,
+                None => continue,
+            };
+            let variant = match path.file_stem() {
+                Some(stem) => stem.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            layers_sprites.insert(LayerKey { character: name, layer, variant }, handle.clone().typed());
         } else if path.iter().count() == expected_len - 1 {
-            characters_configs.insert(
-                name.clone(),
-                actor_config_assets
-                    .get(&handle.clone().typed::<ActorConfig>())
-                    .context(format!("Failed to retrieve CharacterConfig for '{}'", name))?
-                    .clone(),
-            );
+            let config = actor_config_assets
+                .get(&handle.clone().typed::<ActorConfig>())
+                .context(format!("Failed to retrieve CharacterConfig for '{}'", name))?;
+            if let ActorConfig::Character(character) = config {
+                character.validate()?;
+            }
+            characters_configs.insert(name.clone(), config.clone());
         }
     }
     for spr in characters_sprites {
         sprite_resource.0.insert(SpriteIdentifier::Character(spr.0), spr.1);
     }
+    for spr in layers_sprites {
+        sprite_resource.0.insert(SpriteIdentifier::Layer(spr.0), spr.1);
+    }
+    commands.insert_resource(LoadedCharacterConfigs(characters_configs.clone()));
     commands.insert_resource(ActorsConfigs(actual_configs.0.clone().into_iter().chain(characters_configs).collect()));
+    commands.insert_resource(NamedPositions(named_positions));
     Ok(())
 }
 fn define_animations_map(
@@ -417,6 +866,7 @@ fn setup(
     folder_char_handle: Res<HandleToCharactersFolder>,
     folder_anim_handle: Res<HandleToAnimationsFolder>,
     actor_config_asset: Res<Assets<ActorConfig>>,
+    positions_asset: Res<Assets<PositionsConfig>>,
     actual_configs: ResMut<ActorsConfigs>,
     mut sprite_resource: ResMut<ActorsResource>,
     mut char_folder_loaded: ResMut<CharFolderLoaded>,
@@ -431,7 +881,7 @@ fn setup(
             match state {
                 LoadState::Loaded => {
                     if let Some(loaded_folder) = loaded_folders.get(folder_char_handle.0.id()) {
-                        define_characters_map(&mut commands, &actor_config_asset, loaded_folder, &actual_configs, &mut sprite_resource)?;
+                        define_characters_map(&mut commands, &actor_config_asset, &positions_asset, loaded_folder, &actual_configs, &mut sprite_resource)?;
                         char_folder_loaded.0 = true;
                     } else {
                         return Err(anyhow::anyhow!("Error loading character assets").into());
@@ -479,6 +929,24 @@ fn import_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.insert_resource(HandleToCharactersFolder(loaded_char_folder));
     commands.insert_resource(HandleToAnimationsFolder(loaded_anim_folder));
 }
+fn spawn_actor_stage(
+    mut commands: Commands,
+    ui_root: Single<Entity, With<UiRoot>>,
+) {
+    let stage = commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: percent(100.),
+            height: percent(100.),
+            ..default()
+        },
+        ZIndex(CHARACTERS_Z_INDEX),
+        Pickable::IGNORE,
+        ActorStage,
+        DespawnOnExit(SabiState::Running),
+    )).id();
+    commands.entity(*ui_root).add_child(stage);
+}
 fn wait_trigger(
     mut msg_reader: MessageReader<ControllersSetStateMessage>,
     mut controller_state: ResMut<NextState<CharacterControllerState>>,
@@ -490,15 +958,20 @@ fn wait_trigger(
 fn exec_operation(
     actor_config: &mut ActorConfig,
     operation: &ActorOperation,
-    actor_query: &mut Query<(Entity, &mut ActorConfig, &mut ImageNode, Option<&mut AnimationTimer>, Option<&AnimationScale>)>,
+    actor_query: &mut Query<(Entity, &mut ActorConfig, &mut ImageNode, Option<&mut AnimationTimer>, Option<&AnimationScale>, &Node)>,
+    layer_query: &mut Query<(Entity, &ChildOf, &CharacterLayer, &mut ImageNode, &mut Visibility), Without<ActorConfig>>,
     mut commands: &mut Commands,
     mut fading_actors: &mut ResMut<FadingActors>,
     moving_actors: &mut ResMut<MovingActors>,
-    ui_root: &Single<Entity, With<UiRoot>>,
+    zooming_actors: &mut ResMut<ZoomingActors>,
+    depth_query: &mut Query<(&mut ActorDepth, &mut ZIndex)>,
+    zoom_query: &Query<&CharacterZoom>,
+    (stage, ui_root): (Entity, Entity),
     game_state: &mut ResMut<VisualNovelState>,
     actor_sprites: &Res<ActorsResource>,
     images: &Res<Assets<Image>>,
     texture_atlases: &mut ResMut<Assets<TextureAtlasLayout>>,
+    named_positions: &NamedPositions,
     window: &Window,
 ) -> Result<(), BevyError> {
     match operation {
@@ -514,13 +987,17 @@ fn exec_operation(
             if let ActorConfig::Character(c) = actor_config {
                 let emotion = if let Some(e) = &info.emotion { e.to_owned() } else { c.emotion.clone() };
                 c.emotion = emotion.clone();
+                if let Some(outfit) = &info.outfit {
+                    validate_outfit(c, actor_sprites, outfit)?;
+                    c.outfit = outfit.clone();
+                }
             }
-            spawn_actor(&mut commands, actor_config.clone(), &actor_sprites, &mut fading_actors, &ui_root, &images, info.clone(), texture_atlases, &window)?;
-            if info.fading {
+            spawn_actor(&mut commands, actor_config.clone(), &actor_sprites, &mut fading_actors, (stage, ui_root), &images, info.clone(), texture_atlases, named_positions, &window)?;
+            if info.fading && info.fade.blocking {
                 game_state.blocking = true;
             }
         },
-        ActorOperation::EmotionChange(emotion) => {
+        ActorOperation::EmotionChange(emotion, crossfade) => {
             let actor_config = if let ActorConfig::Character(c) = actor_config {
                 c
             } else { return Err(anyhow::anyhow!("Expected character config, found {:?}", actor_config).into()); };
@@ -538,7 +1015,98 @@ fn exec_operation(
                     return Ok(());
                 }
             };
+            actor_config.emotion = emotion.clone();
+            let crossfade = crossfade.or(actor_config.crossfade);
+            if let Some(layer) = &actor_config.emotion_layer {
+                change_layer(commands, fading_actors, entity.0, &mut entity.2, layer_query, actor_sprites, actor_config, layer, &LayerChange::Variant(emotion.clone()), crossfade)?;
+                return Ok(());
+            }
+            let previous = entity.2.clone();
             change_character_emotion(&mut entity.2, &actor_sprites, emotion, actor_config)?;
+            if previous.image != entity.2.image {
+                crossfade_sprite(commands, fading_actors, entity.0, previous, crossfade);
+            }
+        },
+        ActorOperation::OutfitChange(outfit) => {
+            let actor_config = if let ActorConfig::Character(c) = actor_config {
+                c
+            } else { return Err(anyhow::anyhow!("Expected character config, found {:?}", actor_config).into()); };
+            validate_outfit(actor_config, actor_sprites, outfit)?;
+            actor_config.outfit = outfit.clone();
+            let mut entity = match actor_query.iter_mut().find(|(_, actor_cfg, ..)| match &**actor_cfg {
+                ActorConfig::Animation(_) => false,
+                ActorConfig::Character(a) => a.name == actor_config.name
+            }) {
+                Some(e) => e,
+                None => {
+                    // The outfit is worn the next time the character appears
+                    return Ok(());
+                }
+            };
+            if let Some(layer) = &actor_config.outfit_layer {
+                change_layer(commands, fading_actors, entity.0, &mut entity.2, layer_query, actor_sprites, actor_config, layer, &LayerChange::Variant(outfit.clone()), actor_config.crossfade)?;
+                return Ok(());
+            }
+            let previous = entity.2.clone();
+            change_character_emotion(&mut entity.2, actor_sprites, &actor_config.emotion, actor_config)?;
+            if previous.image != entity.2.image {
+                crossfade_sprite(commands, fading_actors, entity.0, previous, actor_config.crossfade);
+            }
+        },
+        ActorOperation::LayerChange(layer, change) => {
+            let actor_config = if let ActorConfig::Character(c) = actor_config {
+                c
+            } else { return Err(anyhow::anyhow!("Expected character config, found {:?}", actor_config).into()); };
+            if actor_config.emotion_layer.as_ref() == Some(layer) && matches!(change, LayerChange::Variant(_)) {
+                return Err(anyhow::anyhow!("The {} layer of {} follows its emotion, change the emotion instead", layer, actor_config.name).into());
+            }
+            if actor_config.outfit_layer.as_ref() == Some(layer) && matches!(change, LayerChange::Variant(_)) {
+                return Err(anyhow::anyhow!("The {} layer of {} follows its outfit, change the outfit instead", layer, actor_config.name).into());
+            }
+            let mut entity = match actor_query.iter_mut().find(|(_, actor_cfg, ..)| match &**actor_cfg {
+                ActorConfig::Animation(_) => false,
+                ActorConfig::Character(a) => a.name == actor_config.name
+            }) {
+                Some(e) => e,
+                None => {
+                    let warn_message = format!("Character {} not found in the World!", actor_config.name);
+                    warn!(warn_message);
+                    return Ok(());
+                }
+            };
+            change_layer(commands, fading_actors, entity.0, &mut entity.2, layer_query, actor_sprites, actor_config, layer, change, actor_config.crossfade)?;
+        },
+        ActorOperation::Reorder(target) => {
+            // A character and an animation may share a name, only the one being reordered moves
+            let actors: Vec<(Entity, String, ActorDepth, bool)> = actor_query.iter()
+                .filter_map(|(entity, config, ..)| {
+                    let same = match (config, &actor_config) {
+                        (ActorConfig::Character(a), ActorConfig::Character(b)) => a.name == b.name,
+                        (ActorConfig::Animation(a), ActorConfig::Animation(b)) => a.name == b.name,
+                        _ => false
+                    };
+                    depth_query.get(entity).ok().map(|(depth, _)| (entity, config.name().to_owned(), *depth, same))
+                })
+                .collect();
+            let others = actors.iter()
+                .filter(|(.., same)| !same)
+                .map(|(_, other, depth, _)| (other.as_str(), *depth));
+            let depth = match target.resolve(others) {
+                Ok(depth) => depth,
+                Err(e) => {
+                    warn!("Could not reorder {}, keeping its current depth: {}", actor_config.name(), e);
+                    return Ok(());
+                }
+            };
+            for (entity, _, previous, _) in actors.iter().filter(|(.., same)| *same) {
+                let (mut actor_depth, mut z_index) = depth_query.get_mut(*entity)?;
+                *actor_depth = depth;
+                *z_index = depth.z_index();
+                let parent = depth.parent(stage, ui_root);
+                if parent != previous.parent(stage, ui_root) {
+                    commands.entity(parent).add_child(*entity);
+                }
+            }
         },
         ActorOperation::Despawn(fading) => {
             let entities = actor_query.iter().filter(|c| match (&c.1, &actor_config) {
@@ -546,11 +1114,13 @@ fn exec_operation(
                 (ActorConfig::Animation(a), ActorConfig::Animation(b)) => a.name == b.name,
                 _ => false
             });
-            if *fading {
+            if let Some(fade) = fading {
                 for entity in entities {
-                    fading_actors.0.push((entity.0, -0.01, true));
+                    start_fade(fading_actors, entity.0, entity.2.color.alpha(), 0., fade, true);
+                }
+                if fade.blocking {
+                    game_state.blocking = true;
                 }
-                game_state.blocking = true;
             } else {
                 for entity in entities {
                     commands.entity(entity.0).despawn();
@@ -563,38 +1133,72 @@ fn exec_operation(
                 (ActorConfig::Animation(a), ActorConfig::Animation(b)) => a.name == b.name,
                 _ => false
             });
-            for (_, _, mut image, _, _) in entities {
+            for (_, _, mut image, ..) in entities {
                 image.flip_x = direction == &ActorDirection::Left;
             }
         },
-        ActorOperation::Move(position) => {
+        ActorOperation::Move(position, tween) => {
             let entities = actor_query.iter().filter(|c| match (&c.1, &actor_config) {
                 (ActorConfig::Character(a), ActorConfig::Character(b)) => a.name == b.name,
                 (ActorConfig::Animation(a), ActorConfig::Animation(b)) => a.name == b.name,
                 _ => false
             });
-            for (entity, _, _, _, scale) in entities {
-                let (entity, target_position) = match position {
+            for (entity, _, _, _, scale, node) in entities {
+                let target_position = match position {
                     ActorPosition::Character(position) => {
-                        let target_position = position.to_percentage_value();
-                        (entity, (target_position, 0.))
+                        // Characters keep their height above the ground
+                        let bottom = match moving_actors.0.iter().find(|m| m.entity == entity) {
+                            Some(movement) => movement.to.1,
+                            None => node_position(node)?.1,
+                        };
+                        (position.to_percentage_value(named_positions)?, bottom)
                     },
                     ActorPosition::Animation(position) => {
                         let scale = if let Some(s) = scale { s } else { return Err(anyhow::anyhow!("Scale is not present among components").into()); };
                         let anim_config = if let ActorConfig::Animation(config) = &actor_config {
                             config
                         } else { return Err(anyhow::anyhow!("Expected animation config, found {:?}", actor_config).into()); };
-                        let target_position: (f32, f32) = position_relative_to_center(
-                            position.clone().into(),
+                        position_relative_to_center(
+                            position.coordinates(named_positions)?,
                             (anim_config.width, anim_config.height),
                             scale.0,
                             window,
-                        );
-                        (entity, target_position)
+                        )
                     }
                 };
-                moving_actors.0.push((entity, target_position));
-                game_state.blocking = true;
+                start_movement(moving_actors, entity, node, target_position, tween, game_state)?;
+            }
+        },
+        ActorOperation::Zoom(zoom, tween) => {
+            let ActorConfig::Character(character) = &actor_config else {
+                return Err(anyhow::anyhow!("Only characters can zoom, found {:?}", actor_config).into());
+            };
+            let entities = actor_query.iter().filter(|c| match &c.1 {
+                ActorConfig::Character(a) => a.name == character.name,
+                ActorConfig::Animation(_) => false,
+            });
+            for (entity, ..) in entities {
+                let from = zoom_query.get(entity)?.0;
+                start_zoom(zooming_actors, entity, from, *zoom, tween, game_state);
+            }
+        },
+        ActorOperation::MoveBy(offset, tween) => {
+            let (offset_x, offset_y) = match offset {
+                ActorOffset::Character(x, y) => (*x, *y),
+                ActorOffset::Animation(x, y) => (*x, *y),
+            };
+            let entities = actor_query.iter().filter(|c| match (&c.1, &actor_config) {
+                (ActorConfig::Character(a), ActorConfig::Character(b)) => a.name == b.name,
+                (ActorConfig::Animation(a), ActorConfig::Animation(b)) => a.name == b.name,
+                _ => false
+            });
+            for (entity, .., node) in entities {
+                // Offsets add up with the movement in progress
+                let (left, bottom) = match moving_actors.0.iter().find(|m| m.entity == entity) {
+                    Some(movement) => movement.to,
+                    None => node_position(node)?,
+                };
+                start_movement(moving_actors, entity, node, (left + offset_x, bottom + offset_y), tween, game_state)?;
             }
         }
     }
@@ -602,17 +1206,20 @@ fn exec_operation(
 }
 fn update_actors(
     mut commands: Commands,
-    mut actor_query: Query<(Entity, &mut ActorConfig, &mut ImageNode, Option<&mut AnimationTimer>, Option<&AnimationScale>)>,
+    mut actor_query: Query<(Entity, &mut ActorConfig, &mut ImageNode, Option<&mut AnimationTimer>, Option<&AnimationScale>, &Node)>,
+    mut layer_query: Query<(Entity, &ChildOf, &CharacterLayer, &mut ImageNode, &mut Visibility), Without<ActorConfig>>,
+    (mut depth_query, zoom_query): (Query<(&mut ActorDepth, &mut ZIndex)>, Query<&CharacterZoom>),
+    stage: Single<Entity, With<ActorStage>>,
     ui_root: Single<Entity, With<UiRoot>>,
     actor_sprites: Res<ActorsResource>,
     mut actor_configs: ResMut<ActorsConfigs>,
     mut fading_actors: ResMut<FadingActors>,
-    mut moving_actors: ResMut<MovingActors>,
+    (mut moving_actors, mut zooming_actors): (ResMut<MovingActors>, ResMut<ZoomingActors>),
     mut actor_change_message: MessageReader<ActorChangeMessage>,
     mut game_state: ResMut<VisualNovelState>,
     images: Res<Assets<Image>>,
     mut texture_atlases: ResMut<Assets<TextureAtlasLayout>>,
-    time: Res<Time>,
+    named_positions: Res<NamedPositions>,
     window: Query<&Window, With<PrimaryWindow>>,
 ) -> Result<(), BevyError> {
     
@@ -620,24 +1227,154 @@ fn update_actors(
     
     for msg in actor_change_message.read() {
         let actor_config = actor_configs.0.get_mut(&msg.name).context(format!("Actor config not found for {}", &msg.name))?;
-        exec_operation(actor_config, &msg.operation, &mut actor_query, &mut commands, &mut fading_actors, &mut moving_actors, &ui_root, &mut game_state, &actor_sprites, &images, &mut texture_atlases, window)?;
+        exec_operation(actor_config, &msg.operation, &mut actor_query, &mut layer_query, &mut commands, &mut fading_actors, &mut moving_actors, &mut zooming_actors, &mut depth_query, &zoom_query, (*stage, *ui_root), &mut game_state, &actor_sprites, &images, &mut texture_atlases, &named_positions, window)?;
     }
-    
-    for (_, config, mut image, mut timer, _) in actor_query {
-        if let ActorConfig::Animation(animation_config) = &*config {
-            if let Some(timer) = &mut timer {
-                timer.0.tick(time.delta());
-                if timer.0.just_finished() {
-                    if let Some(atlas) = &mut image.texture_atlas {
-                        let next_index = atlas.index + 1;
-                        atlas.index = if next_index > animation_config.end_index {
-                            animation_config.start_index
-                        } else { next_index };
-                    }
-                }
+
+    Ok(())
+}
+fn run_sprite_animations(
+    actor_query: Query<(&ActorConfig, &mut ImageNode, &mut AnimationTimer)>,
+    time: Res<Time>,
+) {
+    for (config, mut image, mut timer) in actor_query {
+        if let ActorConfig::Animation(animation_config) = config {
+            timer.0.tick(time.delta());
+            if timer.0.just_finished() && let Some(atlas) = &mut image.texture_atlas {
+                let next_index = atlas.index + 1;
+                atlas.index = if next_index > animation_config.end_index {
+                    animation_config.start_index
+                } else { next_index };
             }
         }
     }
+}
+/// Rebuilds the actors recorded in a [StageRestoreMessage], without fading nor moving them.
+#[allow(clippy::too_many_arguments)]
+fn restore_actors(
+    mut commands: Commands,
+    mut restore_message: MessageReader<StageRestoreMessage>,
+    actor_query: Query<Entity, With<Actor>>,
+    stage: Single<Entity, With<ActorStage>>,
+    ui_root: Single<Entity, With<UiRoot>>,
+    actor_sprites: Res<ActorsResource>,
+    mut actor_configs: ResMut<ActorsConfigs>,
+    loaded_configs: Res<LoadedCharacterConfigs>,
+    mut fading_actors: ResMut<FadingActors>,
+    mut moving_actors: ResMut<MovingActors>,
+    mut zooming_actors: ResMut<ZoomingActors>,
+    images: Res<Assets<Image>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlasLayout>>,
+    named_positions: Res<NamedPositions>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) -> Result<(), BevyError> {
+    let window = window.single().context("Could not retrieve window entity")?;
+
+    for msg in restore_message.read() {
+        fading_actors.0.clear();
+        moving_actors.0.clear();
+        zooming_actors.0.clear();
+        for entity in actor_query {
+            commands.entity(entity).despawn();
+        }
+
+        // Outfits and emotions changed after the snapshot, on stage or not, must not carry over
+        for (name, config) in actor_configs.0.iter_mut() {
+            let (ActorConfig::Character(c), Some(ActorConfig::Character(loaded))) = (config, loaded_configs.0.get(name)) else {
+                continue;
+            };
+            c.emotion = msg.0.emotions.get(name).unwrap_or(&loaded.emotion).clone();
+            c.outfit = msg.0.outfits.get(name).unwrap_or(&loaded.outfit).clone();
+        }
+
+        for actor in &msg.0.actors {
+            let actor_config = actor_configs.0.get(&actor.name).context(format!("Actor config not found for {}", &actor.name))?;
+            spawn_actor(&mut commands, actor_config.clone(), &actor_sprites, &mut fading_actors, (*stage, *ui_root), &images, actor.info.clone(), &mut texture_atlases, &named_positions, window)?;
+        }
+        info!("[ Restored {} actors ]", msg.0.actors.len());
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_starts_from_the_default_position() {
+        assert_eq!(
+            ActorOffset::Character(10., 0.).moved_from(None),
+            ActorPosition::Character(CharacterPosition::Offset(Box::new(CharacterPosition::Center), 10.))
+        );
+        assert_eq!(
+            ActorOffset::Animation(0., -5.).moved_from(None),
+            ActorPosition::Animation(AnimationPosition::Offset(Box::new(AnimationPosition::Center), 0., -5.))
+        );
+    }
+
+    #[test]
+    fn offsets_add_up_on_the_same_base() {
+        let first = ActorOffset::Character(10., 0.).moved_from(Some(ActorPosition::Character(CharacterPosition::Left)));
+        assert_eq!(
+            ActorOffset::Character(-4., 0.).moved_from(Some(first)),
+            ActorPosition::Character(CharacterPosition::Offset(Box::new(CharacterPosition::Left), 6.))
+        );
+
+        let first = ActorOffset::Animation(5., 5.).moved_from(Some(ActorPosition::Animation(AnimationPosition::Named("window".into()))));
+        assert_eq!(
+            ActorOffset::Animation(1., -2.).moved_from(Some(first)),
+            ActorPosition::Animation(AnimationPosition::Offset(Box::new(AnimationPosition::Named("window".into())), 6., 3.))
+        );
+    }
+
+    #[test]
+    fn offset_ignores_a_position_of_the_other_actor_type() {
+        assert_eq!(
+            ActorOffset::Character(10., 0.).moved_from(Some(ActorPosition::Animation(AnimationPosition::Top))),
+            ActorPosition::Character(CharacterPosition::Offset(Box::new(CharacterPosition::Center), 10.))
+        );
+    }
+
+    #[test]
+    fn layer_variants_show_the_layer() {
+        let mut setting = LayerSetting::default();
+        setting.apply(&LayerChange::Variant("smiling".into()));
+        assert_eq!(setting, LayerSetting { variant: Some("smiling".into()), visible: true });
+
+        setting.apply(&LayerChange::Hide);
+        assert_eq!(setting, LayerSetting { variant: Some("smiling".into()), visible: false });
+        setting.apply(&LayerChange::Show);
+        assert!(setting.visible);
+    }
+
+    fn stage() -> Vec<(&'static str, ActorDepth)> {
+        vec![("Nayu", ActorDepth::Layer(2)), ("Kiyomi", ActorDepth::Layer(-1)), ("rain", ActorDepth::AboveTextbox)]
+    }
+
+    #[test]
+    fn front_and_back_go_past_the_stage_layers() {
+        assert_eq!(DepthTarget::Front.resolve(stage().into_iter()).unwrap(), ActorDepth::Layer(3));
+        assert_eq!(DepthTarget::Back.resolve(stage().into_iter()).unwrap(), ActorDepth::Layer(-2));
+        assert_eq!(DepthTarget::Front.resolve(std::iter::empty()).unwrap(), ActorDepth::Layer(0));
+    }
+
+    #[test]
+    fn depth_relative_to_another_actor() {
+        assert_eq!(DepthTarget::Behind("Nayu".into()).resolve(stage().into_iter()).unwrap(), ActorDepth::Layer(1));
+        assert_eq!(DepthTarget::InFrontOf("Kiyomi".into()).resolve(stage().into_iter()).unwrap(), ActorDepth::Layer(0));
+        assert_eq!(DepthTarget::InFrontOf("rain".into()).resolve(stage().into_iter()).unwrap(), ActorDepth::AboveTextbox);
+    }
+
+    #[test]
+    fn depth_fails_against_an_unknown_or_above_textbox_actor() {
+        assert!(DepthTarget::Behind("Haru".into()).resolve(stage().into_iter()).is_err());
+        assert!(DepthTarget::InFrontOf("Haru".into()).resolve(stage().into_iter()).is_err());
+        assert!(DepthTarget::Behind("rain".into()).resolve(stage().into_iter()).is_err());
+    }
+
+    #[test]
+    fn explicit_depths_ignore_the_stage() {
+        assert_eq!(DepthTarget::Layer(-4).resolve(stage().into_iter()).unwrap(), ActorDepth::Layer(-4));
+        assert_eq!(DepthTarget::AboveTextbox.resolve(std::iter::empty()).unwrap(), ActorDepth::AboveTextbox);
+    }
+}
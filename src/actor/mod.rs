@@ -5,4 +5,5 @@ pub(crate) use controller::ActorChangeMessage;
 pub(crate) use controller::CharacterConfig;
 pub(crate) use controller::CharacterController;
 pub(crate) use controller::ActorOperation;
+pub(crate) use operations::{CharacterZoom, CHARACTER_HEIGHT_PERCENTAGE};
 
@@ -1,23 +1,58 @@
-use std::{ops::Index, time::Duration};
+use std::{collections::HashMap, time::Duration};
 use anyhow::Context;
 use bevy::prelude::*;
 use crate::{
     VisualNovelState,
+    chat::{CharacterSayMessage, controller::{InfoTextMessage, SpeechBubbleMessage}},
+    settings::Settings,
     actor::{
         CharacterConfig,
         controller::{
-            ActorConfig, ActorPosition, ActorsResource, AnimationPosition, AnimationScale, AnimationTimer, ActorDirection, CharacterPosition, FadingActors, MovingActors, SpawnInfo, SpriteIdentifier, SpriteKey
+            ActorConfig, ActorPosition, ActorFade, ActorMovement, ActorsConfigs, ActorsResource, AnimationPosition, AnimationScale, AnimationTimer, ActorDirection, CharacterPosition, CharacterSpeakingMessage, Easing, FadingActors, FocusedCharacters, LayerChange, LayerConfig, LayerKey, LayerSetting, ActorZoom, MovingActors, NamedPositions, SpawnInfo, SpeakingStyle, SpriteIdentifier, SpriteKey, Tween, ZoomingActors
         }
     },
-    compiler::controller::SabiState
+    compiler::{controller::SabiState, rollback::StageRestoreMessage}
 };
-use crate::compiler::controller::UiRoot;
 
-const MOVEMENT_STEP: f32 = 0.4;
-const CHARACTERS_Z_INDEX: i32 = 3;
+/// Speed of the movements without a duration, in percentage of the screen per second
+const MOVEMENT_SPEED: f32 = 24.;
+/// Seconds of the fades without a duration
+const FADE_DURATION: f32 = 1.5;
+/// Seconds the previous sprite of a character takes to dissolve into the new emotion,
+/// unless the character or the script sets its own
+const EMOTION_CROSSFADE_DURATION: f32 = 0.3;
+/// Seconds between two mouth movements of the characters flapping their lips
+const LIP_FLAP_INTERVAL: f32 = 0.15;
+/// Seconds a character takes to get in or out of focus
+const FOCUS_DURATION: f32 = 0.25;
+/// Brightness and scale of the characters out of focus
+const UNFOCUSED_BRIGHTNESS: f32 = 0.55;
+const UNFOCUSED_SCALE: f32 = 0.96;
+/// Seconds of the zooms without a duration
+const ZOOM_DURATION: f32 = 0.5;
+/// Height of the character sprites without their own, in percentage of the screen
+pub(crate) const CHARACTER_HEIGHT_PERCENTAGE: f32 = 75.;
 
 #[derive(Component)]
 pub struct Actor;
+/// Previous sprite of a character or of one of its layers, dissolving over the new one
+#[derive(Component)]
+pub(in crate::actor) struct SpriteCrossfade;
+/// Sprite layer stacked over a layered character, following its alpha and facing
+#[derive(Component)]
+pub(in crate::actor) struct CharacterLayer(pub String);
+/// How much a character is in focus, from 0 when dimmed to 1 when highlighted
+#[derive(Component)]
+pub(in crate::actor) struct CharacterFocus(f32);
+/// Scale of a character set by the script, combined with the focus scale
+#[derive(Component)]
+pub(crate) struct CharacterZoom(pub f32);
+/// Lips of a character flapping while their line reveals
+#[derive(Component)]
+pub(in crate::actor) struct LipFlap {
+    timer: Timer,
+    open: bool,
+}
 
 pub(in crate::actor) fn position_relative_to_center(
     (left, bottom): (f32, f32),
@@ -47,113 +82,514 @@ pub fn change_character_emotion(
    
    Ok(())
 }
+/// Checks the character lists the outfit and has sprites loaded for it
+pub(in crate::actor) fn validate_outfit(
+    config: &CharacterConfig,
+    sprites: &ActorsResource,
+    outfit: &str,
+) -> Result<(), BevyError> {
+    if !config.outfits.iter().any(|o| o == outfit) {
+        return Err(anyhow::anyhow!("Character {} does not have {} outfit!", config.name, outfit).into());
+    }
+    let loaded = sprites.0.keys().any(|key| match key {
+        SpriteIdentifier::Character(key) => key.character == config.name && key.outfit == outfit,
+        SpriteIdentifier::Layer(key) => key.character == config.name
+            && config.outfit_layer.as_ref() == Some(&key.layer)
+            && key.variant == outfit,
+        SpriteIdentifier::Animation(_) => false,
+    });
+    if !loaded {
+        return Err(anyhow::anyhow!("No sprites loaded for the {} outfit of {}", outfit, config.name).into());
+    }
+    Ok(())
+}
+pub(in crate::actor) fn layer_sprite(
+    sprites: &ActorsResource,
+    config: &CharacterConfig,
+    layer: &str,
+    variant: &str,
+) -> Result<Handle<Image>, BevyError> {
+    let layer_key = LayerKey {
+        character: config.name.clone(),
+        layer: layer.to_owned(),
+        variant: variant.to_owned(),
+    };
+    let sprite = sprites.0.get(&SpriteIdentifier::Layer(layer_key.clone())).context(format!("Sprite not found for {:?}", layer_key))?;
+    Ok(sprite.clone())
+}
+/// Variant and visibility of a layer when the character appears.
+/// The emotion and outfit layers always show the emotion and outfit of the character
+fn initial_layer_state(
+    config: &CharacterConfig,
+    layer: &LayerConfig,
+    settings: &HashMap<String, LayerSetting>,
+) -> (String, bool) {
+    let setting = settings.get(&layer.name);
+    let variant = if config.emotion_layer.as_ref() == Some(&layer.name) {
+        config.emotion.clone()
+    } else if config.outfit_layer.as_ref() == Some(&layer.name) {
+        config.outfit.clone()
+    } else {
+        setting.and_then(|s| s.variant.clone()).unwrap_or_else(|| layer.default.clone())
+    };
+    (variant, setting.map_or(!layer.hidden, |s| s.visible))
+}
+/// Shows, hides or switches a layer of a character, dissolving the previous sprite over `crossfade` seconds.
+/// The bottom layer is drawn by the character node itself and can't be hidden
+#[allow(clippy::too_many_arguments)]
+pub(in crate::actor) fn change_layer(
+    commands: &mut Commands,
+    fading_actors: &mut FadingActors,
+    actor: Entity,
+    actor_image: &mut ImageNode,
+    layer_query: &mut Query<(Entity, &ChildOf, &CharacterLayer, &mut ImageNode, &mut Visibility), Without<ActorConfig>>,
+    sprites: &ActorsResource,
+    config: &CharacterConfig,
+    layer: &str,
+    change: &LayerChange,
+    crossfade: Option<f32>,
+) -> Result<(), BevyError> {
+    let index = config.layers.iter().position(|l| l.name == layer)
+        .context(format!("Character {} has no {} layer", config.name, layer))?;
+    if index == 0 {
+        let LayerChange::Variant(variant) = change else {
+            return Err(anyhow::anyhow!("The {} layer of {} can't be hidden", layer, config.name).into());
+        };
+        let previous = actor_image.clone();
+        actor_image.image = layer_sprite(sprites, config, layer, variant)?;
+        if previous.image != actor_image.image {
+            crossfade_sprite(commands, fading_actors, actor, previous, crossfade);
+        }
+        return Ok(());
+    }
+
+    let (entity, _, _, mut image, mut visibility) = layer_query.iter_mut()
+        .find(|(_, parent, l, ..)| parent.parent() == actor && l.0 == layer)
+        .context(format!("Layer {} of {} not found in the World", layer, config.name))?;
+    match change {
+        LayerChange::Show => *visibility = Visibility::Inherited,
+        LayerChange::Hide => *visibility = Visibility::Hidden,
+        LayerChange::Variant(variant) => {
+            let previous = image.clone();
+            image.image = layer_sprite(sprites, config, layer, variant)?;
+            // A hidden layer has nothing to dissolve
+            if previous.image != image.image && *visibility != Visibility::Hidden {
+                crossfade_sprite(commands, fading_actors, entity, previous, crossfade);
+            }
+            *visibility = Visibility::Inherited;
+        },
+    }
+    Ok(())
+}
+/// Switches the characters whose lines reveal to their speaking variant, or flaps their lips
+pub fn animate_speaking(
+    mut commands: Commands,
+    mut speaking_messages: MessageReader<CharacterSpeakingMessage>,
+    mut actor_query: Query<(Entity, &ActorConfig, &mut ImageNode, Option<&mut LipFlap>), With<Actor>>,
+    mut layer_query: Query<(&ChildOf, &CharacterLayer, &mut ImageNode), Without<Actor>>,
+    actor_configs: Res<ActorsConfigs>,
+    sprites: Res<ActorsResource>,
+    time: Res<Time>,
+) {
+    // Flapped first so a line ending this frame leaves the mouth closed
+    for (entity, actor_config, mut image, lip_flap) in &mut actor_query {
+        let (Some(mut lip_flap), ActorConfig::Character(character)) = (lip_flap, actor_config) else {
+            continue;
+        };
+        if !lip_flap.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        lip_flap.open = !lip_flap.open;
+        // The emotion of the character is kept up to date in its configuration resource
+        if let Some(ActorConfig::Character(config)) = actor_configs.0.get(&character.name) {
+            show_mouth(entity, &mut image, &mut layer_query, &sprites, config, lip_flap.open);
+        }
+    }
+
+    for msg in speaking_messages.read() {
+        let Some(ActorConfig::Character(config)) = actor_configs.0.get(&msg.name) else {
+            continue;
+        };
+        let Some(style) = config.speaking else {
+            continue;
+        };
+        let Some((entity, _, mut image, _)) = actor_query.iter_mut()
+            .find(|(_, actor_config, ..)| matches!(actor_config, ActorConfig::Character(c) if c.name == config.name)) else {
+            continue;
+        };
+        if msg.speaking && style == SpeakingStyle::Flap {
+            commands.entity(entity).insert(LipFlap {
+                timer: Timer::from_seconds(LIP_FLAP_INTERVAL, TimerMode::Repeating),
+                open: true,
+            });
+        } else {
+            commands.entity(entity).remove::<LipFlap>();
+        }
+        show_mouth(entity, &mut image, &mut layer_query, &sprites, config, msg.speaking);
+    }
+}
+/// Shows the emotion of the character, or its `<emotion>speaking` variant while the mouth is open.
+/// Emotions without a speaking variant keep their sprite
+fn show_mouth(
+    actor: Entity,
+    actor_image: &mut ImageNode,
+    layer_query: &mut Query<(&ChildOf, &CharacterLayer, &mut ImageNode), Without<Actor>>,
+    sprites: &ActorsResource,
+    config: &CharacterConfig,
+    open: bool,
+) {
+    let emotion = if open { format!("{}speaking", config.emotion) } else { config.emotion.clone() };
+    let Some(layer) = &config.emotion_layer else {
+        let sprite_key = SpriteKey {
+            character: config.name.clone(),
+            outfit: config.outfit.clone(),
+            emotion,
+        };
+        if let Some(sprite) = sprites.0.get(&SpriteIdentifier::Character(sprite_key)) {
+            actor_image.image = sprite.clone();
+        }
+        return;
+    };
+    let Ok(sprite) = layer_sprite(sprites, config, layer, &emotion) else {
+        return;
+    };
+    if config.layers.first().is_some_and(|base| &base.name == layer) {
+        actor_image.image = sprite;
+    } else if let Some((.., mut image)) = layer_query.iter_mut().find(|(parent, l, _)| parent.parent() == actor && &l.0 == layer) {
+        image.image = sprite;
+    }
+}
+/// Highlights the speaking characters by dimming and shrinking the other characters on stage
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn focus_characters(
+    mut say_messages: MessageReader<CharacterSayMessage>,
+    mut bubble_messages: MessageReader<SpeechBubbleMessage>,
+    mut info_text_messages: MessageReader<InfoTextMessage>,
+    mut restore_messages: MessageReader<StageRestoreMessage>,
+    mut focused: ResMut<FocusedCharacters>,
+    mut query: Query<(Entity, &ActorConfig, &mut CharacterFocus, &CharacterZoom, &mut ImageNode, &mut UiTransform), With<Actor>>,
+    mut overlay_query: Query<(&ChildOf, &mut ImageNode), (With<SpriteCrossfade>, Without<Actor>)>,
+    layer_query: Query<&ChildOf, With<CharacterLayer>>,
+    game_state: Res<VisualNovelState>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    // Lines said together arrive at once, and all of their speakers are in focus
+    let speakers: Vec<String> = say_messages.read().map(|msg| msg.name.clone())
+        .chain(bubble_messages.read().flat_map(|msg| msg.lines.iter().map(|line| line.name.clone())))
+        .collect();
+    if !speakers.is_empty() {
+        focused.0 = Some(speakers);
+    }
+    // Narration and restored stages put every character back in focus
+    if info_text_messages.read().count() > 0 || restore_messages.read().count() > 0 {
+        focused.0 = None;
+    }
+
+    let enabled = game_state.stage.focus.unwrap_or(settings.auto_focus);
+    let on_stage = query.iter().filter(|(_, config, ..)| matches!(config, ActorConfig::Character(_))).count();
+    for (entity, config, mut focus, zoom, mut image, mut transform) in &mut query {
+        let ActorConfig::Character(character) = config else {
+            continue;
+        };
+        let in_focus = !enabled || on_stage < 2 || focused.0.as_ref().is_none_or(|names| names.contains(&character.name));
+        let step = time.delta_secs() / FOCUS_DURATION;
+        let level = if in_focus { (focus.0 + step).min(1.) } else { (focus.0 - step).max(0.) };
+        if (level - focus.0).abs() < f32::EPSILON {
+            continue;
+        }
+        // Relative to the previous brightness, so a tint set on the character is kept
+        let ratio = focus_brightness(level) / focus_brightness(focus.0);
+        focus.0 = level;
+
+        dim_color(&mut image.color, ratio);
+        // The previous sprites still dissolving, over the character or one of its layers
+        for (parent, mut overlay) in &mut overlay_query {
+            let owner = layer_query.get(parent.parent()).map(ChildOf::parent).unwrap_or(parent.parent());
+            if owner == entity {
+                dim_color(&mut overlay.color, ratio);
+            }
+        }
+        scale_character(&mut transform, zoom.0, level);
+    }
+}
+fn focus_brightness(focus: f32) -> f32 {
+    UNFOCUSED_BRIGHTNESS + (1. - UNFOCUSED_BRIGHTNESS) * Easing::EaseInOut.sample(focus)
+}
+/// Scales the colour channels, leaving the alpha to the fades
+fn dim_color(color: &mut Color, ratio: f32) {
+    let srgba = color.to_srgba();
+    *color = Color::srgba(srgba.red * ratio, srgba.green * ratio, srgba.blue * ratio, srgba.alpha);
+}
+/// Scales a character by their zoom and their focus, keeping their feet where they stand
+fn scale_character(transform: &mut UiTransform, zoom: f32, focus: f32) {
+    let eased = Easing::EaseInOut.sample(focus);
+    let scale = zoom * (UNFOCUSED_SCALE + (1. - UNFOCUSED_SCALE) * eased);
+    transform.scale = Vec2::splat(scale);
+    // Scales around the center, so the sprite is moved back to the ground
+    transform.translation = Val2::percent(0., (1. - scale) * 50.);
+}
+/// Keeps the layers of every character as transparent, tinted and facing the same way as the character
+#[allow(clippy::type_complexity)]
+pub fn sync_character_layers(
+    actor_query: Query<&ImageNode, (With<Actor>, Changed<ImageNode>)>,
+    mut layer_query: Query<(&ChildOf, &mut ImageNode), (With<CharacterLayer>, Without<Actor>)>,
+) {
+    for (parent, mut image) in &mut layer_query {
+        let Ok(actor) = actor_query.get(parent.parent()) else {
+            continue;
+        };
+        image.color = actor.color;
+        image.flip_x = actor.flip_x;
+    }
+}
 pub fn move_characters(
-    query: Query<(Entity, &mut Node), With<Actor>>,
+    mut query: Query<&mut Node, With<Actor>>,
     mut moving_actors: ResMut<MovingActors>,
     mut game_state: ResMut<VisualNovelState>,
+    time: Res<Time>,
 ) {
-    for (entity, mut node) in query {
-        let enumerated_element = moving_actors.0.iter().enumerate().find(|(_, e)| e.0 == entity);
-        if let Some((index, target_pos)) = enumerated_element {
-            let new_coords: (f32, f32) = match (node.left, node.bottom) {
-                (Val::Percent(left), Val::Percent(btm)) => {
-                    let new_left = if (left - target_pos.1.0).abs() < MOVEMENT_STEP {
-                        target_pos.1.0
-                    } else if left < target_pos.1.0 {
-                        left + MOVEMENT_STEP
-                    } else { left - MOVEMENT_STEP };
-                    let new_bottom = if (btm - target_pos.1.1).abs() < MOVEMENT_STEP {
-                        target_pos.1.1
-                    } else if btm < target_pos.1.1 {
-                        btm + MOVEMENT_STEP
-                    } else { btm - MOVEMENT_STEP };
-                    
-                    (new_left, new_bottom)
-                },
-                _ => {
-                    warn!("Movement directives accept only actors with percentage value as position!");
-                    moving_actors.0.remove(index);
-                    if moving_actors.0.is_empty() {
-                        game_state.blocking = false;
-                        return;
-                    }
-                    continue;
-                }
-            };
-            node.left = percent(new_coords.0);
-            node.bottom = percent(new_coords.1);
-            if new_coords == target_pos.1 {
-                moving_actors.0.remove(index);
-            }
-            if moving_actors.0.is_empty() {
-                game_state.blocking = false;
-                return;
-            }
+    if moving_actors.0.is_empty() {
+        return;
+    }
+
+    let mut finished_blocking = false;
+    moving_actors.0.retain_mut(|movement| {
+        let Ok(mut node) = query.get_mut(movement.entity) else {
+            // The actor was despawned while moving
+            finished_blocking |= movement.blocking;
+            return false;
+        };
+        movement.elapsed += time.delta_secs();
+        let progress = if movement.duration > 0. { (movement.elapsed / movement.duration).min(1.) } else { 1. };
+        let eased = movement.easing.sample(progress);
+        node.left = percent(movement.from.0 + (movement.to.0 - movement.from.0) * eased);
+        node.bottom = percent(movement.from.1 + (movement.to.1 - movement.from.1) * eased);
+        if progress >= 1. {
+            finished_blocking |= movement.blocking;
+            return false;
         }
+        true
+    });
+
+    if finished_blocking && !moving_actors.0.iter().any(|m| m.blocking) {
+        game_state.blocking = false;
+    }
+}
+pub fn zoom_characters(
+    mut query: Query<(&mut CharacterZoom, &CharacterFocus, &mut UiTransform), With<Actor>>,
+    mut zooming_actors: ResMut<ZoomingActors>,
+    mut game_state: ResMut<VisualNovelState>,
+    time: Res<Time>,
+) {
+    if zooming_actors.0.is_empty() {
+        return;
+    }
+
+    let mut finished_blocking = false;
+    zooming_actors.0.retain_mut(|zooming| {
+        let Ok((mut zoom, focus, mut transform)) = query.get_mut(zooming.entity) else {
+            // The character was despawned while zooming
+            finished_blocking |= zooming.blocking;
+            return false;
+        };
+        zooming.elapsed += time.delta_secs();
+        let progress = if zooming.duration > 0. { (zooming.elapsed / zooming.duration).min(1.) } else { 1. };
+        zoom.0 = zooming.from + (zooming.to - zooming.from) * zooming.easing.sample(progress);
+        scale_character(&mut transform, zoom.0, focus.0);
+        if progress >= 1. {
+            finished_blocking |= zooming.blocking;
+            return false;
+        }
+        true
+    });
+
+    if finished_blocking && !zooming_actors.0.iter().any(|z| z.blocking) {
+        game_state.blocking = false;
+    }
+}
+/// Zooms the character from their current scale, replacing the zoom they may be in
+pub(in crate::actor) fn start_zoom(
+    zooming_actors: &mut ZoomingActors,
+    entity: Entity,
+    from: f32,
+    to: f32,
+    tween: &Tween,
+    game_state: &mut VisualNovelState,
+) {
+    zooming_actors.0.retain(|z| z.entity != entity);
+    zooming_actors.0.push(ActorZoom {
+        entity,
+        from,
+        to,
+        elapsed: 0.,
+        duration: tween.duration.unwrap_or(ZOOM_DURATION),
+        easing: tween.easing,
+        blocking: tween.blocking,
+    });
+    if tween.blocking {
+        game_state.blocking = true;
+    }
+}
+/// Moves the actor from where it stands, replacing the movement it may be in
+pub(in crate::actor) fn start_movement(
+    moving_actors: &mut MovingActors,
+    entity: Entity,
+    node: &Node,
+    to: (f32, f32),
+    tween: &Tween,
+    game_state: &mut VisualNovelState,
+) -> Result<(), BevyError> {
+    let from = node_position(node)?;
+    moving_actors.0.retain(|m| m.entity != entity);
+    let duration = tween.duration.unwrap_or((to.0 - from.0).hypot(to.1 - from.1) / MOVEMENT_SPEED);
+    moving_actors.0.push(ActorMovement {
+        entity,
+        from,
+        to,
+        elapsed: 0.,
+        duration,
+        easing: tween.easing,
+        blocking: tween.blocking,
+    });
+    if tween.blocking {
+        game_state.blocking = true;
+    }
+    Ok(())
+}
+pub(in crate::actor) fn node_position(node: &Node) -> Result<(f32, f32), BevyError> {
+    match (node.left, node.bottom) {
+        (Val::Percent(left), Val::Percent(bottom)) => Ok((left, bottom)),
+        _ => Err(anyhow::anyhow!("Movement directives accept only actors with percentage value as position!").into()),
     }
 }
+#[allow(clippy::type_complexity)]
 pub fn apply_alpha(
     mut commands: Commands,
-    mut query: Query<&mut ImageNode, With<Actor>>,
+    mut query: Query<&mut ImageNode, Or<(With<Actor>, With<SpriteCrossfade>)>>,
     mut fading_actors: ResMut<FadingActors>,
     mut game_state: ResMut<VisualNovelState>,
+    time: Res<Time>,
 ) {
     if fading_actors.0.is_empty() {
         return;
     }
 
-    let mut finished_anim: Vec<Entity> = Vec::new();
-    for actor in &fading_actors.0 {
-        let mut s = match query.get_mut(actor.0) {
-            Ok(e) => e,
-            Err(_) => continue
+    let mut finished_blocking = false;
+    fading_actors.0.retain_mut(|fade| {
+        let Ok(mut image) = query.get_mut(fade.entity) else {
+            // The actor was despawned while fading
+            finished_blocking |= fade.blocking;
+            return false;
         };
-        let mut color = s.color;
-        color.set_alpha(s.color.alpha() + actor.1);
-        s.color = color;
-        if color.alpha() >= 1. || color.alpha() <= 0. {
-            finished_anim.push(actor.0);
-        }
-    }
-    let mut to_remove: Vec<usize> = Vec::new();
-    fading_actors.0.iter().enumerate().for_each(|f| {
-        if finished_anim.contains(&f.1.0) {
-            to_remove.push(f.0);
+        fade.elapsed += time.delta_secs();
+        let progress = if fade.duration > 0. { (fade.elapsed / fade.duration).min(1.) } else { 1. };
+        let alpha = fade.from + (fade.to - fade.from) * fade.easing.sample(progress);
+        image.color.set_alpha(alpha);
+        if progress >= 1. {
+            if fade.despawn {
+                commands.entity(fade.entity).despawn();
+            }
+            finished_blocking |= fade.blocking;
+            return false;
         }
+        true
     });
-    to_remove.reverse();
-    for index in to_remove {
-        let item = fading_actors.0.index(index);
-        let to_despawn = item.2;
-        if to_despawn {
-            commands.entity(item.0).despawn();
-        }
-        fading_actors.0.remove(index);
-    }
-    if fading_actors.0.is_empty() {
+
+    if finished_blocking && !fading_actors.0.iter().any(|f| f.blocking) {
         game_state.blocking = false;
     }
 }
+/// Fades the entity, replacing the fade it may be in
+pub(in crate::actor) fn start_fade(
+    fading_actors: &mut FadingActors,
+    entity: Entity,
+    from: f32,
+    to: f32,
+    tween: &Tween,
+    despawn: bool,
+) {
+    fading_actors.0.retain(|f| f.entity != entity);
+    fading_actors.0.push(ActorFade {
+        entity,
+        from,
+        to,
+        elapsed: 0.,
+        duration: tween.duration.unwrap_or(FADE_DURATION),
+        easing: tween.easing,
+        despawn,
+        blocking: tween.blocking,
+    });
+}
+/// Dissolves the previous sprite of a character, or of one of its layers, over the new one
+pub(in crate::actor) fn crossfade_sprite(
+    commands: &mut Commands,
+    fading_actors: &mut FadingActors,
+    actor: Entity,
+    previous: ImageNode,
+    duration: Option<f32>,
+) {
+    let duration = duration.unwrap_or(EMOTION_CROSSFADE_DURATION);
+    if duration <= 0. {
+        return;
+    }
+    let alpha = previous.color.alpha();
+    let overlay = commands.spawn((
+        ImageNode {
+            image: previous.image,
+            color: previous.color,
+            flip_x: previous.flip_x,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            width: percent(100.),
+            height: percent(100.),
+            ..default()
+        },
+        Pickable::IGNORE,
+        SpriteCrossfade,
+    )).id();
+    // Below the other layers of the character
+    commands.entity(actor).insert_children(0, &[overlay]);
+    let tween = Tween { duration: Some(duration), blocking: false, ..default() };
+    start_fade(fading_actors, overlay, alpha, 0., &tween, true);
+}
 pub fn spawn_actor(
     commands: &mut Commands,
     actor_config: ActorConfig,
     sprites: &Res<ActorsResource>,
     fading_actors: &mut ResMut<FadingActors>,
-    ui_root: &Single<Entity, With<UiRoot>>,
+    (stage, ui_root): (Entity, Entity),
     images: &Res<Assets<Image>>,
     info: SpawnInfo,
     texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    named_positions: &NamedPositions,
     window: &Window,
 ) -> Result<(), BevyError> {
     let actor_entity = match actor_config {
         ActorConfig::Character(actor_config) => {
-            let sprite_key = SpriteKey {
-                character: actor_config.name.clone(),
-                outfit: actor_config.outfit.clone(),
-                emotion: actor_config.emotion.clone(),
+            // Layered characters draw their bottom layer themselves
+            let image = match actor_config.layers.first() {
+                Some(base) => {
+                    let (variant, _) = initial_layer_state(&actor_config, base, &info.layers);
+                    layer_sprite(sprites, &actor_config, &base.name, &variant)?
+                },
+                None => {
+                    let sprite_key = SpriteKey {
+                        character: actor_config.name.clone(),
+                        outfit: actor_config.outfit.clone(),
+                        emotion: actor_config.emotion.clone(),
+                    };
+                    sprites.0.get(&SpriteIdentifier::Character(sprite_key.clone())).context(format!("No sprite found for {:?}", sprite_key))?.clone()
+                }
             };
-            let image = sprites.0.get(&SpriteIdentifier::Character(sprite_key.clone())).context(format!("No sprite found for {:?}", sprite_key))?;
-            let image_asset = images.get(image).context(format!("Asset not found for {:?}", image))?;
+            let mut layers = Vec::new();
+            for layer in actor_config.layers.iter().skip(1) {
+                let (variant, visible) = initial_layer_state(&actor_config, layer, &info.layers);
+                layers.push((layer.name.clone(), layer_sprite(sprites, &actor_config, &layer.name, &variant)?, visible));
+            }
+            let image_asset = images.get(&image).context(format!("Asset not found for {:?}", image))?;
             let aspect_ratio = image_asset.texture_descriptor.size.width as f32 / image_asset.texture_descriptor.size.height as f32;
             let position = if let Some(pos) = info.position {
                 match pos {
@@ -161,30 +597,58 @@ pub fn spawn_actor(
                     _ => { return Err(anyhow::anyhow!(format!("Expected Character position, found {:?}", pos)).into()); }
                 }
             } else { CharacterPosition::default() };
-            commands.spawn(
+            let left = position.to_percentage_value(named_positions)?;
+            let alpha = if info.fading { 0. } else { 1. };
+            let zoom = info.zoom.unwrap_or(1.);
+            let mut transform = UiTransform::default();
+            scale_character(&mut transform, zoom, 1.);
+            let layers: Vec<Entity> = layers.into_iter().map(|(name, sprite, visible)| commands.spawn((
+                ImageNode {
+                    image: sprite,
+                    color: Color::default().with_alpha(alpha),
+                    flip_x: info.direction == ActorDirection::Left,
+                    ..default()
+                },
+                Node {
+                    position_type: PositionType::Absolute,
+                    width: percent(100.),
+                    height: percent(100.),
+                    ..default()
+                },
+                if visible { Visibility::Inherited } else { Visibility::Hidden },
+                Pickable::IGNORE,
+                CharacterLayer(name),
+            )).id()).collect();
+            let character = commands.spawn(
                 (
                     ImageNode {
-                        image: image.clone(),
-                        color: Color::default().with_alpha(if info.fading {
-                            0.
-                        } else { 1. }),
+                        image,
+                        color: Color::default().with_alpha(alpha),
                         flip_x: info.direction == ActorDirection::Left,
                         ..default()
                     },
                     Node {
                         position_type: PositionType::Absolute,
-                        max_height: percent(75.),
-                        bottom: percent(0.),
+                        max_height: percent(actor_config.height.unwrap_or(CHARACTER_HEIGHT_PERCENTAGE)),
+                        bottom: percent(info.elevation),
                         aspect_ratio: Some(aspect_ratio),
-                        left: percent(position.to_percentage_value()),
+                        left: percent(left),
                         ..default()
                     },
-                    ZIndex(CHARACTERS_Z_INDEX),
+                    info.depth.z_index(),
+                    info.depth,
+                    // Actors drawn above the textbox let the clicks through
+                    Pickable::IGNORE,
+                    transform,
+                    CharacterFocus(1.),
+                    CharacterZoom(zoom),
                     Actor,
                     ActorConfig::Character(actor_config),
                     DespawnOnExit(SabiState::Running)
                 )
-            ).id()
+            ).id();
+            commands.entity(character).add_children(&layers);
+            character
         },
         ActorConfig::Animation(actor_config) => {
             let anim_id = actor_config.name.clone();
@@ -207,7 +671,7 @@ pub fn spawn_actor(
             let scale = info.scale.unwrap_or(1.);
             if scale < 0. { return Err(anyhow::anyhow!("Scale value can't be negative: {}", scale).into()); }
             let (left, bottom): (f32, f32) = position_relative_to_center(
-                position.into(),
+                position.coordinates(named_positions)?,
                 (actor_config.width, actor_config.height),
                 scale,
                 window,
@@ -236,7 +700,10 @@ pub fn spawn_actor(
                         bottom: percent(bottom),
                         ..default()
                     },
-                    ZIndex(CHARACTERS_Z_INDEX),
+                    info.depth.z_index(),
+                    info.depth,
+                    // Actors drawn above the textbox let the clicks through
+                    Pickable::IGNORE,
                     Actor,
                     AnimationScale(scale),
                     AnimationTimer(Timer::new(Duration::from_secs_f32(1. / (actor_config.fps as f32)), TimerMode::Repeating)),
@@ -246,9 +713,9 @@ pub fn spawn_actor(
             ).id()
         }
     };
-    commands.entity(ui_root.entity()).add_child(actor_entity);
+    commands.entity(info.depth.parent(stage, ui_root)).add_child(actor_entity);
     if info.fading {
-        fading_actors.0.push((actor_entity, 0.01, false));
+        start_fade(fading_actors, actor_entity, 0., 1., &info.fade, false);
     }
     Ok(())
 }
@@ -6,7 +6,8 @@ use bevy::{app::{App, Plugin}, asset::{AssetServer, Handle}};
 use anyhow::Context;
 
 use crate::VisualNovelState;
-use crate::compiler::controller::{Controller, ControllerReadyMessage, ControllersSetStateMessage, SabiState, UiRoot};
+use crate::compiler::rollback::{BackgroundState, StageRestoreMessage};
+use crate::compiler::controller::{Controller, ControllerReadyMessage, ControllersSetStateMessage, PauseState, SabiState, UiRoot};
 
 const BACKGROUND_Z_INDEX: i32 = 1;
 const BACKGROUNDS_ASSET_PATH: &str   = "sabi/backgrounds";
@@ -85,10 +86,13 @@ impl Plugin for BackgroundController {
             .add_systems(OnEnter(BackgroundControllerState::Loading), import_backgrounds_folder)
             .add_systems(Update, check_loading_state.run_if(in_state(BackgroundControllerState::Loading)))
             .add_systems(Update, (
+                restore_background,
                 update_background,
+            ).run_if(in_state(BackgroundControllerState::Running)))
+            .add_systems(Update, (
                 run_dissolving_animation,
                 run_sliding_animation,
-            ).run_if(in_state(BackgroundControllerState::Running)));
+            ).run_if(in_state(BackgroundControllerState::Running).and(in_state(PauseState::Playing))));
     }
 }
 
@@ -223,6 +227,44 @@ fn update_background(
     Ok(())
 }
 
+/// Checks for [StageRestoreMessage] and instantly sets the background recorded in it,
+/// interrupting any running transition
+fn restore_background(
+    mut commands: Commands,
+    mut restore_message: MessageReader<StageRestoreMessage>,
+    background_images: Res<BackgroundImages>,
+    mut background_query: Single<(&mut ImageNode, &mut Node), With<BackgroundNode>>,
+    next_background_query: Query<Entity, With<NextBackground>>,
+) -> Result<(), BevyError> {
+    for msg in restore_message.read() {
+        commands.insert_resource(Dissolving(None));
+        commands.remove_resource::<Sliding>();
+        for entity in next_background_query {
+            commands.entity(entity).despawn();
+        }
+
+        background_query.0.image = match &msg.0.background {
+            BackgroundState::Unset => Handle::default(),
+            BackgroundState::Image(target) => match background_images.0.get(target) {
+                Some(image) => image.clone(),
+                None => {
+                    // The snapshot may come from a save made before the background was removed
+                    warn!("Background '{}' does not exist, restoring without it", target);
+                    Handle::default()
+                }
+            },
+            BackgroundState::Cleared => TRANSPARENT_IMAGE_HANDLE,
+        };
+        background_query.0.color.set_alpha(1.);
+        background_query.1.top = Val::Auto;
+        background_query.1.left = Val::Auto;
+        background_query.1.bottom = Val::Auto;
+        background_query.1.right = Val::Auto;
+        info!("[ Restore background to '{:?}']", msg.0.background);
+    }
+    Ok(())
+}
+
 /// If a valid [Dissolving] resource is present, this system runs blocks the user input and runs dissolving animation from a background to another one
 fn run_dissolving_animation(
     mut commands: Commands,
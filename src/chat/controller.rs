@@ -1,19 +1,33 @@
-use std::collections::HashMap;
+use std::{any::TypeId, collections::HashMap};
 use anyhow::Context;
-use bevy::{asset::{LoadState, LoadedFolder}, prelude::*, time::Stopwatch};
+use bevy::{asset::{LoadState, LoadedFolder}, prelude::*, render::view::screenshot::{Screenshot, ScreenshotCaptured}, time::Stopwatch};
 use bevy_ui_widgets::{Activate, UiWidgetsPlugins};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    SabiResume,
+    actor::{CharacterZoom, CHARACTER_HEIGHT_PERCENTAGE, controller::{ActorConfig, ActorsConfigs, CharacterSpeakingMessage}},
     VisualNovelState,
+    UserDefinedConstants,
     chat::{INFOTEXT_Z_INDEX_ACTIVE, INFOTEXT_Z_INDEX_INACTIVE, ui::{
         basic::{
-            backplate_container, infotext_container, messagetext, namebox, nametext, textbox, top_section, vn_commands
+            backplate_container, infotext, infotext_container, messagetext, namebox, nametext, textbox, textbox_padding, vn_commands
         },
-        history::history_panel
+        bubble::speech_bubble,
+        history::spawn_history_panel,
+        nvl::{nvl_line, nvl_page, nvl_text},
+        FONT_PATH,
+        save_load::save_load_panel,
+        settings::settings_panel,
+        system_menu::system_menu_panel
     }},
-    compiler::controller::{
-        Controller, ControllerReadyMessage, ControllersSetStateMessage, SabiState, UiRoot
-    }
+    compiler::{
+        controller::{Controller, ControllerReadyMessage, ControllersSetStateMessage, CurrentScript, LoadedScripts, PauseState, SabiState, UiRoot},
+        rollback::{PageLine, StageRestoreMessage}
+    },
+    input::{InputAction, InputActionMessage, controller::read_input_actions},
+    save::{SaveData, SaveSlots, SceneCapture, controller::{QUICK_SLOT, SLOT_PAGES}},
+    settings::{SettingKind, Settings}
 };
 
 const UI_ASSET_PATH: &str = "sabi/ui";
@@ -34,6 +48,16 @@ pub(crate) struct GUIChangeMessage {
     pub sprite_id: String,
     pub image_mode: GuiImageMode,
 }
+/// Lines shown in speech bubbles, several of them are said at once
+#[derive(Message)]
+pub(crate) struct SpeechBubbleMessage {
+    pub lines: Vec<BubbleLine>,
+}
+#[derive(Message)]
+pub(crate) enum PresentationChangeMessage {
+    Mode(PresentationMode),
+    PageClear,
+}
 
 /* States */
 #[derive(States, Debug, Default, Clone, Copy, Hash, Eq, PartialEq)]
@@ -49,7 +73,14 @@ pub(crate) enum ChatControllerState {
 pub(crate) enum ChatControllerSubState {
     #[default]
     Default,
-    History
+    History,
+    Settings,
+    /// The system menu is open and the script is paused
+    SystemMenu,
+    /// The save/load slot browser is open, see [SlotBrowser]
+    SaveLoad,
+    /// The interface is hidden to show the whole scene
+    HiddenUi,
 }
 
 impl From<SabiState> for ChatControllerState {
@@ -77,18 +108,89 @@ pub(crate) struct NameBoxBackground;
 pub(crate) struct NameText;
 #[derive(Component)]
 pub(crate) struct MessageText;
+/// Multiplier of the player's text speed for the line in the textbox, see [crate::actor::CharacterConfig::text_speed]
+#[derive(Component)]
+pub(crate) struct SpeakerTextSpeed(pub f32);
 #[derive(Component)]
 pub(crate) struct InfoTextComponent;
 #[derive(Component)]
 pub(crate) struct InfoTextContainer;
 #[derive(Component)]
 pub(crate) struct VnCommands;
+/// Parent the chat layout gave the [VnCommands], they go back to it once the NVL page is hidden
+#[derive(Component)]
+struct VnCommandsHome(Entity);
+/// Full-screen page the lines accumulate on in [PresentationMode::Nvl]
+#[derive(Component)]
+pub(crate) struct NvlPage;
+#[derive(Component)]
+pub(crate) struct NvlLines;
+#[derive(Component)]
+pub(crate) struct NvlLine;
+/// Text of the NVL line being revealed, removed once the player finishes it
+#[derive(Component)]
+pub(crate) struct NvlCurrentLine;
+/// Bubble anchored above the actor of its speaker
+#[derive(Component)]
+pub(crate) struct SpeechBubble {
+    pub speaker: String,
+    /// Position among the bubbles shown at once, used when the speaker is not on stage
+    pub slot: usize,
+}
+#[derive(Component)]
+pub(crate) struct BubbleText;
 #[derive(Component)]
 pub(crate) struct HistoryPanel;
 #[derive(Component)]
 pub(crate) struct HistoryScrollbar;
 #[derive(Component)]
 pub(crate) struct HistoryText;
+#[derive(Component)]
+pub(crate) struct SettingsPanel;
+#[derive(Component)]
+pub(crate) struct SystemMenuPanel;
+#[derive(Component)]
+pub(crate) struct SaveLoadPanel;
+/// Text showing the current value of a setting in the [SettingsPanel]
+#[derive(Component)]
+pub(crate) struct SettingValueText(pub SettingKind);
+/// Part of the interface skinned by the [GuiTheme] in use
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ThemedWidget {
+    Button,
+    HistoryPanel,
+    Scrollbar,
+    /// System menu, settings and save/load panels
+    Menu,
+}
+/// Look of a [ThemedWidget] as it was built, used for whatever the theme leaves unset
+#[derive(Component, Clone)]
+struct ThemeBase {
+    background: BackgroundColor,
+    border: BorderColor,
+    image: Option<ImageNode>,
+}
+/// Look of a text inside a [ThemedWidget] as it was built
+#[derive(Component, Clone)]
+struct TextThemeBase {
+    color: Color,
+    font: TextFont,
+}
+/// Node built from the [ChatLayout], found again by its place in the tree when the layout is reloaded
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct LayoutElement {
+    pub tree: LayoutTree,
+    /// Indices of the children leading from the root of the tree to the node
+    pub path: Vec<usize>,
+    /// `None` for the root and the plain containers
+    pub widget: Option<LayoutWidget>,
+}
+/// Layout of a [LayoutElement] as it was built, used for whatever the layout file leaves unset
+#[derive(Component, Clone)]
+struct LayoutBase {
+    node: Node,
+    font: Option<TextFont>,
+}
 
 /* Resources */
 #[derive(Resource)]
@@ -97,28 +199,538 @@ pub(crate) struct ChatScrollStopwatch(Stopwatch);
 struct HandleToGuiFolder(Handle<LoadedFolder>);
 #[derive(Resource)]
 struct GuiImages(HashMap<String, Handle<Image>>);
+/// Slicing of the GUI sprites that ship a `<sprite>.slice.json`, by sprite name
+#[derive(Resource, Default)]
+struct GuiSlicings(HashMap<String, GuiSlicing>);
 #[derive(Resource)]
 pub(crate) struct CurrentTextBoxBackground(pub ImageNode);
+/// Themes shipped as `<name>.theme.json` in the GUI folder, by name
+#[derive(Resource, Default)]
+struct GuiThemes(HashMap<String, GuiTheme>);
+/// Theme set by `(GUI theme changes to ...)`, [UserDefinedConstants::gui_theme] is used without one
+#[derive(Resource, Default, PartialEq)]
+pub(crate) struct CurrentGuiTheme(pub Option<String>);
+/// Layout read from the `chat.layout.json` of the GUI folder, kept up to date when the file is edited
+#[derive(Resource, Default)]
+pub(crate) struct CurrentChatLayout(pub ChatLayout);
+/// Skin set by `(GUI bubble changes to ...)` and the padding of its slicing
+#[derive(Resource, Default)]
+pub(crate) struct CurrentBubbleBackground(pub Option<ImageNode>, pub Option<UiRect>);
+/// Display of the interface nodes before they were hidden by [ChatControllerSubState::HiddenUi]
+#[derive(Resource, Default)]
+struct HiddenUiDisplay(Vec<(Entity, Display)>);
+#[derive(Resource, Default)]
+pub(crate) struct PlaybackMode {
+    pub auto: bool,
+    pub skip: bool,
+}
+/// Slot browser currently open, the [SaveLoadPanel] is rebuilt whenever it or the [SaveSlots] change
+#[derive(Resource, Clone)]
+pub(crate) struct SlotBrowser {
+    pub mode: SlotBrowserMode,
+    pub page: usize,
+    /// Action waiting for the player to confirm it
+    pub pending: Option<SlotAction>,
+    /// Textbox skin of the panel, none on the title screen
+    pub plate: Option<ImageNode>,
+    /// Node the panel is spawned under
+    pub parent: Entity,
+}
+
+impl SlotBrowser {
+    pub(crate) fn new(mode: SlotBrowserMode, plate: Option<ImageNode>, parent: Entity) -> Self {
+        Self { mode, page: 0, pending: None, plate, parent }
+    }
+}
 
 /* Custom types */
+/// Nodes hidden by [ChatControllerSubState::HiddenUi]
+type InterfaceNodes = Or<(With<VNContainer>, With<VnCommands>, With<InfoTextContainer>, With<NvlPage>, With<SpeechBubble>)>;
 #[derive(Debug, Clone)]
 pub(crate) enum GuiChangeTarget {
     TextBoxBackground,
     NameBoxBackground,
+    BubbleBackground,
+    Theme,
 }
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub(crate) enum GuiImageMode {
     Sliced,
     #[default]
     Auto
 }
+/// 9-slice metadata of a GUI sprite, read from the `<sprite>.slice.json` next to it.
+/// Sprites without one are cut at a fifth of their size.
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub(crate) struct GuiSlicing {
+    /// Size of the corners, in pixels of the image
+    pub border: SliceInsets,
+    #[serde(default)]
+    pub center: SliceScale,
+    #[serde(default)]
+    pub sides: SliceScale,
+    #[serde(default = "default_max_corner_scale")]
+    pub max_corner_scale: f32,
+    /// Space between the borders and the content of the node, in pixels
+    pub padding: Option<SliceInsets>,
+}
+fn default_max_corner_scale() -> f32 { 1. }
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct SliceInsets {
+    pub top: f32,
+    pub bottom: f32,
+    pub left: f32,
+    pub right: f32,
+}
+/// Written `"stretch"` or `{ "tile": 1.0 }` in the slice file
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SliceScale {
+    Stretch,
+    Tile(f32),
+}
+
+impl Default for SliceScale {
+    fn default() -> Self {
+        SliceScale::Tile(1.)
+    }
+}
+
+impl From<SliceScale> for SliceScaleMode {
+    fn from(value: SliceScale) -> Self {
+        match value {
+            SliceScale::Stretch => SliceScaleMode::Stretch,
+            SliceScale::Tile(stretch_value) => SliceScaleMode::Tile { stretch_value },
+        }
+    }
+}
+
+impl From<SliceInsets> for UiRect {
+    fn from(value: SliceInsets) -> Self {
+        UiRect {
+            top: px(value.top),
+            bottom: px(value.bottom),
+            left: px(value.left),
+            right: px(value.right),
+        }
+    }
+}
+/// Skin of the interface widgets, read from a `<name>.theme.json` in the GUI folder
+#[derive(Asset, TypePath, Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct GuiTheme {
+    pub button: WidgetStyle,
+    pub history_panel: WidgetStyle,
+    pub scrollbar: WidgetStyle,
+    pub menu: WidgetStyle,
+}
+
+impl GuiTheme {
+    fn style(&self, widget: ThemedWidget) -> &WidgetStyle {
+        match widget {
+            ThemedWidget::Button => &self.button,
+            ThemedWidget::HistoryPanel => &self.history_panel,
+            ThemedWidget::Scrollbar => &self.scrollbar,
+            ThemedWidget::Menu => &self.menu,
+        }
+    }
+
+    /// Checks the colours and sprites of every widget once the GUI folder is loaded
+    fn validate(&self, name: &str, gui_sprites: &HashMap<String, Handle<Image>>) -> Result<(), BevyError> {
+        let widgets = [("button", &self.button), ("history_panel", &self.history_panel), ("scrollbar", &self.scrollbar), ("menu", &self.menu)];
+        for (widget, style) in widgets {
+            if let Some(image) = &style.image && !gui_sprites.contains_key(image) {
+                return Err(anyhow::anyhow!("Theme '{}': {} image '{}' is not a GUI asset", name, widget, image).into());
+            }
+            for (field, color) in [("color", &style.color), ("border_color", &style.border_color), ("text_color", &style.text_color)] {
+                if let Some(hex) = color {
+                    Srgba::hex(hex)
+                        .with_context(|| format!("Theme '{}': {} {} '{}' is not a valid hex colour", name, widget, field, hex))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+/// Every field is optional, the widget keeps its built look for the unset ones
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct WidgetStyle {
+    /// GUI sprite, sliced when it ships a slice file
+    pub image: Option<String>,
+    /// Background colour, in hex
+    pub color: Option<String>,
+    pub border_color: Option<String>,
+    pub text_color: Option<String>,
+    /// Font path, relative to the assets folder
+    pub font: Option<String>,
+    pub font_size: Option<f32>,
+}
+/// Node trees of the chat, read from the `chat.layout.json` of the GUI folder.
+/// Trees left out keep the built-in one, and fields left out keep the look the nodes are built with.
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct ChatLayout {
+    /// Container of the namebox and the textbox
+    pub adv: NodeLayout,
+    /// Full-screen container of the narration
+    pub infotext: NodeLayout,
+    pub history: NodeLayout,
+}
+
+impl Default for ChatLayout {
+    fn default() -> Self {
+        let widget = |widget, children| NodeLayout { widget: Some(widget), children, ..default() };
+        let container = |children| NodeLayout { children, ..default() };
+        Self {
+            adv: container(vec![
+                // Row of the namebox, above the textbox
+                container(vec![widget(LayoutWidget::Namebox, vec![widget(LayoutWidget::NameText, vec![])])]),
+                widget(LayoutWidget::Textbox, vec![widget(LayoutWidget::MessageText, vec![]), widget(LayoutWidget::Commands, vec![])]),
+            ]),
+            infotext: container(vec![widget(LayoutWidget::Infotext, vec![])]),
+            history: container(vec![
+                widget(LayoutWidget::HistoryTitle, vec![]),
+                widget(LayoutWidget::HistoryScroll, vec![widget(LayoutWidget::HistoryText, vec![])]),
+                widget(LayoutWidget::Scrollbar, vec![]),
+                widget(LayoutWidget::Close, vec![]),
+            ]),
+        }
+    }
+}
+
+impl ChatLayout {
+    pub(crate) fn tree(&self, tree: LayoutTree) -> &NodeLayout {
+        match tree {
+            LayoutTree::Adv => &self.adv,
+            LayoutTree::Infotext => &self.infotext,
+            LayoutTree::History => &self.history,
+        }
+    }
+
+    /// Layout of a node built from this layout, `None` once the tree no longer has the same node at its place
+    fn element(&self, element: &LayoutElement) -> Option<&NodeLayout> {
+        let mut node = self.tree(element.tree);
+        for index in &element.path {
+            node = node.children.get(*index)?;
+        }
+        (node.widget == element.widget).then_some(node)
+    }
+
+    /// Layout of a built-in widget, found in the tree it belongs to
+    pub(crate) fn widget(&self, widget: LayoutWidget) -> Option<&NodeLayout> {
+        self.tree(widget.tree()).find(widget)
+    }
+
+    /// Checks every tree holds each of its widgets exactly once, the chat looks them up once built
+    pub(crate) fn validate(&self) -> Result<(), BevyError> {
+        for tree in [LayoutTree::Adv, LayoutTree::Infotext, LayoutTree::History] {
+            let mut widgets = Vec::new();
+            self.tree(tree).collect_widgets(None, &mut widgets)?;
+            for widget in &widgets {
+                if widget.tree() != tree {
+                    return Err(anyhow::anyhow!("Chat layout: {} can't be placed in the {} tree", widget.name(), tree.name()).into());
+                }
+            }
+            for widget in LayoutWidget::ALL.into_iter().filter(|widget| widget.tree() == tree) {
+                let count = widgets.iter().filter(|found| **found == widget).count();
+                if count != 1 {
+                    return Err(anyhow::anyhow!("Chat layout: the {} tree needs exactly one {}, found {}", tree.name(), widget.name(), count).into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+/// Node of a layout tree, a built-in widget when `widget` is set and a plain container otherwise
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct NodeLayout {
+    pub widget: Option<LayoutWidget>,
+    pub children: Vec<NodeLayout>,
+    /// Corner or side of the parent the node sticks to
+    pub anchor: Option<LayoutAnchor>,
+    pub width: Option<LayoutVal>,
+    pub height: Option<LayoutVal>,
+    pub min_width: Option<LayoutVal>,
+    pub min_height: Option<LayoutVal>,
+    pub max_width: Option<LayoutVal>,
+    pub max_height: Option<LayoutVal>,
+    pub left: Option<LayoutVal>,
+    pub right: Option<LayoutVal>,
+    pub top: Option<LayoutVal>,
+    pub bottom: Option<LayoutVal>,
+    pub margin: LayoutRect,
+    pub padding: LayoutRect,
+    pub aspect_ratio: Option<f32>,
+    /// Font path, relative to the assets folder
+    pub font: Option<String>,
+    pub font_size: Option<f32>,
+}
+
+impl NodeLayout {
+    /// Widgets of the node and its descendants, `parent` being the widget the node is nested in
+    fn collect_widgets(&self, parent: Option<LayoutWidget>, widgets: &mut Vec<LayoutWidget>) -> Result<(), BevyError> {
+        let parent = match self.widget {
+            Some(widget) => {
+                if let Some(required) = widget.parent() && parent != Some(required) {
+                    return Err(anyhow::anyhow!("Chat layout: {} must be placed in the {}", widget.name(), required.name()).into());
+                }
+                widgets.push(widget);
+                Some(widget)
+            },
+            None => parent,
+        };
+        for child in &self.children {
+            child.collect_widgets(parent, widgets)?;
+        }
+        Ok(())
+    }
+
+    fn find(&self, widget: LayoutWidget) -> Option<&NodeLayout> {
+        if self.widget == Some(widget) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(widget))
+    }
+
+    fn apply(&self, node: &mut Node) {
+        set_val(&mut node.width, self.width);
+        set_val(&mut node.height, self.height);
+        set_val(&mut node.min_width, self.min_width);
+        set_val(&mut node.min_height, self.min_height);
+        set_val(&mut node.max_width, self.max_width);
+        set_val(&mut node.max_height, self.max_height);
+        set_val(&mut node.left, self.left);
+        set_val(&mut node.right, self.right);
+        set_val(&mut node.top, self.top);
+        set_val(&mut node.bottom, self.bottom);
+        self.margin.apply(&mut node.margin);
+        self.padding.apply(&mut node.padding);
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            node.aspect_ratio = Some(aspect_ratio);
+        }
+
+        // Auto margins push the node against the anchored sides of its parent
+        if let Some(anchor) = self.anchor {
+            let (left, right, top, bottom) = anchor.auto_margins();
+            if left { node.margin.left = Val::Auto; }
+            if right { node.margin.right = Val::Auto; }
+            if top { node.margin.top = Val::Auto; }
+            if bottom { node.margin.bottom = Val::Auto; }
+        }
+    }
+}
+fn set_val(target: &mut Val, value: Option<LayoutVal>) {
+    if let Some(value) = value {
+        *target = value.0;
+    }
+}
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct LayoutRect {
+    pub top: Option<LayoutVal>,
+    pub bottom: Option<LayoutVal>,
+    pub left: Option<LayoutVal>,
+    pub right: Option<LayoutVal>,
+}
+
+impl LayoutRect {
+    fn apply(&self, rect: &mut UiRect) {
+        set_val(&mut rect.top, self.top);
+        set_val(&mut rect.bottom, self.bottom);
+        set_val(&mut rect.left, self.left);
+        set_val(&mut rect.right, self.right);
+    }
+}
+/// Written `"45px"`, `"20%"`, `"70vw"`, `"5vh"`, `"10vmin"`, `"10vmax"` or `"auto"` in layout files
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct LayoutVal(Val);
+
+impl TryFrom<String> for LayoutVal {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let value = value.trim();
+        if value == "auto" {
+            return Ok(LayoutVal(Val::Auto));
+        }
+        let units = [
+            ("px", Val::Px as fn(f32) -> Val),
+            ("%", Val::Percent),
+            ("vw", Val::Vw),
+            ("vh", Val::Vh),
+            ("vmin", Val::VMin),
+            ("vmax", Val::VMax),
+        ];
+        for (suffix, unit) in units {
+            if let Some(number) = value.strip_suffix(suffix) {
+                return number.trim().parse::<f32>()
+                    .map(|number| LayoutVal(unit(number)))
+                    .map_err(|_| format!("Invalid length '{}'", value));
+            }
+        }
+        Err(format!("Invalid length '{}', expected px, %, vw, vh, vmin, vmax or auto", value))
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum LayoutAnchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl LayoutAnchor {
+    /// Margins made auto to reach the anchor, as left, right, top and bottom
+    fn auto_margins(self) -> (bool, bool, bool, bool) {
+        let left = matches!(self, Self::Top | Self::Center | Self::Bottom | Self::TopRight | Self::Right | Self::BottomRight);
+        let right = matches!(self, Self::Top | Self::Center | Self::Bottom | Self::TopLeft | Self::Left | Self::BottomLeft);
+        let top = matches!(self, Self::Left | Self::Center | Self::Right | Self::BottomLeft | Self::Bottom | Self::BottomRight);
+        let bottom = matches!(self, Self::Left | Self::Center | Self::Right | Self::TopLeft | Self::Top | Self::TopRight);
+        (left, right, top, bottom)
+    }
+}
+/// Trees of the [ChatLayout], spawned under the UI root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LayoutTree {
+    Adv,
+    Infotext,
+    History,
+}
+
+impl LayoutTree {
+    fn name(self) -> &'static str {
+        match self {
+            LayoutTree::Adv => "adv",
+            LayoutTree::Infotext => "infotext",
+            LayoutTree::History => "history",
+        }
+    }
+}
+/// Built-in nodes placed by the [ChatLayout], each one appears once in its tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LayoutWidget {
+    Namebox,
+    NameText,
+    Textbox,
+    MessageText,
+    /// Textbox commands, moved to the NVL page while it is shown
+    Commands,
+    Infotext,
+    HistoryTitle,
+    /// Scrolled area of the history, holding its text
+    HistoryScroll,
+    HistoryText,
+    Scrollbar,
+    Close,
+}
+
+impl LayoutWidget {
+    const ALL: [LayoutWidget; 11] = [
+        LayoutWidget::Namebox,
+        LayoutWidget::NameText,
+        LayoutWidget::Textbox,
+        LayoutWidget::MessageText,
+        LayoutWidget::Commands,
+        LayoutWidget::Infotext,
+        LayoutWidget::HistoryTitle,
+        LayoutWidget::HistoryScroll,
+        LayoutWidget::HistoryText,
+        LayoutWidget::Scrollbar,
+        LayoutWidget::Close,
+    ];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            LayoutWidget::Namebox => "namebox",
+            LayoutWidget::NameText => "name_text",
+            LayoutWidget::Textbox => "textbox",
+            LayoutWidget::MessageText => "message_text",
+            LayoutWidget::Commands => "commands",
+            LayoutWidget::Infotext => "infotext",
+            LayoutWidget::HistoryTitle => "history_title",
+            LayoutWidget::HistoryScroll => "history_scroll",
+            LayoutWidget::HistoryText => "history_text",
+            LayoutWidget::Scrollbar => "scrollbar",
+            LayoutWidget::Close => "close",
+        }
+    }
+
+    fn tree(self) -> LayoutTree {
+        match self {
+            LayoutWidget::Namebox | LayoutWidget::NameText | LayoutWidget::Textbox | LayoutWidget::MessageText | LayoutWidget::Commands => LayoutTree::Adv,
+            LayoutWidget::Infotext => LayoutTree::Infotext,
+            LayoutWidget::HistoryTitle | LayoutWidget::HistoryScroll | LayoutWidget::HistoryText | LayoutWidget::Scrollbar | LayoutWidget::Close => LayoutTree::History,
+        }
+    }
+
+    /// Widget this one has to be nested in
+    fn parent(self) -> Option<LayoutWidget> {
+        match self {
+            // The scrolled area moves its content, the text has to be inside it
+            LayoutWidget::HistoryText => Some(LayoutWidget::HistoryScroll),
+            _ => None,
+        }
+    }
+}
+/// ADV shows one line at a time in the textbox, NVL accumulates lines on a full-screen page
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum PresentationMode {
+    #[default]
+    Adv,
+    Nvl,
+}
+#[derive(Debug, Clone)]
+pub(crate) struct BubbleLine {
+    pub name: String,
+    pub text: String,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SlotBrowserMode {
+    Save,
+    Load,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SlotAction {
+    Overwrite(usize),
+    Delete(usize),
+}
 #[derive(Hash, Eq, PartialEq, Component, Clone, Debug)]
 pub(crate) enum UiButtons {
     OpenHistory,
     ExitHistory,
+    OpenSettings,
+    ExitSettings,
+    SettingDown(SettingKind),
+    SettingUp(SettingKind),
+    OpenSystemMenu,
+    CloseSystemMenu,
+    Save,
+    Load,
+    ExitSaveLoad,
+    SaveSlot(usize),
+    DeleteSlot(usize),
+    PreviousSlotPage,
+    NextSlotPage,
+    ConfirmSlotAction,
+    CancelSlotAction,
+    ReturnToTitle,
     Rewind,
+    RollForward,
     TextBox,
     InfoText,
+    NvlPage,
+    SpeechBubble,
 }
 
 pub(crate) struct ChatController;
@@ -132,24 +744,55 @@ impl Plugin for ChatController {
             .add_message::<CharacterSayMessage>()
             .add_message::<InfoTextMessage>()
             .add_message::<GUIChangeMessage>()
+            .add_message::<PresentationChangeMessage>()
+            .add_message::<SpeechBubbleMessage>()
+            .init_resource::<CurrentBubbleBackground>()
+            .init_resource::<CurrentGuiTheme>()
+            .init_resource::<CurrentChatLayout>()
             .add_plugins(UiWidgetsPlugins)
             .add_systems(Update, wait_trigger)
-            .add_systems(OnEnter(ChatControllerState::Running), spawn_chatbox)
-            .add_systems(Update, (update_chatbox, update_infotext, update_gui).run_if(in_state(ChatControllerState::Running)))
+            .add_systems(OnEnter(ChatControllerState::Running), (validate_character_skins, spawn_chatbox))
+            .add_systems(Update, (update_gui, restore_gui, apply_textbox_opacity).run_if(in_state(ChatControllerState::Running)))
+            .add_systems(Update, restore_nvl_page.before(update_nvl_page).run_if(in_state(ChatControllerState::Running)))
+            .add_systems(Update, dock_vn_commands.after(update_nvl_page).run_if(in_state(ChatControllerState::Running)))
+            .add_systems(Update, apply_speaker_style.before(update_chatbox).run_if(in_state(ChatControllerState::Running).and(in_state(PauseState::Playing))))
+            .add_systems(Update, (update_chatbox, update_infotext, update_nvl_page, update_speech_bubbles).run_if(in_state(ChatControllerState::Running).and(in_state(PauseState::Playing))))
+            .add_systems(Update, anchor_speech_bubbles.run_if(in_state(ChatControllerState::Running)))
+            .add_systems(Update, handle_input_actions.after(read_input_actions).run_if(in_state(ChatControllerState::Running)))
+            .add_systems(Update, handle_quick_save_load.after(read_input_actions).run_if(in_state(ChatControllerSubState::Default)))
+            .add_systems(Update, run_playback_modes.run_if(in_state(ChatControllerSubState::Default)))
+            .add_systems(OnEnter(ChatControllerSubState::HiddenUi), hide_ui)
+            .add_systems(OnExit(ChatControllerSubState::HiddenUi), show_ui)
+            .add_systems(Update, wait_ui_restore.after(read_input_actions).run_if(in_state(ChatControllerSubState::HiddenUi)))
+            .add_systems(OnEnter(ChatControllerSubState::SystemMenu), show_system_menu)
+            .add_systems(OnExit(ChatControllerSubState::SystemMenu), hide_system_menu)
+            .add_systems(Update, update_settings_panel.run_if(resource_changed::<Settings>))
+            .add_systems(Update, (reload_chat_layout, apply_chat_layout).chain().before(apply_gui_theme))
+            .add_systems(Update, apply_gui_theme.run_if(resource_exists::<GuiThemes>))
+            .add_systems(OnExit(ChatControllerState::Running), reset_gui_theme)
+            .add_systems(Update, refresh_slot_browser.run_if(resource_exists::<SlotBrowser>))
             .add_observer(button_clicked_history_state)
-            .add_observer(button_clicked_default_state);
+            .add_observer(button_clicked_settings_state)
+            .add_observer(button_clicked_system_menu_state)
+            .add_observer(button_clicked_save_load_state)
+            .add_observer(button_clicked_slot_browser)
+            .add_observer(button_clicked_default_state)
+            .add_observer(button_clicked_nvl_page)
+            .add_observer(button_clicked_speech_bubble);
     }
 }
 fn button_clicked_history_state(
     trigger: On<Activate>,
     mut commands: Commands,
     q_buttons: Query<(Entity, &UiButtons)>,
-    current_sub_state: Res<State<ChatControllerSubState>>,
+    // Missing while the chat is not running, e.g. on the title screen
+    current_sub_state: Option<Res<State<ChatControllerSubState>>>,
     mut sub_state: ResMut<NextState<ChatControllerSubState>>,
     history_panel: Single<Entity, With<HistoryPanel>>,
+    system_menu: Query<(), With<SystemMenuPanel>>,
 ) -> Result<(), BevyError> {
 
-    if *current_sub_state != ChatControllerSubState::History {
+    if current_sub_state.is_none_or(|s| *s != ChatControllerSubState::History) {
         return Ok(())
     }
 
@@ -158,30 +801,228 @@ fn button_clicked_history_state(
         UiButtons::ExitHistory => {
             warn!("Exit history clicked");
             commands.entity(*history_panel).despawn();
+            sub_state.set(closed_panel_state(&system_menu));
+        },
+        _ => {}
+    }
+    Ok(())
+}
+#[allow(clippy::too_many_arguments)]
+fn button_clicked_settings_state(
+    trigger: On<Activate>,
+    mut commands: Commands,
+    q_buttons: Query<(Entity, &UiButtons)>,
+    // Missing while the chat is not running, e.g. on the title screen
+    current_sub_state: Option<Res<State<ChatControllerSubState>>>,
+    mut sub_state: ResMut<NextState<ChatControllerSubState>>,
+    settings_panel: Single<Entity, With<SettingsPanel>>,
+    system_menu: Query<(), With<SystemMenuPanel>>,
+    mut settings: ResMut<Settings>,
+    user_defined_constants: Res<UserDefinedConstants>,
+) -> Result<(), BevyError> {
+
+    if current_sub_state.is_none_or(|s| *s != ChatControllerSubState::Settings) {
+        return Ok(())
+    }
+
+    let entity = q_buttons.get(trigger.entity).context("Clicked Entity does not have UiButtons declared")?;
+    match entity.1 {
+        UiButtons::ExitSettings => {
+            warn!("Exit settings clicked");
+            commands.entity(*settings_panel).despawn();
+            sub_state.set(closed_panel_state(&system_menu));
+        },
+        UiButtons::SettingDown(kind) => settings.step(*kind, false, &user_defined_constants.languages),
+        UiButtons::SettingUp(kind) => settings.step(*kind, true, &user_defined_constants.languages),
+        _ => {}
+    }
+    Ok(())
+}
+/// Panels opened from the system menu go back to it when closed
+fn closed_panel_state(system_menu: &Query<(), With<SystemMenuPanel>>) -> ChatControllerSubState {
+    if system_menu.is_empty() { ChatControllerSubState::Default } else { ChatControllerSubState::SystemMenu }
+}
+#[allow(clippy::too_many_arguments)]
+fn button_clicked_system_menu_state(
+    trigger: On<Activate>,
+    mut commands: Commands,
+    q_buttons: Query<(Entity, &UiButtons)>,
+    // Missing while the chat is not running, e.g. on the title screen
+    current_sub_state: Option<Res<State<ChatControllerSubState>>>,
+    mut sub_state: ResMut<NextState<ChatControllerSubState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut sabi_state: ResMut<NextState<SabiState>>,
+    mut controller_writer: MessageWriter<ControllersSetStateMessage>,
+    system_menu: Single<Entity, With<SystemMenuPanel>>,
+    ui_root: Single<Entity, With<UiRoot>>,
+    game_state: Res<VisualNovelState>,
+    current_plate: Res<CurrentTextBoxBackground>,
+    layout: Res<CurrentChatLayout>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+) -> Result<(), BevyError> {
+
+    if current_sub_state.is_none_or(|s| *s != ChatControllerSubState::SystemMenu) {
+        return Ok(())
+    }
+
+    let entity = q_buttons.get(trigger.entity).context("Clicked Entity does not have UiButtons declared")?;
+    match entity.1 {
+        UiButtons::CloseSystemMenu => {
+            warn!("Close system menu clicked");
+            commands.entity(*system_menu).despawn();
             sub_state.set(ChatControllerSubState::Default);
+            pause_state.set(PauseState::Playing);
+        },
+        UiButtons::OpenHistory => {
+            warn!("Open history clicked");
+            let history_panel_id = spawn_history_panel(&mut commands, &layout.0, current_plate, &game_state, &asset_server)?;
+            commands.entity(*ui_root).add_child(history_panel_id);
+            sub_state.set(ChatControllerSubState::History);
+        },
+        UiButtons::OpenSettings => {
+            warn!("Open settings clicked");
+            let settings_panel_id = commands.spawn(settings_panel(Some(&current_plate.0), &settings, &asset_server)?).id();
+            commands.entity(*ui_root).add_child(settings_panel_id);
+            sub_state.set(ChatControllerSubState::Settings);
+        },
+        UiButtons::Save if game_state.replay.is_some() => warn!("Replays from the gallery can't be saved"),
+        UiButtons::Save | UiButtons::Load => {
+            warn!("Slot browser opened");
+            let mode = if *entity.1 == UiButtons::Save { SlotBrowserMode::Save } else { SlotBrowserMode::Load };
+            commands.insert_resource(SlotBrowser::new(mode, Some(current_plate.0.clone()), *ui_root));
+            sub_state.set(ChatControllerSubState::SaveLoad);
+        },
+        UiButtons::ReturnToTitle => {
+            info!("[ Returning to title ]");
+            sabi_state.set(SabiState::Idle);
+            controller_writer.write(ControllersSetStateMessage(SabiState::Idle));
+        },
+        _ => {}
+    }
+    Ok(())
+}
+fn button_clicked_save_load_state(
+    trigger: On<Activate>,
+    mut commands: Commands,
+    q_buttons: Query<(Entity, &UiButtons)>,
+    // Missing while the chat is not running, e.g. on the title screen
+    current_sub_state: Option<Res<State<ChatControllerSubState>>>,
+    mut sub_state: ResMut<NextState<ChatControllerSubState>>,
+    q_panel: Query<Entity, With<SaveLoadPanel>>,
+    system_menu: Query<(), With<SystemMenuPanel>>,
+) -> Result<(), BevyError> {
+
+    if current_sub_state.is_none_or(|s| *s != ChatControllerSubState::SaveLoad) {
+        return Ok(())
+    }
+
+    let entity = q_buttons.get(trigger.entity).context("Clicked Entity does not have UiButtons declared")?;
+    if *entity.1 == UiButtons::ExitSaveLoad {
+        warn!("Exit slot browser clicked");
+        close_slot_browser(&mut commands, &q_panel);
+        sub_state.set(closed_panel_state(&system_menu));
+    }
+    Ok(())
+}
+/// Handles the slots of the [SaveLoadPanel], both in game and on the title screen
+#[allow(clippy::too_many_arguments)]
+fn button_clicked_slot_browser(
+    trigger: On<Activate>,
+    mut commands: Commands,
+    q_buttons: Query<&UiButtons>,
+    browser: Option<ResMut<SlotBrowser>>,
+    mut save_slots: ResMut<SaveSlots>,
+    mut images: ResMut<Assets<Image>>,
+    // Taken when the system menu opens, missing on the title screen
+    capture: Option<Res<SceneCapture>>,
+    game_state: Res<VisualNovelState>,
+    // Missing until a script has been started
+    current_script: Option<Res<CurrentScript>>,
+    current_sabi_state: Res<State<SabiState>>,
+    mut sabi_state: ResMut<NextState<SabiState>>,
+    mut controller_writer: MessageWriter<ControllersSetStateMessage>,
+    mut resume_writer: MessageWriter<SabiResume>,
+    q_panel: Query<Entity, With<SaveLoadPanel>>,
+    loaded_scripts: LoadedScripts,
+) -> Result<(), BevyError> {
+    let Some(mut browser) = browser else {
+        return Ok(());
+    };
+    let Ok(button) = q_buttons.get(trigger.entity) else {
+        return Ok(());
+    };
+
+    let save_to = |slot: usize, save_slots: &mut SaveSlots, images: &mut Assets<Image>| -> Result<(), BevyError> {
+        let script_id = &current_script.as_ref().context("Saving without a running script")?.0;
+        match SaveData::capture(&game_state, script_id)? {
+            Some(data) => save_slots.write(slot, data, capture.as_deref(), images),
+            None => warn!("Nothing to save before the first line"),
+        }
+        Ok(())
+    };
+
+    match button {
+        UiButtons::SaveSlot(slot) if browser.mode == SlotBrowserMode::Load => {
+            let save = save_slots.0.get(slot)
+                .context(format!("Save slot {} is empty", slot))?;
+            // Checked before anything is torn down, the running script goes on if the save can't be loaded
+            if let Err(e) = loaded_scripts.check_save(&save.data) {
+                warn!("Save slot {} can't be loaded: {:#}", slot, e);
+                return Ok(());
+            }
+            info!("[ Loading slot {} ]", slot);
+            // The running script is torn down before the save is started
+            if *current_sabi_state.get() != SabiState::Idle {
+                sabi_state.set(SabiState::Idle);
+                controller_writer.write(ControllersSetStateMessage(SabiState::Idle));
+            }
+            resume_writer.write(SabiResume(save.data.clone()));
+            close_slot_browser(&mut commands, &q_panel);
         },
+        UiButtons::SaveSlot(slot) if save_slots.0.contains_key(slot) => browser.pending = Some(SlotAction::Overwrite(*slot)),
+        UiButtons::SaveSlot(slot) => save_to(*slot, &mut save_slots, &mut images)?,
+        UiButtons::DeleteSlot(slot) => browser.pending = Some(SlotAction::Delete(*slot)),
+        UiButtons::PreviousSlotPage => browser.page = (browser.page + SLOT_PAGES - 1) % SLOT_PAGES,
+        UiButtons::NextSlotPage => browser.page = (browser.page + 1) % SLOT_PAGES,
+        UiButtons::ConfirmSlotAction => match browser.pending.take() {
+            Some(SlotAction::Overwrite(slot)) => save_to(slot, &mut save_slots, &mut images)?,
+            Some(SlotAction::Delete(slot)) => save_slots.delete(slot),
+            None => {}
+        },
+        UiButtons::CancelSlotAction => browser.pending = None,
         _ => {}
     }
     Ok(())
 }
+pub(crate) fn close_slot_browser(commands: &mut Commands, q_panel: &Query<Entity, With<SaveLoadPanel>>) {
+    for panel in q_panel {
+        commands.entity(panel).despawn();
+    }
+    commands.remove_resource::<SlotBrowser>();
+}
 fn button_clicked_default_state(
     trigger: On<Activate>,
     mut commands: Commands,
     vncontainer_visibility: Single<&mut Visibility, (With<VNContainer>, Without<InfoTextContainer>, Without<InfoTextComponent>)>,
     scroll_stopwatch: ResMut<ChatScrollStopwatch>,
-    mut message_text: Single<(&mut GUIScrollText, &mut Text), (With<MessageText>, Without<NameText>, Without<InfoTextComponent>)>,
+    mut message_text: Single<(&mut GUIScrollText, &mut Text, &SpeakerTextSpeed), (With<MessageText>, Without<NameText>, Without<InfoTextComponent>)>,
     mut info_text: Single<(&mut GUIScrollText, &mut Text, &mut Visibility), (With<InfoTextComponent>, Without<NameText>, Without<MessageText>, Without<VNContainer>)>,
     info_text_container_zidx: Single<&mut ZIndex, (With<InfoTextContainer>, Without<VNContainer>)>,
     mut game_state: ResMut<VisualNovelState>,
     ui_root: Single<Entity, With<UiRoot>>,
     q_buttons: Query<(Entity, &UiButtons)>,
     current_plate: Res<CurrentTextBoxBackground>,
+    layout: Res<CurrentChatLayout>,
     asset_server: Res<AssetServer>,
-    current_sub_state: Res<State<ChatControllerSubState>>,
+    settings: Res<Settings>,
+    // Missing while the chat is not running, e.g. on the title screen
+    current_sub_state: Option<Res<State<ChatControllerSubState>>>,
     mut sub_state: ResMut<NextState<ChatControllerSubState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
 ) -> Result<(), BevyError> {
 
-    if *current_sub_state != ChatControllerSubState::Default {
+    if current_sub_state.is_none_or(|s| *s != ChatControllerSubState::Default) {
         return Ok(())
     }
 
@@ -190,84 +1031,422 @@ fn button_clicked_default_state(
     match entity.1 {
         UiButtons::OpenHistory => {
             warn!("Open history clicked");
-            let history_panel_id = commands.spawn(history_panel(current_plate, &game_state, &asset_server)?).id();
+            let history_panel_id = spawn_history_panel(&mut commands, &layout.0, current_plate, &game_state, &asset_server)?;
             commands.entity(*ui_root).add_child(history_panel_id);
             sub_state.set(ChatControllerSubState::History);
         },
+        UiButtons::OpenSettings => {
+            warn!("Open settings clicked");
+            let settings_panel_id = commands.spawn(settings_panel(Some(&current_plate.0), &settings, &asset_server)?).id();
+            commands.entity(*ui_root).add_child(settings_panel_id);
+            sub_state.set(ChatControllerSubState::Settings);
+        },
+        UiButtons::OpenSystemMenu => {
+            warn!("Open system menu clicked");
+            let system_menu_id = commands.spawn(system_menu_panel(&current_plate.0, game_state.replay.is_none())?).id();
+            commands.entity(*ui_root).add_child(system_menu_id);
+            // The menu stays hidden until the scene has been captured for the save thumbnails
+            commands.spawn(Screenshot::primary_window())
+                .observe(store_scene_capture)
+                .observe(reveal_system_menu);
+            sub_state.set(ChatControllerSubState::SystemMenu);
+            pause_state.set(PauseState::Paused);
+        },
         UiButtons::Rewind => {
             warn!("Rewind button clicked!");
             *info_text.0 = GUIScrollText::default();
             *message_text.0 = GUIScrollText::default();
             game_state.set_rewind();
         },
+        UiButtons::RollForward => {
+            warn!("Roll forward button clicked!");
+            *info_text.0 = GUIScrollText::default();
+            *message_text.0 = GUIScrollText::default();
+            game_state.set_roll_forward();
+        },
         UiButtons::TextBox => {
             warn!("Textbox history clicked");
-            textbox_clicked(vncontainer_visibility, scroll_stopwatch, message_text, game_state);
+            let reveal_rate = settings.text_speed * message_text.2.0;
+            textbox_clicked(vncontainer_visibility, scroll_stopwatch, message_text, game_state, reveal_rate);
         },
         UiButtons::InfoText => {
             warn!("Infotext container clicked");
-            infotext_clicked(scroll_stopwatch, info_text, info_text_container_zidx, game_state);
+            infotext_clicked(scroll_stopwatch, info_text, info_text_container_zidx, game_state, settings.infotext_speed());
         }
         _ => {}
     }
 
     Ok(())
 }
-fn infotext_clicked(
+#[allow(clippy::too_many_arguments)]
+fn button_clicked_nvl_page(
+    trigger: On<Activate>,
+    mut commands: Commands,
+    q_buttons: Query<&UiButtons>,
+    // Missing while the chat is not running, e.g. on the title screen
+    current_sub_state: Option<Res<State<ChatControllerSubState>>>,
+    mut current_line: Query<(Entity, &GUIScrollText, &mut TextSpan), With<NvlCurrentLine>>,
     mut scroll_stopwatch: ResMut<ChatScrollStopwatch>,
-    mut info_text: Single<(&mut GUIScrollText, &mut Text, &mut Visibility), (With<InfoTextComponent>, Without<NameText>, Without<MessageText>, Without<VNContainer>)>,
-    mut container_zidx: Single<&mut ZIndex, (With<InfoTextContainer>, Without<VNContainer>)>,
     mut game_state: ResMut<VisualNovelState>,
+    settings: Res<Settings>,
 ) {
-    let length: u32 = (scroll_stopwatch.0.elapsed_secs() * 25.) as u32;
-    if length < info_text.0.message.len() as u32 {
+    if current_sub_state.is_none_or(|s| *s != ChatControllerSubState::Default) {
+        return;
+    }
+    if !matches!(q_buttons.get(trigger.entity), Ok(UiButtons::NvlPage)) {
+        return;
+    }
+    // Finished lines stay on the page until it is cleared
+    let Ok((entity, scroll_text, mut text_span)) = current_line.single_mut() else {
+        return;
+    };
+
+    let length = (scroll_stopwatch.0.elapsed_secs() * settings.text_speed) as usize;
+    if length < scroll_text.message.chars().count() {
         // Skip message scrolling
         scroll_stopwatch.0.set_elapsed(std::time::Duration::from_secs_f32(100000000.));
         return;
     }
-    println!("[ Infotext finished ]");
+    info!("[ Player finished NVL line ]");
+
+    text_span.0 = scroll_text.message.clone();
+    commands.entity(entity).remove::<NvlCurrentLine>();
 
     // Allow transitions to be run again
     game_state.blocking = false;
-    *info_text.2 = Visibility::Hidden;
-    **container_zidx = ZIndex(INFOTEXT_Z_INDEX_INACTIVE);
 }
-fn textbox_clicked(
-    mut vncontainer_visibility: Single<&mut Visibility, (With<VNContainer>, Without<InfoTextContainer>, Without<InfoTextComponent>)>,
+#[allow(clippy::too_many_arguments)]
+fn button_clicked_speech_bubble(
+    trigger: On<Activate>,
+    mut commands: Commands,
+    q_buttons: Query<&UiButtons>,
+    // Missing while the chat is not running, e.g. on the title screen
+    current_sub_state: Option<Res<State<ChatControllerSubState>>>,
+    q_bubbles: Query<Entity, With<SpeechBubble>>,
+    q_bubble_texts: Query<&GUIScrollText, With<BubbleText>>,
     mut scroll_stopwatch: ResMut<ChatScrollStopwatch>,
-    message_text: Single<(&mut GUIScrollText, &mut Text), (With<MessageText>, Without<NameText>, Without<InfoTextComponent>)>,
     mut game_state: ResMut<VisualNovelState>,
+    settings: Res<Settings>,
 ) {
-    let length: u32 = (scroll_stopwatch.0.elapsed_secs() * 50.) as u32;
-    if length < message_text.0.message.len() as u32 {
+    if current_sub_state.is_none_or(|s| *s != ChatControllerSubState::Default) {
+        return;
+    }
+    if !matches!(q_buttons.get(trigger.entity), Ok(UiButtons::SpeechBubble)) {
+        return;
+    }
+
+    // Bubbles said together are revealed and dismissed together
+    let length = (scroll_stopwatch.0.elapsed_secs() * settings.text_speed) as usize;
+    if q_bubble_texts.iter().any(|text| length < text.message.chars().count()) {
         // Skip message scrolling
         scroll_stopwatch.0.set_elapsed(std::time::Duration::from_secs_f32(100000000.));
         return;
     }
-    println!("[ Player finished message ]");
+    info!("[ Player finished speech bubbles ]");
 
-    // Hide textbox parent object
-    **vncontainer_visibility = Visibility::Hidden;
+    for bubble in &q_bubbles {
+        commands.entity(bubble).despawn();
+    }
 
     // Allow transitions to be run again
     game_state.blocking = false;
 }
-fn setup(
+/// Dispatches [InputActionMessage]s by activating the matching [UiButtons],
+/// so that bindings behave exactly like clicks
+#[allow(clippy::too_many_arguments)]
+fn handle_input_actions(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    loaded_folders: Res<Assets<LoadedFolder>>,
-    folder_handle: Res<HandleToGuiFolder>,
-    mut controller_state: ResMut<NextState<ChatControllerState>>,
-    mut msg_writer: MessageWriter<ControllerReadyMessage>,
-) -> Result<(), BevyError> {
-    let mut gui_sprites = HashMap::<String, Handle<Image>>::new();
-    if let Some(state) = asset_server.get_load_state(folder_handle.0.id()) {
-        match state {
-            LoadState::Loaded => {
-                if let Some(loaded_folder) = loaded_folders.get(folder_handle.0.id()) {
-                    for handle in &loaded_folder.handles {
+    mut action_reader: MessageReader<InputActionMessage>,
+    q_buttons: Query<(Entity, &UiButtons)>,
+    vncontainer_visibility: Single<&Visibility, With<VNContainer>>,
+    info_text_visibility: Single<&Visibility, With<InfoTextComponent>>,
+    nvl_page_visibility: Single<&Visibility, With<NvlPage>>,
+    q_bubbles: Query<(), With<SpeechBubble>>,
+    current_sub_state: Res<State<ChatControllerSubState>>,
+    mut sub_state: ResMut<NextState<ChatControllerSubState>>,
+    mut playback: ResMut<PlaybackMode>,
+) {
+    for InputActionMessage(action) in action_reader.read() {
+        let target = match (action, current_sub_state.get()) {
+            (InputAction::Advance, ChatControllerSubState::Default) => {
+                if **info_text_visibility == Visibility::Visible {
+                    UiButtons::InfoText
+                } else if **nvl_page_visibility == Visibility::Visible {
+                    UiButtons::NvlPage
+                } else if !q_bubbles.is_empty() {
+                    UiButtons::SpeechBubble
+                } else if **vncontainer_visibility == Visibility::Visible {
+                    UiButtons::TextBox
+                } else { continue; }
+            },
+            (InputAction::Rewind, ChatControllerSubState::Default) => UiButtons::Rewind,
+            (InputAction::RollForward, ChatControllerSubState::Default) => UiButtons::RollForward,
+            (InputAction::History, ChatControllerSubState::Default) => UiButtons::OpenHistory,
+            (InputAction::History, ChatControllerSubState::History) => UiButtons::ExitHistory,
+            (InputAction::Pause, ChatControllerSubState::Default) => UiButtons::OpenSystemMenu,
+            (InputAction::Pause, ChatControllerSubState::SystemMenu) => UiButtons::CloseSystemMenu,
+            (InputAction::Pause, ChatControllerSubState::History) => UiButtons::ExitHistory,
+            (InputAction::Pause, ChatControllerSubState::Settings) => UiButtons::ExitSettings,
+            (InputAction::Pause, ChatControllerSubState::SaveLoad) => UiButtons::ExitSaveLoad,
+            (InputAction::HideUi, ChatControllerSubState::Default) => {
+                sub_state.set(ChatControllerSubState::HiddenUi);
+                continue;
+            },
+            (InputAction::Skip, ChatControllerSubState::Default) => {
+                playback.skip = !playback.skip;
+                info!("[ Skip mode {} ]", if playback.skip { "on" } else { "off" });
+                continue;
+            },
+            (InputAction::Auto, ChatControllerSubState::Default) => {
+                playback.auto = !playback.auto;
+                info!("[ Auto mode {} ]", if playback.auto { "on" } else { "off" });
+                continue;
+            },
+            _ => continue,
+        };
+        if let Some((entity, _)) = q_buttons.iter().find(|(_, button)| **button == target) {
+            commands.trigger(Activate { entity });
+        }
+    }
+}
+/// Saves to and resumes from the quick slot, which is kept apart from the slot browser
+#[allow(clippy::too_many_arguments)]
+fn handle_quick_save_load(
+    mut action_reader: MessageReader<InputActionMessage>,
+    mut save_slots: ResMut<SaveSlots>,
+    mut images: ResMut<Assets<Image>>,
+    game_state: Res<VisualNovelState>,
+    current_script: Res<CurrentScript>,
+    mut sabi_state: ResMut<NextState<SabiState>>,
+    mut controller_writer: MessageWriter<ControllersSetStateMessage>,
+    mut resume_writer: MessageWriter<SabiResume>,
+    loaded_scripts: LoadedScripts,
+) -> Result<(), BevyError> {
+    for InputActionMessage(action) in action_reader.read() {
+        match action {
+            InputAction::QuickSave if game_state.replay.is_some() => warn!("Replays from the gallery can't be saved"),
+            InputAction::QuickSave => match SaveData::capture(&game_state, &current_script.0)? {
+                // The quick slot is never shown in the slot browser, so it goes without a thumbnail
+                Some(data) => save_slots.write(QUICK_SLOT, data, None, &mut images),
+                None => warn!("Nothing to save before the first line"),
+            },
+            InputAction::QuickLoad => {
+                let Some(save) = save_slots.0.get(&QUICK_SLOT) else {
+                    warn!("Quick load pressed without a quick save");
+                    continue;
+                };
+                if let Err(e) = loaded_scripts.check_save(&save.data) {
+                    warn!("Quick save can't be loaded: {:#}", e);
+                    continue;
+                }
+                info!("[ Loading quick save ]");
+                sabi_state.set(SabiState::Idle);
+                controller_writer.write(ControllersSetStateMessage(SabiState::Idle));
+                resume_writer.write(SabiResume(save.data.clone()));
+                // Anything read after the load belongs to the torn down script
+                return Ok(());
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
+fn hide_ui(
+    mut commands: Commands,
+    mut q_nodes: Query<(Entity, &mut Node), InterfaceNodes>,
+) {
+    // Display is used instead of Visibility, which already tracks the dialogue progress
+    let mut hidden = HiddenUiDisplay::default();
+    for (entity, mut node) in &mut q_nodes {
+        hidden.0.push((entity, node.display));
+        node.display = Display::None;
+    }
+    commands.insert_resource(hidden);
+}
+fn show_ui(
+    mut commands: Commands,
+    hidden: Option<Res<HiddenUiDisplay>>,
+    mut q_nodes: Query<&mut Node>,
+) {
+    let Some(hidden) = hidden else { return; };
+    for (entity, display) in &hidden.0 {
+        if let Ok(mut node) = q_nodes.get_mut(*entity) {
+            node.display = *display;
+        }
+    }
+    commands.remove_resource::<HiddenUiDisplay>();
+}
+/// The system menu stays spawned behind the panels it opens, hidden until they are closed
+fn show_system_menu(mut system_menu: Single<&mut Node, With<SystemMenuPanel>>) {
+    system_menu.display = Display::Flex;
+}
+fn hide_system_menu(mut system_menu: Query<&mut Node, With<SystemMenuPanel>>) {
+    for mut node in &mut system_menu {
+        node.display = Display::None;
+    }
+}
+fn store_scene_capture(
+    captured: On<ScreenshotCaptured>,
+    mut commands: Commands,
+) {
+    commands.insert_resource(SceneCapture(captured.image.clone()));
+}
+fn reveal_system_menu(
+    _captured: On<ScreenshotCaptured>,
+    mut system_menu: Query<&mut Visibility, With<SystemMenuPanel>>,
+) {
+    for mut visibility in &mut system_menu {
+        *visibility = Visibility::Inherited;
+    }
+}
+/// Brings the interface back on the first click or keypress, without advancing the script.
+/// Runs after [read_input_actions] so the same press is dropped by [handle_input_actions] while the UI is still hidden
+fn wait_ui_restore(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut sub_state: ResMut<NextState<ChatControllerSubState>>,
+) {
+    let pressed = keys.get_just_pressed().next().is_some()
+        || mouse_buttons.get_just_pressed().next().is_some()
+        || gamepads.iter().any(|gamepad| gamepad.get_just_pressed().next().is_some());
+    if pressed {
+        sub_state.set(ChatControllerSubState::Default);
+    }
+}
+/// Advances the current line on its own while skip or auto mode is on
+#[allow(clippy::too_many_arguments)]
+fn run_playback_modes(
+    mut commands: Commands,
+    mut playback: ResMut<PlaybackMode>,
+    scroll_stopwatch: Res<ChatScrollStopwatch>,
+    settings: Res<Settings>,
+    game_state: Res<VisualNovelState>,
+    q_buttons: Query<(Entity, &UiButtons)>,
+    vncontainer_visibility: Single<&Visibility, With<VNContainer>>,
+    message_text: Single<(&GUIScrollText, &SpeakerTextSpeed), With<MessageText>>,
+    info_text: Single<(&GUIScrollText, &Visibility), With<InfoTextComponent>>,
+    nvl_line: Query<&GUIScrollText, With<NvlCurrentLine>>,
+    bubble_texts: Query<&GUIScrollText, With<BubbleText>>,
+) {
+    if !playback.skip && !playback.auto {
+        return;
+    }
+    if playback.skip && !settings.skip_unread && !game_state.current_line_read {
+        playback.skip = false;
+        info!("[ Skip mode stopped on unread text ]");
+        return;
+    }
+
+    let (target, length, rate) = if *info_text.1 == Visibility::Visible {
+        (UiButtons::InfoText, info_text.0.message.chars().count(), settings.infotext_speed())
+    } else if let Ok(line) = nvl_line.single() {
+        (UiButtons::NvlPage, line.message.chars().count(), settings.text_speed)
+    } else if let Some(length) = bubble_texts.iter().map(|text| text.message.chars().count()).max() {
+        (UiButtons::SpeechBubble, length, settings.text_speed)
+    } else if **vncontainer_visibility == Visibility::Visible {
+        (UiButtons::TextBox, message_text.0.message.chars().count(), settings.text_speed * message_text.1.0)
+    } else {
+        return;
+    };
+
+    let read_time = length as f32 / rate + settings.auto_advance_delay;
+    if !playback.skip && scroll_stopwatch.0.elapsed_secs() < read_time {
+        return;
+    }
+    if let Some((entity, _)) = q_buttons.iter().find(|(_, button)| **button == target) {
+        commands.trigger(Activate { entity });
+    }
+}
+fn infotext_clicked(
+    mut scroll_stopwatch: ResMut<ChatScrollStopwatch>,
+    mut info_text: Single<(&mut GUIScrollText, &mut Text, &mut Visibility), (With<InfoTextComponent>, Without<NameText>, Without<MessageText>, Without<VNContainer>)>,
+    mut container_zidx: Single<&mut ZIndex, (With<InfoTextContainer>, Without<VNContainer>)>,
+    mut game_state: ResMut<VisualNovelState>,
+    reveal_rate: f32,
+) {
+    let length = (scroll_stopwatch.0.elapsed_secs() * reveal_rate) as usize;
+    if length < info_text.0.message.chars().count() {
+        // Skip message scrolling
+        scroll_stopwatch.0.set_elapsed(std::time::Duration::from_secs_f32(100000000.));
+        return;
+    }
+    println!("[ Infotext finished ]");
+
+    // Allow transitions to be run again
+    game_state.blocking = false;
+    *info_text.2 = Visibility::Hidden;
+    **container_zidx = ZIndex(INFOTEXT_Z_INDEX_INACTIVE);
+}
+fn textbox_clicked(
+    mut vncontainer_visibility: Single<&mut Visibility, (With<VNContainer>, Without<InfoTextContainer>, Without<InfoTextComponent>)>,
+    mut scroll_stopwatch: ResMut<ChatScrollStopwatch>,
+    message_text: Single<(&mut GUIScrollText, &mut Text, &SpeakerTextSpeed), (With<MessageText>, Without<NameText>, Without<InfoTextComponent>)>,
+    mut game_state: ResMut<VisualNovelState>,
+    reveal_rate: f32,
+) {
+    let length = (scroll_stopwatch.0.elapsed_secs() * reveal_rate) as usize;
+    if length < message_text.0.message.chars().count() {
+        // Skip message scrolling
+        scroll_stopwatch.0.set_elapsed(std::time::Duration::from_secs_f32(100000000.));
+        return;
+    }
+    println!("[ Player finished message ]");
+
+    // Hide textbox parent object
+    **vncontainer_visibility = Visibility::Hidden;
+
+    // Allow transitions to be run again
+    game_state.blocking = false;
+}
+#[allow(clippy::too_many_arguments)]
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    loaded_folders: Res<Assets<LoadedFolder>>,
+    folder_handle: Res<HandleToGuiFolder>,
+    slicing_assets: Res<Assets<GuiSlicing>>,
+    theme_assets: Res<Assets<GuiTheme>>,
+    user_defined_constants: Res<UserDefinedConstants>,
+    layout_assets: Res<Assets<ChatLayout>>,
+    mut controller_state: ResMut<NextState<ChatControllerState>>,
+    mut msg_writer: MessageWriter<ControllerReadyMessage>,
+) -> Result<(), BevyError> {
+    let mut gui_sprites = HashMap::<String, Handle<Image>>::new();
+    let mut gui_slicings = HashMap::<String, GuiSlicing>::new();
+    let mut gui_themes = HashMap::<String, GuiTheme>::new();
+    if let Some(state) = asset_server.get_load_state(folder_handle.0.id()) {
+        match state {
+            LoadState::Loaded => {
+                if let Some(loaded_folder) = loaded_folders.get(folder_handle.0.id()) {
+                    for handle in &loaded_folder.handles {
                         let path = handle.path()
                             .context("Error retrieving gui path")?;
+                        if handle.type_id() == TypeId::of::<GuiSlicing>() {
+                            let sprite = path.path().file_name()
+                                .and_then(|name| name.to_string_lossy().strip_suffix(".slice.json").map(str::to_owned))
+                                .context("GUI slice file has no sprite name")?;
+                            let slicing = slicing_assets.get(&handle.clone().typed::<GuiSlicing>())
+                                .context(format!("Could not find slicing of '{}'", sprite))?;
+                            gui_slicings.insert(sprite, slicing.clone());
+                            continue;
+                        }
+                        if handle.type_id() == TypeId::of::<GuiTheme>() {
+                            let name = path.path().file_name()
+                                .and_then(|name| name.to_string_lossy().strip_suffix(".theme.json").map(str::to_owned))
+                                .context("GUI theme file has no name")?;
+                            let theme = theme_assets.get(&handle.clone().typed::<GuiTheme>())
+                                .context(format!("Could not find theme '{}'", name))?;
+                            gui_themes.insert(name, theme.clone());
+                            continue;
+                        }
+                        if handle.type_id() == TypeId::of::<ChatLayout>() {
+                            let layout = layout_assets.get(&handle.clone().typed::<ChatLayout>())
+                                .context("Could not find chat layout")?;
+                            layout.validate()?;
+                            commands.insert_resource(CurrentChatLayout(layout.clone()));
+                            continue;
+                        }
                         let filename = path.path().file_stem()
                             .context("GUI file has no name")?
                             .to_string_lossy()
@@ -278,7 +1457,15 @@ fn setup(
                     return Err(anyhow::anyhow!("Could not find chat loaded folder!").into());
                 }
 
+                for (name, theme) in &gui_themes {
+                    theme.validate(name, &gui_sprites)?;
+                }
+                if let Some(name) = &user_defined_constants.gui_theme && !gui_themes.contains_key(name) {
+                    return Err(anyhow::anyhow!("Default GUI theme '{}' does not exist", name).into());
+                }
                 commands.insert_resource(GuiImages(gui_sprites));
+                commands.insert_resource(GuiSlicings(gui_slicings));
+                commands.insert_resource(GuiThemes(gui_themes));
                 controller_state.set(ChatControllerState::Idle);
                 msg_writer.write(ControllerReadyMessage(Controller::Chat));
                 info!("chat controller ready");
@@ -298,50 +1485,83 @@ fn import_gui_sprites(mut commands: Commands, asset_server: Res<AssetServer> ){
 fn spawn_chatbox(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    layout: Res<CurrentChatLayout>,
     ui_root: Single<Entity, With<UiRoot>>,
 ) -> Result<(), BevyError> {
-    // Spawn Backplate + Nameplate
-    // Container
+    commands.insert_resource(PlaybackMode::default());
+    commands.insert_resource(CurrentBubbleBackground::default());
+
+    // Container of the namebox and the textbox, laid out by the adv tree
     let container = commands.spawn(backplate_container()).id();
     commands.entity(ui_root.entity()).add_child(container);
-
-    // Top section: Nameplate flex container
-    let top_section = commands.spawn(top_section()).id();
-    commands.entity(container).add_child(top_section);
-
-    // Namebox Node
-    let namebox = commands.spawn(namebox()).id();
-    commands.entity(top_section).add_child(namebox);
-
-    // NameText
-    let nametext = commands.spawn(nametext(&asset_server)).id();
-    commands.entity(namebox).add_child(nametext);
-
-    // Backplate Node
-    let textbox_bg = commands.spawn(textbox()).id();
-    commands.entity(container).add_child(textbox_bg);
-
-    // MessageText
-    let messagetext = commands.spawn(messagetext(&asset_server)).id();
-    commands.entity(textbox_bg).add_child(messagetext);
-
-    // VN commands
-    let vn_commands = commands.spawn(vn_commands()?).id();
-    commands.entity(textbox_bg).add_child(vn_commands);
+    spawn_layout_tree(&mut commands, &layout.0, LayoutTree::Adv, container, |commands, widget| Ok(match widget {
+        LayoutWidget::Namebox => commands.spawn(namebox()).id(),
+        LayoutWidget::NameText => commands.spawn(nametext(&asset_server)).id(),
+        LayoutWidget::Textbox => commands.spawn(textbox()).id(),
+        LayoutWidget::MessageText => commands.spawn(messagetext(&asset_server)).id(),
+        LayoutWidget::Commands => commands.spawn(vn_commands()?).id(),
+        other => return Err(anyhow::anyhow!("{} is not an ADV widget", other.name()).into()),
+    }))?;
 
     // InfoText
-    let infotext_container = commands.spawn(infotext_container(&asset_server)).id();
+    let infotext_container = commands.spawn(infotext_container()).id();
     commands.entity(ui_root.entity()).add_child(infotext_container);
+    spawn_layout_tree(&mut commands, &layout.0, LayoutTree::Infotext, infotext_container, |commands, widget| Ok(match widget {
+        LayoutWidget::Infotext => commands.spawn(infotext(&asset_server)).id(),
+        other => return Err(anyhow::anyhow!("{} is not an infotext widget", other.name()).into()),
+    }))?;
+
+    // NVL page
+    let nvl_page = commands.spawn(nvl_page()?).id();
+    commands.entity(ui_root.entity()).add_child(nvl_page);
     
     Ok(())
 }
+/// Spawns the nodes of a [ChatLayout] tree under its `root`, the widgets by `spawn_widget` and the plain containers as empty nodes.
+/// They are laid out by `apply_chat_layout` once spawned.
+pub(crate) fn spawn_layout_tree(
+    commands: &mut Commands,
+    layout: &ChatLayout,
+    tree: LayoutTree,
+    root: Entity,
+    mut spawn_widget: impl FnMut(&mut Commands, LayoutWidget) -> Result<Entity, BevyError>,
+) -> Result<(), BevyError> {
+    commands.entity(root).insert(LayoutElement { tree, path: Vec::new(), widget: None });
+    spawn_layout_children(commands, layout.tree(tree), tree, &mut Vec::new(), root, &mut spawn_widget)
+}
+fn spawn_layout_children(
+    commands: &mut Commands,
+    node: &NodeLayout,
+    tree: LayoutTree,
+    path: &mut Vec<usize>,
+    parent: Entity,
+    spawn_widget: &mut impl FnMut(&mut Commands, LayoutWidget) -> Result<Entity, BevyError>,
+) -> Result<(), BevyError> {
+    for (index, child) in node.children.iter().enumerate() {
+        path.push(index);
+        let entity = match child.widget {
+            Some(widget) => spawn_widget(commands, widget)?,
+            None => commands.spawn(Node::default()).id(),
+        };
+        commands.entity(entity).insert(LayoutElement { tree, path: path.clone(), widget: child.widget });
+        commands.entity(parent).add_child(entity);
+        spawn_layout_children(commands, child, tree, path, entity, spawn_widget)?;
+        path.pop();
+    }
+    Ok(())
+}
+#[allow(clippy::too_many_arguments)]
 fn update_chatbox(
     mut event_message: MessageReader<CharacterSayMessage>,
     vncontainer_visibility: Single<&mut Visibility, With<VNContainer>>,
     mut name_text: Single<&mut Text, (With<NameText>, Without<MessageText>)>,
-    mut message_text: Single<(&mut GUIScrollText, &mut Text), (With<MessageText>, Without<NameText>)>,
+    mut message_text: Single<(&mut GUIScrollText, &mut Text, &SpeakerTextSpeed), (With<MessageText>, Without<NameText>)>,
     mut scroll_stopwatch: ResMut<ChatScrollStopwatch>,
     mut game_state: ResMut<VisualNovelState>,
+    mut speaking_messages: MessageWriter<CharacterSpeakingMessage>,
+    // Character whose line is still revealing
+    mut speaker: Local<Option<String>>,
+    settings: Res<Settings>,
     time: Res<Time>,
 ) -> Result<(), BevyError> {
     // Tick clock
@@ -351,41 +1571,130 @@ fn update_chatbox(
 
     /* STANDARD SAY EVENTS INITIALIZATION [Transition::Say] */
     for ev in event_message.read() {
+        // Lines are put on the NVL page by `update_nvl_page` instead
+        if game_state.stage.mode == PresentationMode::Nvl {
+            continue;
+        }
         game_state.blocking = true;
         // Make the visual novel ui container visible
         *vncontainer_visibility = Visibility::Visible;
         // Reset the scrolling timer
         scroll_stopwatch.0.set_elapsed(std::time::Duration::from_secs_f32(0.));
         // Update the name
-        let name = if ev.name == "[_PLAYERNAME_]" { game_state.playername.clone() } else { ev.name.clone() };
-        name_text.0 = name;
+        name_text.0 = speaker_name(&game_state, &ev.name);
         println!("MESSAGE {}", ev.message);
         message_text.0.message = ev.message.clone();
+        // A line skipped past stops its speaker
+        if let Some(name) = speaker.replace(ev.name.clone()) {
+            speaking_messages.write(CharacterSpeakingMessage { name, speaking: false });
+        }
+        speaking_messages.write(CharacterSpeakingMessage { name: ev.name.clone(), speaking: true });
     }
 
     // If vn container is hidden, ignore the next section dedicated to updating it
     if *vncontainer_visibility == Visibility::Hidden {
+        if let Some(name) = speaker.take() {
+            speaking_messages.write(CharacterSpeakingMessage { name, speaking: false });
+        }
         return Ok(());
     }
 
-    // Take the original string from the message object
-    let mut original_string: String = message_text.0.message.clone();
-
     // Get the section of the string according to the elapsed time
-    let length: u32 = (scroll_stopwatch.0.elapsed_secs() * 50.) as u32;
+    let length = (scroll_stopwatch.0.elapsed_secs() * settings.text_speed * message_text.2.0) as usize;
+
+    // The speaker stops once the whole line is shown
+    let revealed = length >= message_text.0.message.chars().count();
+    if let Some(name) = speaker.take_if(|_| revealed) {
+        speaking_messages.write(CharacterSpeakingMessage { name, speaking: false });
+    }
 
-    // Return the section and apply it to the text object
-    original_string.truncate(length as usize);
-    message_text.1.0 = original_string;
+    // Return the section and apply it to the text object, counted in characters like the reading speed
+    message_text.1.0 = message_text.0.message.chars().take(length).collect();
+
+    Ok(())
+}
+/// Applies the textbox, namebox, colours, font and text speed declared in the speaker's `character.json`.
+/// Narrators and unstyled characters get the GUI set by the script back.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn apply_speaker_style(
+    mut say_messages: MessageReader<CharacterSayMessage>,
+    mut textbox: Single<(&mut ImageNode, &mut Node), (With<TextBoxBackground>, Without<NameBoxBackground>)>,
+    mut namebox: Single<&mut ImageNode, (With<NameBoxBackground>, Without<TextBoxBackground>)>,
+    mut name_text: Single<(&mut TextColor, &mut TextFont), (With<NameText>, Without<MessageText>)>,
+    mut message_text: Single<(&mut TextColor, &mut TextFont, &mut SpeakerTextSpeed), (With<MessageText>, Without<NameText>)>,
+    current_plate: Option<Res<CurrentTextBoxBackground>>,
+    actor_configs: Res<ActorsConfigs>,
+    gui_images: Res<GuiImages>,
+    gui_slicings: Res<GuiSlicings>,
+    concrete_images: Res<Assets<Image>>,
+    game_state: Res<VisualNovelState>,
+    layout: Res<CurrentChatLayout>,
+    asset_server: Res<AssetServer>,
+) -> Result<(), BevyError> {
+    for msg in say_messages.read() {
+        let style = match actor_configs.0.get(&msg.name) {
+            Some(ActorConfig::Character(config)) => Some(config),
+            _ => None,
+        };
+
+        match style.and_then(|s| s.textbox.as_ref()) {
+            Some(sprite_id) => {
+                let image = gui_images.0.get(sprite_id)
+                    .context(format!("GUI asset '{}' of {} does not exist", sprite_id, msg.name))?;
+                // Skins shipping a slice file are meant to be sliced
+                let slicing = gui_slicings.0.get(sprite_id);
+                let image_mode = if slicing.is_some() { GuiImageMode::Sliced } else { GuiImageMode::Auto };
+                textbox.0.image = image.clone();
+                textbox.0.image_mode = gui_image_mode(&image_mode, image, slicing, &concrete_images)?;
+                textbox.1.padding = textbox_skin_padding(Some(sprite_id), &gui_slicings, &layout.0);
+            },
+            None => {
+                let plate = current_plate.as_ref().map(|p| p.0.clone()).unwrap_or_default();
+                textbox.0.image = plate.image;
+                textbox.0.image_mode = plate.image_mode;
+                textbox.1.padding = textbox_skin_padding(game_state.stage.textbox.as_ref().map(|(id, _)| id), &gui_slicings, &layout.0);
+            }
+        }
+
+        let namebox_id = style.and_then(|s| s.namebox.as_ref()).or(game_state.stage.namebox.as_ref());
+        namebox.image = match namebox_id {
+            Some(sprite_id) => gui_images.0.get(sprite_id)
+                .context(format!("GUI asset '{}' does not exist", sprite_id))?
+                .clone(),
+            None => Handle::default(),
+        };
+
+        *name_text.0 = TextColor(style_color(style.and_then(|s| s.name_color.as_deref()))?);
+        *message_text.0 = TextColor(style_color(style.and_then(|s| s.text_color.as_deref()))?);
+
+        let font = style.and_then(|s| s.font.as_ref());
+        let layout_font = |widget| layout.0.widget(widget).and_then(|node| node.font.as_ref());
+        let name_font = font.or(layout_font(LayoutWidget::NameText)).map_or(FONT_PATH, String::as_str);
+        let message_font = font.or(layout_font(LayoutWidget::MessageText)).map_or(FONT_PATH, String::as_str);
+        name_text.1.font = asset_server.load(name_font.to_owned());
+        message_text.1.font = asset_server.load(message_font.to_owned());
+
+        message_text.2.0 = style.and_then(|s| s.text_speed).unwrap_or(1.);
+    }
 
     Ok(())
 }
+fn style_color(hex: Option<&str>) -> Result<Color, BevyError> {
+    match hex {
+        Some(hex) => hex_color(hex),
+        None => Ok(Color::WHITE),
+    }
+}
+fn hex_color(hex: &str) -> Result<Color, BevyError> {
+    Ok(Srgba::hex(hex).with_context(|| format!("Invalid colour '{}'", hex))?.into())
+}
 fn update_infotext(
     mut event_message: MessageReader<InfoTextMessage>,
     mut info_text: Single<(&mut GUIScrollText, &mut Text, &mut Visibility), With<InfoTextComponent>>,
     mut info_text_container_zidx: Single<&mut ZIndex, With<InfoTextContainer>>,
     mut scroll_stopwatch: ResMut<ChatScrollStopwatch>,
     mut game_state: ResMut<VisualNovelState>,
+    settings: Res<Settings>,
     time: Res<Time>,
 ) -> Result<(), BevyError> {
     // Tick clock
@@ -394,6 +1703,10 @@ fn update_infotext(
 
     /* STANDARD SAY EVENTS INITIALIZATION [Transition::Say] */
     for ev in event_message.read() {
+        // Narration is put on the NVL page by `update_nvl_page` instead
+        if game_state.stage.mode == PresentationMode::Nvl {
+            continue;
+        }
         game_state.blocking = true;
         // Reset the scrolling timer
         scroll_stopwatch.0.set_elapsed(std::time::Duration::from_secs_f32(0.));
@@ -404,18 +1717,181 @@ fn update_infotext(
         **info_text_container_zidx = ZIndex(INFOTEXT_Z_INDEX_ACTIVE);
     }
 
-    // Take the original string from the message object
-    let mut original_string: String = info_text.0.message.clone();
-
     // Get the section of the string according to the elapsed time
-    let length: u32 = (scroll_stopwatch.0.elapsed_secs() * 25.) as u32;
+    let length = (scroll_stopwatch.0.elapsed_secs() * settings.infotext_speed()) as usize;
 
-    // Return the section and apply it to the text object
-    original_string.truncate(length as usize);
-    info_text.1.0 = original_string;
+    // Return the section and apply it to the text object, counted in characters like the reading speed
+    info_text.1.0 = info_text.0.message.chars().take(length).collect();
     
     Ok(())
 }
+#[allow(clippy::too_many_arguments)]
+fn update_nvl_page(
+    mut commands: Commands,
+    mut presentation_messages: MessageReader<PresentationChangeMessage>,
+    mut say_messages: MessageReader<CharacterSayMessage>,
+    mut info_text_messages: MessageReader<InfoTextMessage>,
+    mut page_visibility: Single<&mut Visibility, With<NvlPage>>,
+    lines_container: Single<Entity, With<NvlLines>>,
+    q_lines: Query<Entity, With<NvlLine>>,
+    mut current_line: Query<(Entity, &GUIScrollText, &mut TextSpan), With<NvlCurrentLine>>,
+    mut scroll_stopwatch: ResMut<ChatScrollStopwatch>,
+    game_state: Res<VisualNovelState>,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+) {
+    for msg in presentation_messages.read() {
+        // Both a mode change and a page clear start from an empty page
+        for line in &q_lines {
+            commands.entity(line).despawn();
+        }
+        if let PresentationChangeMessage::Mode(mode) = msg {
+            **page_visibility = if *mode == PresentationMode::Nvl { Visibility::Visible } else { Visibility::Hidden };
+        }
+    }
+
+    // Get the section of the string according to the elapsed time
+    if let Ok((_, scroll_text, mut text_span)) = current_line.single_mut() {
+        let length = (scroll_stopwatch.0.elapsed_secs() * settings.text_speed) as usize;
+        text_span.0 = scroll_text.message.chars().take(length).collect();
+    }
+
+    if game_state.stage.mode == PresentationMode::Nvl {
+        let lines: Vec<PageLine> = say_messages.read()
+            .map(|ev| PageLine { name: Some(ev.name.clone()), text: ev.message.clone() })
+            .chain(info_text_messages.read().map(|ev| PageLine { name: None, text: ev.text.clone() }))
+            .collect();
+        if lines.is_empty() {
+            return;
+        }
+        // A line that was skipped past stays on the page in full
+        for (entity, scroll_text, mut text_span) in &mut current_line {
+            text_span.0 = scroll_text.message.clone();
+            // The line may have just been cleared off the page
+            commands.entity(entity).try_remove::<NvlCurrentLine>();
+        }
+        // Reset the scrolling timer
+        scroll_stopwatch.0.set_elapsed(std::time::Duration::from_secs_f32(0.));
+        // Lines said together arrive in the same frame, only the last one scrolls so there is a single current line
+        let last = lines.len() - 1;
+        for (i, line) in lines.iter().enumerate() {
+            info!("NVL {}", line.text);
+            spawn_nvl_line(&mut commands, *lines_container, line, i != last, &game_state, &asset_server);
+        }
+    } else {
+        // ADV lines are handled by `update_chatbox` and `update_infotext`
+        say_messages.clear();
+        info_text_messages.clear();
+    }
+}
+/// Keeps the single [VnCommands] bar on whichever of the NVL page and the node the chat layout puts it in is in use
+fn dock_vn_commands(
+    mut commands: Commands,
+    nvl_page: Single<(Entity, Ref<Visibility>), With<NvlPage>>,
+    vn_commands: Single<(Entity, &ChildOf, Option<&VnCommandsHome>), With<VnCommands>>,
+) {
+    let (page, visibility) = nvl_page.into_inner();
+    if !visibility.is_changed() {
+        return;
+    }
+    let (entity, parent, home) = vn_commands.into_inner();
+    if *visibility == Visibility::Visible {
+        if parent.parent() != page {
+            commands.entity(entity).insert(VnCommandsHome(parent.parent()));
+            commands.entity(page).add_child(entity);
+        }
+    } else if let Some(home) = home {
+        commands.entity(home.0).add_child(entity);
+    }
+}
+#[allow(clippy::too_many_arguments)]
+fn update_speech_bubbles(
+    mut commands: Commands,
+    mut bubble_messages: MessageReader<SpeechBubbleMessage>,
+    ui_root: Single<Entity, With<UiRoot>>,
+    q_bubbles: Query<Entity, With<SpeechBubble>>,
+    mut q_bubble_texts: Query<(&GUIScrollText, &mut Text), With<BubbleText>>,
+    mut scroll_stopwatch: ResMut<ChatScrollStopwatch>,
+    bubble_background: Res<CurrentBubbleBackground>,
+    game_state: Res<VisualNovelState>,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+) {
+    for msg in bubble_messages.read() {
+        for bubble in &q_bubbles {
+            commands.entity(bubble).despawn();
+        }
+        // Reset the scrolling timer
+        scroll_stopwatch.0.set_elapsed(std::time::Duration::from_secs_f32(0.));
+
+        for (slot, line) in msg.lines.iter().enumerate() {
+            info!("BUBBLE {}: {}", line.name, line.text);
+            let bubble = SpeechBubble { speaker: line.name.clone(), slot };
+            let bubble = commands.spawn(speech_bubble(
+                bubble,
+                speaker_name(&game_state, &line.name),
+                line.text.clone(),
+                &bubble_background,
+                &asset_server,
+            )).id();
+            commands.entity(ui_root.entity()).add_child(bubble);
+        }
+    }
+
+    // Get the section of the string according to the elapsed time
+    let length = (scroll_stopwatch.0.elapsed_secs() * settings.text_speed) as usize;
+    for (scroll_text, mut text) in &mut q_bubble_texts {
+        text.0 = scroll_text.message.chars().take(length).collect();
+    }
+}
+/// Keeps every [SpeechBubble] above the head of its speaker, following them as they move or zoom.
+/// Speakers that are not on stage get their bubbles lined up from the left.
+fn anchor_speech_bubbles(
+    mut q_bubbles: Query<(&SpeechBubble, &mut Node)>,
+    q_actors: Query<(&ActorConfig, &Node, &CharacterZoom), Without<SpeechBubble>>,
+) {
+    for (bubble, mut node) in &mut q_bubbles {
+        let actor = q_actors.iter().find_map(|(config, node, zoom)| match config {
+            ActorConfig::Character(c) if c.name == bubble.speaker => Some((c, node, zoom)),
+            _ => None,
+        });
+        let (left, bottom) = match actor {
+            Some((config, actor_node, zoom)) => {
+                let actor_bottom = if let Val::Percent(bottom) = actor_node.bottom { bottom } else { 0. };
+                // Zooms scale the sprite around its feet
+                let actor_height = config.height.unwrap_or(CHARACTER_HEIGHT_PERCENTAGE) * zoom.0;
+                (actor_node.left, percent(actor_bottom + actor_height))
+            },
+            None => (percent(5. + bubble.slot as f32 * 35.), percent(CHARACTER_HEIGHT_PERCENTAGE)),
+        };
+        // Compared first so the bubbles are not flagged as changed every frame
+        if node.left != left || node.bottom != bottom {
+            node.left = left;
+            node.bottom = bottom;
+        }
+    }
+}
+fn spawn_nvl_line(
+    commands: &mut Commands,
+    lines_container: Entity,
+    line: &PageLine,
+    revealed: bool,
+    game_state: &VisualNovelState,
+    asset_server: &Res<AssetServer>,
+) {
+    let name = line.name.as_ref().map(|name| speaker_name(game_state, name));
+    let line_entity = commands.spawn(nvl_line(name, asset_server)).id();
+    commands.entity(lines_container).add_child(line_entity);
+
+    let text = commands.spawn(nvl_text(line.text.clone(), revealed, asset_server)).id();
+    commands.entity(line_entity).add_child(text);
+    if !revealed {
+        commands.entity(text).insert(NvlCurrentLine);
+    }
+}
+fn speaker_name(game_state: &VisualNovelState, name: &str) -> String {
+    if name == "[_PLAYERNAME_]" { game_state.playername.clone() } else { name.to_owned() }
+}
 fn wait_trigger(
     mut msg_reader: MessageReader<ControllersSetStateMessage>,
     mut controller_state: ResMut<NextState<ChatControllerState>>,
@@ -424,53 +1900,519 @@ fn wait_trigger(
         controller_state.set(msg.0.into());
     }
 }
+fn gui_image_mode(
+    image_mode: &GuiImageMode,
+    image: &Handle<Image>,
+    slicing: Option<&GuiSlicing>,
+    concrete_images: &Res<Assets<Image>>,
+) -> Result<NodeImageMode, BevyError> {
+    Ok(match (image_mode, slicing) {
+        (GuiImageMode::Sliced, Some(slicing)) => {
+            NodeImageMode::Sliced(TextureSlicer {
+                border: BorderRect {
+                    top: slicing.border.top,
+                    bottom: slicing.border.bottom,
+                    left: slicing.border.left,
+                    right: slicing.border.right,
+                },
+                center_scale_mode: slicing.center.into(),
+                sides_scale_mode: slicing.sides.into(),
+                max_corner_scale: slicing.max_corner_scale,
+            })
+        },
+        (GuiImageMode::Sliced, None) => {
+            let concrete_image = concrete_images.get(image).context("Could not find image")?;
+            let concrete_image_size = concrete_image.texture_descriptor.size;
+            let slice_cuts = BorderRect {
+                top: concrete_image_size.height as f32 / 5.,
+                bottom: concrete_image_size.height as f32 / 5.,
+                left: concrete_image_size.width as f32 / 5.,
+                right: concrete_image_size.width as f32 / 5.
+            };
+            NodeImageMode::Sliced(TextureSlicer {
+                border: slice_cuts,
+                center_scale_mode: SliceScaleMode::Tile { stretch_value: 1. },
+                sides_scale_mode: SliceScaleMode::Tile { stretch_value: 1. },
+                ..default()
+            })
+        },
+        (GuiImageMode::Auto, _) => NodeImageMode::Auto
+    })
+}
+/// Padding of the textbox with the given skin, the built-in one when its slicing has none.
+/// The sides set by the layout file take precedence over both
+fn textbox_skin_padding(sprite_id: Option<&String>, gui_slicings: &GuiSlicings, layout: &ChatLayout) -> UiRect {
+    let mut padding = sprite_id.and_then(|id| gui_slicings.0.get(id))
+        .and_then(|slicing| slicing.padding)
+        .map(UiRect::from)
+        .unwrap_or(textbox_padding());
+    if let Some(textbox) = layout.widget(LayoutWidget::Textbox) {
+        textbox.padding.apply(&mut padding);
+    }
+    padding
+}
+/// Checks the textbox and namebox skins of every character, the actors are loaded by the time the chat runs
+fn validate_character_skins(
+    actor_configs: Res<ActorsConfigs>,
+    gui_images: Res<GuiImages>,
+) -> Result<(), BevyError> {
+    for config in actor_configs.0.values() {
+        let ActorConfig::Character(character) = config else {
+            continue;
+        };
+        for (field, sprite_id) in [("textbox", &character.textbox), ("namebox", &character.namebox)] {
+            if let Some(sprite_id) = sprite_id && !gui_images.0.contains_key(sprite_id) {
+                return Err(anyhow::anyhow!("Character '{}': {} skin '{}' is not a GUI asset", character.name, field, sprite_id).into());
+            }
+        }
+    }
+    Ok(())
+}
+fn gui_image<'a>(gui_images: &'a GuiImages, sprite_id: &str) -> Result<&'a Handle<Image>, BevyError> {
+    Ok(gui_images.0.get(sprite_id)
+        .context(format!("GUI asset '{}' does not exist", sprite_id))?)
+}
+/// Skin of the speech bubbles with its slicing padding
+fn bubble_skin(
+    sprite_id: &str,
+    image_mode: &GuiImageMode,
+    gui_images: &GuiImages,
+    gui_slicings: &GuiSlicings,
+    concrete_images: &Res<Assets<Image>>,
+) -> Result<CurrentBubbleBackground, BevyError> {
+    let image = gui_images.0.get(sprite_id)
+        .context(format!("GUI asset '{}' does not exist", sprite_id))?;
+    let slicing = gui_slicings.0.get(sprite_id);
+    Ok(CurrentBubbleBackground(
+        Some(ImageNode {
+            image: image.clone(),
+            image_mode: gui_image_mode(image_mode, image, slicing, concrete_images)?,
+            ..default()
+        }),
+        slicing.and_then(|slicing| slicing.padding).map(UiRect::from),
+    ))
+}
+#[allow(clippy::too_many_arguments)]
 fn update_gui(
     mut commands: Commands,
     mut change_messages: MessageReader<GUIChangeMessage>,
     mut q_image_node: Query<
-        (&mut ImageNode, Has<TextBoxBackground>, Has<NameBoxBackground>),
+        (&mut ImageNode, &mut Node, Has<TextBoxBackground>, Has<NameBoxBackground>),
         Or<(With<TextBoxBackground>, With<NameBoxBackground>)>
     >,
     concrete_images: Res<Assets<Image>>,
     gui_images: Res<GuiImages>,
+    gui_slicings: Res<GuiSlicings>,
+    gui_themes: Res<GuiThemes>,
+    layout: Res<CurrentChatLayout>,
 ) -> Result<(), BevyError> {
     for ev in change_messages.read() {
-        let image = gui_images.0.get(&ev.sprite_id)
-            .context(format!("GUI asset '{}' does not exist", ev.sprite_id))?;
         match ev.gui_target {
             GuiChangeTarget::TextBoxBackground => {
-                let mut target = q_image_node.iter_mut().find(|q| q.1 == true)
-                    .context("Unable to find textbox")?.0;
+                let image = gui_image(&gui_images, &ev.sprite_id)?;
+                let (mut target, mut node, ..) = q_image_node.iter_mut().find(|q| q.2)
+                    .context("Unable to find textbox")?;
                 target.image = image.clone();
-                target.image_mode = match ev.image_mode {
-                    GuiImageMode::Sliced => {
-                        let concrete_image = concrete_images.get(image).context("Could not find image")?;
-                        let concrete_image_size = concrete_image.texture_descriptor.size;
-                        let slice_cuts = BorderRect {
-                            top: concrete_image_size.height as f32 / 5.,
-                            bottom: concrete_image_size.height as f32 / 5.,
-                            left: concrete_image_size.width as f32 / 5.,
-                            right: concrete_image_size.width as f32 / 5.
-                        };
-                        NodeImageMode::Sliced(TextureSlicer {
-                            border: slice_cuts,
-                            center_scale_mode: SliceScaleMode::Tile { stretch_value: 1. },
-                            sides_scale_mode: SliceScaleMode::Tile { stretch_value: 1. },
-                            ..default()
-                        })
-                    },
-                    GuiImageMode::Auto => NodeImageMode::Auto
-                };
+                target.image_mode = gui_image_mode(&ev.image_mode, image, gui_slicings.0.get(&ev.sprite_id), &concrete_images)?;
+                node.padding = textbox_skin_padding(Some(&ev.sprite_id), &gui_slicings, &layout.0);
                 commands.insert_resource(CurrentTextBoxBackground(target.clone()));
             }
             GuiChangeTarget::NameBoxBackground => {
-                let mut target = q_image_node.iter_mut().find(|q| q.2 == true)
+                let image = gui_image(&gui_images, &ev.sprite_id)?;
+                let mut target = q_image_node.iter_mut().find(|q| q.3)
                     .context("Unable to find namebox")?.0;
 
                 target.image = image.clone();
             }
+            GuiChangeTarget::BubbleBackground => {
+                // Applies to the bubbles spawned from now on
+                commands.insert_resource(bubble_skin(&ev.sprite_id, &ev.image_mode, &gui_images, &gui_slicings, &concrete_images)?);
+            }
+            GuiChangeTarget::Theme => {
+                // The widgets are reskinned by `apply_gui_theme`
+                if !gui_themes.0.contains_key(&ev.sprite_id) {
+                    return Err(anyhow::anyhow!("GUI theme '{}' does not exist", ev.sprite_id).into());
+                }
+                commands.insert_resource(CurrentGuiTheme(Some(ev.sprite_id.clone())));
+            }
+        };
+    }
+
+    Ok(())
+}
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn restore_gui(
+    mut commands: Commands,
+    mut restore_message: MessageReader<StageRestoreMessage>,
+    mut textbox: Single<(&mut ImageNode, &mut Node), (With<TextBoxBackground>, Without<NameBoxBackground>)>,
+    mut namebox: Single<&mut ImageNode, (With<NameBoxBackground>, Without<TextBoxBackground>)>,
+    mut info_text: Single<(&mut GUIScrollText, &mut Visibility), With<InfoTextComponent>>,
+    mut info_text_container_zidx: Single<&mut ZIndex, With<InfoTextContainer>>,
+    q_bubbles: Query<Entity, With<SpeechBubble>>,
+    mut current_theme: ResMut<CurrentGuiTheme>,
+    concrete_images: Res<Assets<Image>>,
+    gui_images: Res<GuiImages>,
+    gui_slicings: Res<GuiSlicings>,
+    layout: Res<CurrentChatLayout>,
+) -> Result<(), BevyError> {
+    for msg in restore_message.read() {
+        match &msg.0.textbox {
+            Some((sprite_id, image_mode)) => {
+                let image = gui_images.0.get(sprite_id)
+                    .context(format!("GUI asset '{}' does not exist", sprite_id))?;
+                textbox.0.image = image.clone();
+                textbox.0.image_mode = gui_image_mode(image_mode, image, gui_slicings.0.get(sprite_id), &concrete_images)?;
+            },
+            None => *textbox.0 = ImageNode::default(),
+        }
+        textbox.1.padding = textbox_skin_padding(msg.0.textbox.as_ref().map(|(id, _)| id), &gui_slicings, &layout.0);
+        commands.insert_resource(CurrentTextBoxBackground(textbox.0.clone()));
+
+        namebox.image = match &msg.0.namebox {
+            Some(sprite_id) => gui_images.0.get(sprite_id)
+                .context(format!("GUI asset '{}' does not exist", sprite_id))?
+                .clone(),
+            None => Handle::default(),
+        };
+
+        let bubble = match &msg.0.bubble {
+            Some((sprite_id, image_mode)) => bubble_skin(sprite_id, image_mode, &gui_images, &gui_slicings, &concrete_images)?,
+            None => CurrentBubbleBackground::default(),
         };
+        commands.insert_resource(bubble);
+        current_theme.set_if_neq(CurrentGuiTheme(msg.0.theme.clone()));
+
+        // Bubbles and infotext are run again by the restored cursor if needed
+        for bubble in &q_bubbles {
+            commands.entity(bubble).despawn();
+        }
+        *info_text.0 = GUIScrollText::default();
+        *info_text.1 = Visibility::Hidden;
+        **info_text_container_zidx = ZIndex(INFOTEXT_Z_INDEX_INACTIVE);
     }
 
     Ok(())
 }
+fn restore_nvl_page(
+    mut commands: Commands,
+    mut restore_message: MessageReader<StageRestoreMessage>,
+    mut page_visibility: Single<&mut Visibility, With<NvlPage>>,
+    lines_container: Single<Entity, With<NvlLines>>,
+    q_lines: Query<Entity, With<NvlLine>>,
+    game_state: Res<VisualNovelState>,
+    asset_server: Res<AssetServer>,
+) {
+    for msg in restore_message.read() {
+        for line in &q_lines {
+            commands.entity(line).despawn();
+        }
+        **page_visibility = if msg.0.mode == PresentationMode::Nvl { Visibility::Visible } else { Visibility::Hidden };
+
+        // The restored line itself is put back on the page when its statement runs again
+        for line in &msg.0.page {
+            spawn_nvl_line(&mut commands, *lines_container, line, true, &game_state, &asset_server);
+        }
+    }
+}
+fn apply_textbox_opacity(
+    settings: Res<Settings>,
+    mut textbox: Single<&mut ImageNode, With<TextBoxBackground>>,
+) {
+    // Compared first so the textbox is not flagged as changed every frame
+    if textbox.color.alpha() != settings.textbox_opacity {
+        textbox.color.set_alpha(settings.textbox_opacity);
+    }
+}
+/// Picks up the layout file when it is edited, or added, while the game runs.
+/// Sizes and fonts apply to the spawned nodes, a changed tree is only built with the next chat.
+fn reload_chat_layout(
+    mut asset_events: MessageReader<AssetEvent<ChatLayout>>,
+    layout_assets: Res<Assets<ChatLayout>>,
+    mut layout: ResMut<CurrentChatLayout>,
+) {
+    for event in asset_events.read() {
+        let (AssetEvent::Added { id } | AssetEvent::Modified { id }) = event else {
+            continue;
+        };
+        if let Some(modified) = layout_assets.get(*id) {
+            if let Err(e) = modified.validate() {
+                warn!("keeping the previous chat layout: {}", e);
+                continue;
+            }
+            info!("chat layout reloaded");
+            layout.0 = modified.clone();
+        }
+    }
+}
+/// Lays out every [LayoutElement] when the layout changes, and the new ones as they are spawned
+#[allow(clippy::type_complexity)]
+fn apply_chat_layout(
+    mut commands: Commands,
+    layout: Res<CurrentChatLayout>,
+    asset_server: Res<AssetServer>,
+    mut q_elements: Query<(Entity, Ref<LayoutElement>, Option<&LayoutBase>, &mut Node, Option<&mut TextFont>)>,
+) {
+    for (entity, element, base, mut node, font) in &mut q_elements {
+        if !layout.is_changed() && !element.is_added() {
+            continue;
+        }
+
+        let base = match base {
+            Some(base) => base.clone(),
+            None => {
+                let base = LayoutBase { node: node.clone(), font: font.as_deref().cloned() };
+                commands.entity(entity).insert(base.clone());
+                base
+            }
+        };
+
+        // The tree changed since the node was built, it keeps its layout until the chat is built again
+        let Some(element_layout) = layout.0.element(&element) else {
+            continue;
+        };
+        let mut laid_out = base.node;
+        element_layout.apply(&mut laid_out);
+        // Hidden nodes stay hidden, and the textbox padding follows its skin, see `textbox_skin_padding`
+        laid_out.display = node.display;
+        if element.widget == Some(LayoutWidget::Textbox) {
+            laid_out.padding = node.padding;
+            element_layout.padding.apply(&mut laid_out.padding);
+        }
+        *node = laid_out;
+
+        if let (Some(mut font), Some(base_font)) = (font, base.font) {
+            font.font = match &element_layout.font {
+                Some(path) => asset_server.load(path),
+                None => base_font.font,
+            };
+            font.font_size = element_layout.font_size.unwrap_or(base_font.font_size);
+        }
+    }
+}
+/// Skins every [ThemedWidget] when the theme changes, and the new ones as they are spawned
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn apply_gui_theme(
+    mut commands: Commands,
+    current_theme: Res<CurrentGuiTheme>,
+    user_defined_constants: Res<UserDefinedConstants>,
+    gui_themes: Res<GuiThemes>,
+    gui_images: Res<GuiImages>,
+    gui_slicings: Res<GuiSlicings>,
+    concrete_images: Res<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+    mut q_widgets: Query<(Entity, Ref<ThemedWidget>, Option<&ThemeBase>, &mut BackgroundColor, &mut BorderColor, Option<&mut ImageNode>)>,
+    mut q_texts: Query<(&mut TextColor, &mut TextFont, Option<&TextThemeBase>)>,
+    q_children: Query<&Children>,
+    q_nested: Query<(), With<ThemedWidget>>,
+) -> Result<(), BevyError> {
+    let theme = match current_theme.0.as_ref().or(user_defined_constants.gui_theme.as_ref()) {
+        Some(name) => Some(gui_themes.0.get(name).context(format!("GUI theme '{}' does not exist", name))?),
+        None => None,
+    };
+
+    for (entity, widget, base, mut background, mut border, image) in &mut q_widgets {
+        if !current_theme.is_changed() && !widget.is_added() {
+            continue;
+        }
+        let style = theme.map(|theme| theme.style(*widget).clone()).unwrap_or_default();
+
+        // Until a theme is applied the widget still has the look it was built with
+        let base = match base {
+            Some(base) => base.clone(),
+            None => {
+                let base = ThemeBase {
+                    background: *background,
+                    border: *border,
+                    image: image.as_deref().cloned(),
+                };
+                commands.entity(entity).insert(base.clone());
+                base
+            }
+        };
+
+        *background = match &style.color {
+            Some(hex) => BackgroundColor(hex_color(hex)?),
+            None => base.background,
+        };
+        *border = match &style.border_color {
+            Some(hex) => BorderColor::all(hex_color(hex)?),
+            None => base.border,
+        };
+        match (&style.image, image) {
+            (Some(sprite_id), image) => {
+                let handle = gui_image(&gui_images, sprite_id)?;
+                let slicing = gui_slicings.0.get(sprite_id);
+                let image_mode = if slicing.is_some() { GuiImageMode::Sliced } else { GuiImageMode::Auto };
+                let skin = ImageNode {
+                    image: handle.clone(),
+                    image_mode: gui_image_mode(&image_mode, handle, slicing, &concrete_images)?,
+                    ..default()
+                };
+                match image {
+                    Some(mut image) => *image = skin,
+                    None => { commands.entity(entity).insert(skin); }
+                }
+            },
+            (None, Some(mut image)) => match base.image {
+                Some(base_image) => *image = base_image,
+                None => { commands.entity(entity).remove::<ImageNode>(); }
+            },
+            (None, None) => {}
+        }
+
+        for text in widget_descendants(entity, &q_children, &q_nested) {
+            let Ok((mut color, mut font, text_base)) = q_texts.get_mut(text) else {
+                continue;
+            };
+            let text_base = match text_base {
+                Some(text_base) => text_base.clone(),
+                None => {
+                    let text_base = TextThemeBase { color: color.0, font: font.clone() };
+                    commands.entity(text).insert(text_base.clone());
+                    text_base
+                }
+            };
+            color.0 = match &style.text_color {
+                Some(hex) => hex_color(hex)?,
+                None => text_base.color,
+            };
+            font.font = match &style.font {
+                Some(path) => asset_server.load(path),
+                None => text_base.font.font,
+            };
+            font.font_size = style.font_size.unwrap_or(text_base.font.font_size);
+        }
+    }
+
+    Ok(())
+}
+/// The widget and its descendants, leaving out the widgets nested in it which follow their own style
+fn widget_descendants(entity: Entity, q_children: &Query<&Children>, q_nested: &Query<(), With<ThemedWidget>>) -> Vec<Entity> {
+    let mut descendants = vec![entity];
+    let mut stack: Vec<Entity> = q_children.get(entity).map(|children| children.to_vec()).unwrap_or_default();
+    while let Some(child) = stack.pop() {
+        if q_nested.contains(child) {
+            continue;
+        }
+        descendants.push(child);
+        if let Ok(children) = q_children.get(child) {
+            stack.extend(children.iter());
+        }
+    }
+    descendants
+}
+/// Games started afterwards, and the title screen, go back to the default theme
+fn reset_gui_theme(mut commands: Commands) {
+    commands.insert_resource(CurrentGuiTheme::default());
+}
+fn update_settings_panel(
+    settings: Res<Settings>,
+    mut q_values: Query<(&SettingValueText, &mut Text)>,
+) {
+    for (value, mut text) in &mut q_values {
+        text.0 = settings.display(value.0);
+    }
+}
+fn refresh_slot_browser(
+    mut commands: Commands,
+    browser: Res<SlotBrowser>,
+    save_slots: Res<SaveSlots>,
+    q_panel: Query<Entity, With<SaveLoadPanel>>,
+    asset_server: Res<AssetServer>,
+) -> Result<(), BevyError> {
+    if !browser.is_changed() && !save_slots.is_changed() {
+        return Ok(());
+    }
+    for panel in &q_panel {
+        commands.entity(panel).despawn();
+    }
+    let panel = commands.spawn(save_load_panel(&browser, &save_slots, &asset_server)?).id();
+    commands.entity(browser.parent).add_child(panel);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_file_reads_every_field() {
+        let slicing: GuiSlicing = serde_json::from_str(r#"{
+            "border": { "top": 12, "bottom": 14, "left": 20, "right": 20 },
+            "center": "stretch",
+            "sides": { "tile": 2.5 },
+            "max_corner_scale": 0.5,
+            "padding": { "top": 8, "left": 16 }
+        }"#).unwrap();
+
+        assert_eq!(UiRect::from(slicing.border), UiRect { top: px(12.), bottom: px(14.), left: px(20.), right: px(20.) });
+        assert_eq!(SliceScaleMode::from(slicing.center), SliceScaleMode::Stretch);
+        assert_eq!(SliceScaleMode::from(slicing.sides), SliceScaleMode::Tile { stretch_value: 2.5 });
+        assert_eq!(slicing.max_corner_scale, 0.5);
+        assert_eq!(slicing.padding.map(UiRect::from), Some(UiRect { top: px(8.), bottom: px(0.), left: px(16.), right: px(0.) }));
+    }
+
+    #[test]
+    fn slice_file_defaults_to_tiling_without_padding() {
+        let slicing: GuiSlicing = serde_json::from_str(r#"{ "border": { "top": 10, "bottom": 10, "left": 10, "right": 10 } }"#).unwrap();
+
+        assert_eq!(SliceScaleMode::from(slicing.center), SliceScaleMode::Tile { stretch_value: 1. });
+        assert_eq!(SliceScaleMode::from(slicing.sides), SliceScaleMode::Tile { stretch_value: 1. });
+        assert_eq!(slicing.max_corner_scale, 1.);
+        assert!(slicing.padding.is_none());
+    }
+
+    #[test]
+    fn slice_file_needs_a_border_and_known_scale_modes() {
+        assert!(serde_json::from_str::<GuiSlicing>(r#"{ "center": "stretch" }"#).is_err());
+        assert!(serde_json::from_str::<GuiSlicing>(r#"{ "border": {}, "sides": "repeat" }"#).is_err());
+    }
+
+    #[test]
+    fn theme_file_rejects_misspelled_keys() {
+        let theme: GuiTheme = serde_json::from_str(r##"{ "menu": { "text_color": "#FFFFFF" } }"##).unwrap();
+        assert_eq!(theme.menu.text_color.as_deref(), Some("#FFFFFF"));
+        assert!(theme.button.text_color.is_none());
+
+        assert!(serde_json::from_str::<GuiTheme>(r##"{ "buttons": { "color": "#000000" } }"##).is_err());
+        assert!(serde_json::from_str::<GuiTheme>(r##"{ "menu": { "text_colour": "#FFFFFF" } }"##).is_err());
+    }
+
+    #[test]
+    fn default_chat_layout_is_valid() {
+        assert!(ChatLayout::default().validate().is_ok());
+    }
+
+    #[test]
+    fn layout_file_rebuilds_a_tree() {
+        let layout: ChatLayout = serde_json::from_str(r#"{ "infotext": {
+            "padding": { "top": "5%" },
+            "children": [{ "children": [{ "widget": "infotext", "font_size": 30 }] }]
+        } }"#).unwrap();
+
+        assert!(layout.validate().is_ok());
+        assert_eq!(layout.widget(LayoutWidget::Infotext).and_then(|node| node.font_size), Some(30.));
+        let element = LayoutElement { tree: LayoutTree::Infotext, path: vec![0, 0], widget: Some(LayoutWidget::Infotext) };
+        assert!(layout.element(&element).is_some());
+        // The default tree keeps it one level up
+        assert!(ChatLayout::default().element(&element).is_none());
+    }
+
+    #[test]
+    fn layout_file_needs_each_widget_once_in_its_tree() {
+        let missing: ChatLayout = serde_json::from_str(r#"{ "infotext": { "children": [] } }"#).unwrap();
+        assert!(missing.validate().is_err());
+
+        let twice: ChatLayout = serde_json::from_str(r#"{ "infotext": { "children": [{ "widget": "infotext" }, { "widget": "infotext" }] } }"#).unwrap();
+        assert!(twice.validate().is_err());
+
+        let misplaced: ChatLayout = serde_json::from_str(r#"{ "infotext": { "children": [{ "widget": "infotext" }, { "widget": "close" }] } }"#).unwrap();
+        assert!(misplaced.validate().is_err());
+
+        let outside_scroll: ChatLayout = serde_json::from_str(r#"{ "history": { "children": [
+            { "widget": "history_title" }, { "widget": "history_scroll" }, { "widget": "history_text" },
+            { "widget": "scrollbar" }, { "widget": "close" }
+        ] } }"#).unwrap();
+        assert!(outside_scroll.validate().is_err());
+    }
+}
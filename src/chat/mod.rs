@@ -1,5 +1,5 @@
 pub(crate) mod controller;
-mod ui;
+pub(crate) mod ui;
 
 pub(crate) use controller::ChatController;
 pub(crate) use controller::GUIScrollText;
@@ -8,4 +8,7 @@ pub(crate) use controller::GUIChangeMessage;
 
 const INFOTEXT_Z_INDEX_ACTIVE: i32 = 4;
 const INFOTEXT_Z_INDEX_INACTIVE: i32 = -1;
-const UI_Z_INDEX: i32 = 5;
\ No newline at end of file
+pub(crate) const UI_Z_INDEX: i32 = 5;
+/// Actors put above the textbox, still under the menus
+pub(crate) const ABOVE_TEXTBOX_Z_INDEX: i32 = UI_Z_INDEX + 1;
+pub(crate) const MENU_Z_INDEX: i32 = UI_Z_INDEX + 2;
\ No newline at end of file
@@ -4,7 +4,7 @@ use bevy_ui_widgets::Button;
 use crate::{
         chat::{
             GUIScrollText, INFOTEXT_Z_INDEX_INACTIVE, UI_Z_INDEX, controller::{
-                InfoTextComponent, InfoTextContainer, MessageText, NameBoxBackground, NameText, TextBoxBackground, UiButtons, VNContainer, VnCommands
+                InfoTextComponent, InfoTextContainer, MessageText, NameBoxBackground, NameText, SpeakerTextSpeed, TextBoxBackground, ThemedWidget, UiButtons, VNContainer, VnCommands
             }, ui::FONT_PATH
     },
     compiler::controller::SabiState
@@ -27,12 +27,6 @@ pub(in crate::chat) fn backplate_container() -> impl Bundle {
     )
 }
 
-pub(in crate::chat) fn top_section() -> impl Bundle {
-    // Needed for horizontal flex,
-    // open to modification
-    Node::default()
-}
-
 pub(in crate::chat) fn namebox() -> impl Bundle {
     (
         ImageNode::default(),
@@ -59,7 +53,7 @@ pub(in crate::chat) fn nametext(asset_server: &Res<AssetServer>) -> impl Bundle
             font_size: 30.0,
             ..default()
         },
-        NameText
+        NameText,
     )
 }
 
@@ -69,11 +63,7 @@ pub(in crate::chat) fn textbox() -> impl Bundle {
         Node {
             width: percent(100.),
             min_height: percent(100.),
-            padding: UiRect {
-                top: percent(2.),
-                bottom: percent(2.),
-                ..UiRect::horizontal(percent(5.))
-            },
+            padding: textbox_padding(),
             ..default()
         },
         ZIndex(UI_Z_INDEX),
@@ -84,6 +74,15 @@ pub(in crate::chat) fn textbox() -> impl Bundle {
     )
 }
 
+/// Padding of the textbox for skins without slicing padding
+pub(in crate::chat) fn textbox_padding() -> UiRect {
+    UiRect {
+        top: percent(2.),
+        bottom: percent(2.),
+        ..UiRect::horizontal(percent(5.))
+    }
+}
+
 pub(in crate::chat) fn messagetext(asset_server: &Res<AssetServer>) -> impl Bundle {
     (
         Text::new("TEST"),
@@ -94,11 +93,12 @@ pub(in crate::chat) fn messagetext(asset_server: &Res<AssetServer>) -> impl Bund
             font_size: 30.0,
             ..default()
         },
-        MessageText
+        SpeakerTextSpeed(1.),
+        MessageText,
     )
 }
 
-pub(in crate::chat) fn infotext_container(asset_server: &Res<AssetServer>) -> impl Bundle {
+pub(in crate::chat) fn infotext_container() -> impl Bundle {
     (
         Node {
             width: percent(100),
@@ -117,13 +117,10 @@ pub(in crate::chat) fn infotext_container(asset_server: &Res<AssetServer>) -> im
         UiButtons::InfoText,
         InfoTextContainer,
         DespawnOnExit(SabiState::Running),
-        children![
-            infotext(asset_server)
-        ]
     )
 }
 
-fn infotext(asset_server: &Res<AssetServer>) -> impl Bundle {
+pub(in crate::chat) fn infotext(asset_server: &Res<AssetServer>) -> impl Bundle {
     (
         Text::new(""),
         GUIScrollText::default(),
@@ -153,6 +150,22 @@ pub(in crate::chat::ui) fn button(action: UiButtons) -> Result<impl Bundle, Bevy
         UiButtons::OpenHistory => (String::from("History"), PositionType::Relative),
         UiButtons::ExitHistory => (String::from("Close"), PositionType::Absolute),
         UiButtons::Rewind      => (String::from("Rewind"), PositionType::Relative),
+        UiButtons::RollForward => (String::from("Forward"), PositionType::Relative),
+        UiButtons::OpenSettings => (String::from("Settings"), PositionType::Relative),
+        UiButtons::ExitSettings => (String::from("Close"), PositionType::Absolute),
+        UiButtons::SettingDown(_) => (String::from("-"), PositionType::Relative),
+        UiButtons::SettingUp(_) => (String::from("+"), PositionType::Relative),
+        UiButtons::OpenSystemMenu => (String::from("Menu"), PositionType::Relative),
+        UiButtons::CloseSystemMenu => (String::from("Resume"), PositionType::Relative),
+        UiButtons::Save => (String::from("Save"), PositionType::Relative),
+        UiButtons::Load => (String::from("Load"), PositionType::Relative),
+        UiButtons::ExitSaveLoad => (String::from("Close"), PositionType::Absolute),
+        UiButtons::DeleteSlot(_) => (String::from("Delete"), PositionType::Absolute),
+        UiButtons::PreviousSlotPage => (String::from("<"), PositionType::Relative),
+        UiButtons::NextSlotPage => (String::from(">"), PositionType::Relative),
+        UiButtons::ConfirmSlotAction => (String::from("Yes"), PositionType::Relative),
+        UiButtons::CancelSlotAction => (String::from("No"), PositionType::Relative),
+        UiButtons::ReturnToTitle => (String::from("Return to Title"), PositionType::Relative),
         other                  => return Err(anyhow::anyhow!("{:?} is not a valid button!", other).into()),
     };
     
@@ -172,6 +185,7 @@ pub(in crate::chat::ui) fn button(action: UiButtons) -> Result<impl Bundle, Bevy
         BackgroundColor(Color::Srgba(BLACK)),
         action,
         Button,
+        ThemedWidget::Button,
         children![
             Text::new(button_text),
             TextShadow::default()
@@ -195,7 +209,10 @@ pub(in crate::chat) fn vn_commands() -> Result<impl Bundle, BevyError> {
         ZIndex(UI_Z_INDEX),
         children![
             button(UiButtons::Rewind)?,
+            button(UiButtons::RollForward)?,
             button(UiButtons::OpenHistory)?,
+            button(UiButtons::OpenSettings)?,
+            button(UiButtons::OpenSystemMenu)?,
         ]
     ))
 }
\ No newline at end of file
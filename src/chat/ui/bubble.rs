@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+use bevy_ui_widgets::Button;
+
+use crate::{
+    chat::{
+        GUIScrollText, UI_Z_INDEX, controller::{BubbleText, CurrentBubbleBackground, SpeechBubble, UiButtons}, ui::FONT_PATH
+    },
+    compiler::controller::SabiState
+};
+
+/// Speech bubble of a single speaker, placed above its actor by `anchor_speech_bubbles`.
+/// Without a `(GUI bubble changes to ...)` skin the bubble falls back to a plain rounded box.
+pub(in crate::chat) fn speech_bubble(
+    bubble: SpeechBubble,
+    name: String,
+    text: String,
+    skin: &CurrentBubbleBackground,
+    asset_server: &Res<AssetServer>,
+) -> impl Bundle {
+    let (image, background, border_color) = match &skin.0 {
+        Some(skin) => (
+            ImageNode {
+                image: skin.image.clone(),
+                image_mode: skin.image_mode.clone(),
+                ..default()
+            },
+            BackgroundColor(Color::NONE),
+            BorderColor::all(Color::NONE),
+        ),
+        None => (
+            ImageNode { color: Color::NONE, ..default() },
+            BackgroundColor(Color::BLACK.with_alpha(0.8)),
+            BorderColor::all(Color::WHITE),
+        ),
+    };
+
+    (
+        image,
+        background,
+        border_color,
+        BorderRadius::all(px(16.)),
+        Node {
+            position_type: PositionType::Absolute,
+            max_width: percent(30.),
+            min_width: percent(12.),
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            row_gap: px(4.),
+            border: UiRect::all(px(2)),
+            padding: skin.1.unwrap_or(UiRect::axes(px(24), px(16))),
+            ..default()
+        },
+        ZIndex(UI_Z_INDEX),
+        Button,
+        UiButtons::SpeechBubble,
+        bubble,
+        DespawnOnExit(SabiState::Running),
+        children![
+            (
+                Text::new(name),
+                TextFont {
+                    font: asset_server.load(FONT_PATH),
+                    font_size: 20.,
+                    ..default()
+                },
+            ),
+            (
+                Text::new(""),
+                TextFont {
+                    font: asset_server.load(FONT_PATH),
+                    font_size: 24.,
+                    ..default()
+                },
+                TextLayout {
+                    justify: Justify::Left,
+                    linebreak: LineBreak::WordBoundary,
+                },
+                GUIScrollText { message: text },
+                BubbleText,
+            ),
+        ]
+    )
+}
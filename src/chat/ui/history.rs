@@ -1,23 +1,22 @@
 use bevy::color::palettes::css::GRAY;
-use bevy::{asset::AssetServer, ecs::relationship::RelatedSpawner};
+use bevy::asset::AssetServer;
 use bevy::prelude::*;
 use bevy_ui_widgets::{CoreScrollbarThumb, Scrollbar};
 
-use crate::chat::controller::{HistoryScrollbar, HistoryText, UiButtons};
+use crate::chat::controller::{ChatLayout, HistoryScrollbar, HistoryText, LayoutTree, LayoutWidget, ThemedWidget, UiButtons, spawn_layout_tree};
 use crate::chat::ui::FONT_PATH;
 use crate::chat::ui::basic::button;
-use crate::{VisualNovelState, chat::{UI_Z_INDEX, controller::{CurrentTextBoxBackground, HistoryPanel}}};
+use crate::{VisualNovelState, chat::{MENU_Z_INDEX, UI_Z_INDEX, controller::{CurrentTextBoxBackground, HistoryPanel}}};
 
-pub(crate) fn history_panel(
+/// Spawns the history panel and the nodes of its layout tree, the panel is returned for the caller to parent it
+pub(crate) fn spawn_history_panel(
+    commands: &mut Commands,
+    layout: &ChatLayout,
     current_plate: Res<CurrentTextBoxBackground>,
-    game_state: &ResMut<VisualNovelState>,
+    game_state: &VisualNovelState,
     asset_server: &Res<AssetServer>,
-) -> Result<impl Bundle, BevyError> {
-    
-    let history_text = history_text(asset_server, game_state)?;
-    let exit_history_button = button(UiButtons::ExitHistory)?;
-    
-    Ok((
+) -> Result<Entity, BevyError> {
+    let panel = commands.spawn((
         ImageNode {
             image: current_plate.0.image.clone(),
             image_mode: current_plate.0.image_mode.clone(),
@@ -37,19 +36,22 @@ pub(crate) fn history_panel(
             },
             ..default()
         },
-        ZIndex(UI_Z_INDEX),
+        ZIndex(MENU_Z_INDEX),
         HistoryPanel,
-        Children::spawn(
-            SpawnWith(|parent: &mut RelatedSpawner<ChildOf>| {
-                parent.spawn(history_title());
-                let scroll_area_id = parent.spawn((
-                    history_text,
-                )).id();
-                parent.spawn(scrollbar(scroll_area_id));
-                parent.spawn(exit_history_button);
-            })
-        ),
-    ))
+        ThemedWidget::HistoryPanel,
+    )).id();
+
+    // Spawned ahead, the scrollbar needs it whatever their order in the tree
+    let scroll_area = commands.spawn(history_scroll()).id();
+    spawn_layout_tree(commands, layout, LayoutTree::History, panel, |commands, widget| Ok(match widget {
+        LayoutWidget::HistoryTitle => commands.spawn(history_title()).id(),
+        LayoutWidget::HistoryScroll => scroll_area,
+        LayoutWidget::HistoryText => commands.spawn(history_text(asset_server, game_state)).id(),
+        LayoutWidget::Scrollbar => commands.spawn(scrollbar(scroll_area)).id(),
+        LayoutWidget::Close => commands.spawn(button(UiButtons::ExitHistory)?).id(),
+        other => return Err(anyhow::anyhow!("{} is not a history widget", other.name()).into()),
+    }))?;
+    Ok(panel)
 }
 
 fn history_title() -> impl Bundle {
@@ -88,15 +90,14 @@ fn scrollbar(entity: Entity) -> impl Bundle {
                 BackgroundColor(GRAY.into()),
                 BorderRadius::all(px(4.)),
                 CoreScrollbarThumb,
+                ThemedWidget::Scrollbar,
             )
         ]
     )
 }
 
-fn history_text(asset_server: &Res<AssetServer>, game_state: &ResMut<VisualNovelState>) -> Result<impl Bundle, BevyError> {
-    let history_text = game_state.history_summary()?.join("\n");
-    let font_handle = asset_server.load(FONT_PATH);
-    Ok((
+fn history_scroll() -> impl Bundle {
+    (
         Node {
             display: Display::Flex,
             flex_direction: FlexDirection::Column,
@@ -106,18 +107,21 @@ fn history_text(asset_server: &Res<AssetServer>, game_state: &ResMut<VisualNovel
             flex_shrink: 0.,
             ..default()
         },
-        children![
-            (
-                Text(history_text),
-                TextFont {
-                    font: font_handle,
-                    font_size: 14.,
-                    ..default()
-                },
-            )
-        ],
         ZIndex(UI_Z_INDEX),
         ScrollPosition(Vec2::new(0., 0.)),
         HistoryText
-    ))
-}
\ No newline at end of file
+    )
+}
+
+fn history_text(asset_server: &Res<AssetServer>, game_state: &VisualNovelState) -> impl Bundle {
+    let history_text = game_state.history_summary().join("\n");
+    let font_handle = asset_server.load(FONT_PATH);
+    (
+        Text(history_text),
+        TextFont {
+            font: font_handle,
+            font_size: 14.,
+            ..default()
+        },
+    )
+}
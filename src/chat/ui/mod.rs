@@ -1,4 +1,9 @@
 pub(in crate::chat) mod basic;
+pub(in crate::chat) mod bubble;
 pub(in crate::chat) mod history;
+pub(in crate::chat) mod nvl;
+pub(in crate::chat) mod save_load;
+pub(crate) mod settings;
+pub(in crate::chat) mod system_menu;
 
-const FONT_PATH: &str = "sabi/fonts/ALLER.ttf";
\ No newline at end of file
+pub(in crate::chat) const FONT_PATH: &str = "sabi/fonts/ALLER.ttf";
\ No newline at end of file
@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use bevy_ui_widgets::Button;
+
+use crate::{
+    chat::{
+        GUIScrollText, UI_Z_INDEX, controller::{NvlLine, NvlLines, NvlPage, UiButtons}, ui::FONT_PATH
+    },
+    compiler::controller::SabiState
+};
+
+/// Page the NVL lines are stacked on, the textbox commands are moved onto it while it is shown
+pub(in crate::chat) fn nvl_page() -> Result<impl Bundle, BevyError> {
+    Ok((
+        Node {
+            width: percent(100),
+            height: percent(100),
+            position_type: PositionType::Absolute,
+            top: px(0),
+            left: px(0),
+            padding: UiRect {
+                top: percent(6.),
+                bottom: percent(10.),
+                ..UiRect::horizontal(percent(10.))
+            },
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        // Shown by `(Mode NVL)`
+        Visibility::Hidden,
+        ZIndex(UI_Z_INDEX),
+        Button,
+        UiButtons::NvlPage,
+        NvlPage,
+        DespawnOnExit(SabiState::Running),
+        children![
+            (
+                Node {
+                    display: Display::Flex,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: px(14.),
+                    width: percent(100.),
+                    ..default()
+                },
+                NvlLines,
+            ),
+        ]
+    ))
+}
+
+/// Line of the NVL page, the speaker is shown right away and the text is revealed by its [nvl_text] span.
+pub(in crate::chat) fn nvl_line(name: Option<String>, asset_server: &Res<AssetServer>) -> impl Bundle {
+    let speaker = name.map(|name| format!("{}: ", name)).unwrap_or_default();
+    (
+        Text::new(speaker),
+        nvl_font(asset_server),
+        TextLayout {
+            justify: Justify::Left,
+            linebreak: LineBreak::WordBoundary,
+        },
+        NvlLine,
+    )
+}
+
+pub(in crate::chat) fn nvl_text(text: String, revealed: bool, asset_server: &Res<AssetServer>) -> impl Bundle {
+    let shown = if revealed { text.clone() } else { String::new() };
+    (
+        TextSpan::new(shown),
+        nvl_font(asset_server),
+        GUIScrollText { message: text },
+    )
+}
+
+fn nvl_font(asset_server: &Res<AssetServer>) -> TextFont {
+    TextFont {
+        font: asset_server.load(FONT_PATH),
+        font_size: 26.,
+        ..default()
+    }
+}
@@ -0,0 +1,268 @@
+use bevy::color::palettes::css::GRAY;
+use bevy::{asset::AssetServer, ecs::relationship::RelatedSpawner};
+use bevy::prelude::*;
+use bevy_ui_widgets::Button;
+
+use crate::chat::controller::{SaveLoadPanel, SlotAction, SlotBrowser, SlotBrowserMode, ThemedWidget, UiButtons};
+use crate::chat::ui::FONT_PATH;
+use crate::chat::ui::basic::button;
+use crate::chat::MENU_Z_INDEX;
+use crate::save::{SaveSlot, SaveSlots, controller::{SLOTS_PER_PAGE, SLOT_PAGES}};
+
+/// Characters of the last dialogue line shown on a slot
+const EXCERPT_LENGTH: usize = 60;
+
+pub(in crate::chat) fn save_load_panel(
+    browser: &SlotBrowser,
+    save_slots: &SaveSlots,
+    asset_server: &Res<AssetServer>,
+) -> Result<impl Bundle, BevyError> {
+    let font_handle: Handle<Font> = asset_server.load(FONT_PATH);
+
+    let first_slot = browser.page * SLOTS_PER_PAGE;
+    let mut tiles = Vec::new();
+    for slot in first_slot..first_slot + SLOTS_PER_PAGE {
+        tiles.push(slot_tile(slot, save_slots.0.get(&slot), browser.mode, font_handle.clone())?);
+    }
+    let page_controls = page_controls(browser.page, font_handle.clone())?;
+    let confirmation = match browser.pending {
+        Some(action) => Some(confirmation_dialog(action, font_handle.clone())?),
+        None => None,
+    };
+    let exit_button = button(UiButtons::ExitSaveLoad)?;
+
+    // Without a textbox skin, e.g. on the title screen, the panel falls back to a plain backdrop
+    let (image, background) = match &browser.plate {
+        Some(plate) => (
+            ImageNode {
+                image: plate.image.clone(),
+                image_mode: plate.image_mode.clone(),
+                ..default()
+            },
+            BackgroundColor(Color::NONE),
+        ),
+        None => (
+            ImageNode { color: Color::NONE, ..default() },
+            BackgroundColor(Color::BLACK.with_alpha(0.85)),
+        ),
+    };
+    let title = match browser.mode {
+        SlotBrowserMode::Save => "Save",
+        SlotBrowserMode::Load => "Load",
+    };
+
+    Ok((
+        image,
+        background,
+        Node {
+            position_type: PositionType::Absolute,
+            width: percent(70.),
+            height: percent(65.),
+            top: percent(3.),
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: px(6.),
+            padding: UiRect {
+                top: percent(6.),
+                bottom: percent(2.),
+                ..UiRect::horizontal(percent(4.))
+            },
+            ..default()
+        },
+        ZIndex(MENU_Z_INDEX),
+        SaveLoadPanel,
+        ThemedWidget::Menu,
+        Children::spawn(
+            SpawnWith(move |parent: &mut RelatedSpawner<ChildOf>| {
+                parent.spawn(save_load_title(title));
+                parent.spawn(slot_grid()).with_children(|grid| {
+                    for (tile, action) in tiles {
+                        let mut entity = grid.spawn(tile);
+                        if let Some(action) = action {
+                            entity.insert((action, Button));
+                        }
+                    }
+                });
+                parent.spawn(page_controls);
+                parent.spawn(exit_button);
+                if let Some(confirmation) = confirmation {
+                    parent.spawn(confirmation);
+                }
+            })
+        ),
+    ))
+}
+
+fn save_load_title(title: &'static str) -> impl Bundle {
+    (
+        Node {
+            position_type: PositionType::Absolute,
+            top: percent(3.),
+            ..default()
+        },
+        Text::new(title),
+        TextFont {
+            font_size: 21.,
+            ..default()
+        }
+    )
+}
+
+fn slot_grid() -> impl Bundle {
+    Node {
+        display: Display::Grid,
+        width: percent(100.),
+        flex_grow: 1.,
+        grid_template_columns: RepeatedGridTrack::flex(3, 1.),
+        grid_template_rows: RepeatedGridTrack::flex(2, 1.),
+        column_gap: px(10.),
+        row_gap: px(10.),
+        ..default()
+    }
+}
+
+/// Slots are buttons when there is something to do with them, empty slots cannot be loaded
+fn slot_tile(
+    slot: usize,
+    save: Option<&SaveSlot>,
+    mode: SlotBrowserMode,
+    font_handle: Handle<Font>,
+) -> Result<(impl Bundle, Option<UiButtons>), BevyError> {
+    let clickable = save.is_some() || mode == SlotBrowserMode::Save;
+    let delete_button = match save {
+        Some(_) => Some(button(UiButtons::DeleteSlot(slot))?),
+        None => None,
+    };
+    let thumbnail = save.and_then(|save| save.thumbnail.clone());
+    let lines = match save {
+        Some(save) => vec![
+            (format!("Slot {} - {}", slot + 1, save.data.date()), 14.),
+            (save.data.location(), 12.),
+            (excerpt(&save.data.last_line), 12.),
+        ],
+        None => vec![(format!("Slot {} - Empty", slot + 1), 14.)],
+    };
+    let font_handle = font_handle.clone();
+
+    let tile = (
+        Node {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            border: UiRect::all(px(2)),
+            padding: UiRect::all(px(4)),
+            row_gap: px(2.),
+            overflow: Overflow::clip(),
+            ..default()
+        },
+        BorderColor::all(if clickable { Color::WHITE } else { GRAY.into() }),
+        BorderRadius::all(px(6.)),
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        Children::spawn(
+            SpawnWith(move |parent: &mut RelatedSpawner<ChildOf>| {
+                let thumbnail_node = Node {
+                    width: percent(100.),
+                    aspect_ratio: Some(16. / 9.),
+                    ..default()
+                };
+                match thumbnail {
+                    Some(image) => parent.spawn((thumbnail_node, ImageNode::new(image))),
+                    None => parent.spawn((thumbnail_node, BackgroundColor(GRAY.with_alpha(0.3).into()))),
+                };
+                for (line, font_size) in lines {
+                    parent.spawn((
+                        Text::new(line),
+                        TextFont {
+                            font: font_handle.clone(),
+                            font_size,
+                            ..default()
+                        },
+                    ));
+                }
+                // Clicks on the delete button do not propagate to the slot underneath
+                if let Some(delete_button) = delete_button {
+                    parent.spawn(delete_button);
+                }
+            })
+        ),
+    );
+    Ok((tile, clickable.then_some(UiButtons::SaveSlot(slot))))
+}
+
+fn page_controls(page: usize, font_handle: Handle<Font>) -> Result<impl Bundle, BevyError> {
+    Ok((
+        Node {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Row,
+            align_items: AlignItems::Center,
+            column_gap: px(10.),
+            ..default()
+        },
+        children![
+            button(UiButtons::PreviousSlotPage)?,
+            (
+                Text::new(format!("Page {} / {}", page + 1, SLOT_PAGES)),
+                TextFont {
+                    font: font_handle.clone(),
+                    font_size: 16.,
+                    ..default()
+                },
+            ),
+            button(UiButtons::NextSlotPage)?,
+        ]
+    ))
+}
+
+fn confirmation_dialog(action: SlotAction, font_handle: Handle<Font>) -> Result<impl Bundle, BevyError> {
+    let question = match action {
+        SlotAction::Overwrite(slot) => format!("Overwrite slot {}?", slot + 1),
+        SlotAction::Delete(slot) => format!("Delete slot {}?", slot + 1),
+    };
+    Ok((
+        Node {
+            position_type: PositionType::Absolute,
+            top: percent(35.),
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: px(10.),
+            padding: UiRect::all(px(16)),
+            border: UiRect::all(px(2)),
+            ..default()
+        },
+        BorderColor::all(Color::WHITE),
+        BorderRadius::all(px(6.)),
+        BackgroundColor(Color::BLACK.with_alpha(0.9)),
+        ZIndex(MENU_Z_INDEX + 1),
+        children![
+            (
+                Text::new(question),
+                TextFont {
+                    font: font_handle.clone(),
+                    font_size: 18.,
+                    ..default()
+                },
+            ),
+            (
+                Node {
+                    column_gap: px(10.),
+                    ..default()
+                },
+                children![
+                    button(UiButtons::ConfirmSlotAction)?,
+                    button(UiButtons::CancelSlotAction)?,
+                ]
+            ),
+        ]
+    ))
+}
+
+/// Shortens a line so that it fits on a slot
+fn excerpt(line: &str) -> String {
+    if line.chars().count() <= EXCERPT_LENGTH {
+        return line.to_owned();
+    }
+    let mut excerpt: String = line.chars().take(EXCERPT_LENGTH).collect();
+    excerpt.push_str("...");
+    excerpt
+}
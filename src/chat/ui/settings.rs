@@ -0,0 +1,129 @@
+use bevy::{asset::AssetServer, ecs::relationship::RelatedSpawner};
+use bevy::prelude::*;
+
+use crate::chat::controller::{SettingValueText, SettingsPanel, ThemedWidget, UiButtons};
+use crate::chat::ui::FONT_PATH;
+use crate::chat::ui::basic::button;
+use crate::settings::{SettingKind, Settings};
+use crate::chat::MENU_Z_INDEX;
+
+pub(crate) fn settings_panel(
+    plate: Option<&ImageNode>,
+    settings: &Res<Settings>,
+    asset_server: &Res<AssetServer>,
+) -> Result<impl Bundle, BevyError> {
+
+    let mut rows = Vec::new();
+    for kind in SettingKind::ALL {
+        rows.push(setting_row(kind, settings, asset_server)?);
+    }
+    let exit_settings_button = button(UiButtons::ExitSettings)?;
+
+    // Without a textbox skin, e.g. on the title screen, the panel falls back to a plain backdrop
+    let (image, background) = match plate {
+        Some(plate) => (
+            ImageNode {
+                image: plate.image.clone(),
+                image_mode: plate.image_mode.clone(),
+                ..default()
+            },
+            BackgroundColor(Color::NONE),
+        ),
+        None => (
+            ImageNode { color: Color::NONE, ..default() },
+            BackgroundColor(Color::BLACK.with_alpha(0.85)),
+        ),
+    };
+
+    Ok((
+        image,
+        background,
+        Node {
+            position_type: PositionType::Absolute,
+            width: percent(70.),
+            height: percent(65.),
+            top: percent(3.),
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: px(6.),
+            padding: UiRect {
+                top: percent(6.),
+                bottom: percent(2.),
+                ..UiRect::horizontal(percent(4.))
+            },
+            ..default()
+        },
+        ZIndex(MENU_Z_INDEX),
+        SettingsPanel,
+        ThemedWidget::Menu,
+        Children::spawn(
+            SpawnWith(|parent: &mut RelatedSpawner<ChildOf>| {
+                parent.spawn(settings_title());
+                for row in rows {
+                    parent.spawn(row);
+                }
+                parent.spawn(exit_settings_button);
+            })
+        ),
+    ))
+}
+
+fn settings_title() -> impl Bundle {
+    (
+        Node {
+            position_type: PositionType::Absolute,
+            top: percent(3.),
+            ..default()
+        },
+        Text::new("Settings"),
+        TextFont {
+            font_size: 21.,
+            ..default()
+        }
+    )
+}
+
+fn setting_row(kind: SettingKind, settings: &Res<Settings>, asset_server: &Res<AssetServer>) -> Result<impl Bundle, BevyError> {
+    let font_handle = asset_server.load(FONT_PATH);
+    Ok((
+        Node {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Row,
+            align_items: AlignItems::Center,
+            width: percent(80.),
+            ..default()
+        },
+        children![
+            (
+                Node {
+                    flex_grow: 1.,
+                    ..default()
+                },
+                Text::new(kind.label()),
+                TextFont {
+                    font: font_handle.clone(),
+                    font_size: 18.,
+                    ..default()
+                },
+            ),
+            button(UiButtons::SettingDown(kind))?,
+            (
+                Node {
+                    width: px(110.),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                Text::new(settings.display(kind)),
+                TextFont {
+                    font: font_handle,
+                    font_size: 18.,
+                    ..default()
+                },
+                TextLayout::new_with_justify(Justify::Center),
+                SettingValueText(kind),
+            ),
+            button(UiButtons::SettingUp(kind))?,
+        ]
+    ))
+}
@@ -0,0 +1,64 @@
+use bevy::ecs::relationship::RelatedSpawner;
+use bevy::prelude::*;
+
+use crate::chat::controller::{SystemMenuPanel, ThemedWidget, UiButtons};
+use crate::chat::ui::basic::button;
+use crate::chat::MENU_Z_INDEX;
+
+/// Replays from the gallery can't be saved, `can_save` leaves the Save button out
+pub(crate) fn system_menu_panel(plate: &ImageNode, can_save: bool) -> Result<impl Bundle, BevyError> {
+    let actions = [
+        UiButtons::Save,
+        UiButtons::Load,
+        UiButtons::OpenSettings,
+        UiButtons::OpenHistory,
+        UiButtons::ReturnToTitle,
+        UiButtons::CloseSystemMenu,
+    ];
+    let buttons = actions.into_iter()
+        .filter(|action| can_save || *action != UiButtons::Save)
+        .map(button)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((
+        ImageNode {
+            image: plate.image.clone(),
+            image_mode: plate.image_mode.clone(),
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            width: percent(30.),
+            top: percent(20.),
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: px(10.),
+            padding: UiRect::all(percent(3.)),
+            ..default()
+        },
+        // Revealed once the save thumbnail has been captured
+        Visibility::Hidden,
+        ZIndex(MENU_Z_INDEX),
+        SystemMenuPanel,
+        ThemedWidget::Menu,
+        Children::spawn(
+            SpawnWith(|parent: &mut RelatedSpawner<ChildOf>| {
+                parent.spawn(system_menu_title());
+                for button in buttons {
+                    parent.spawn(button);
+                }
+            })
+        ),
+    ))
+}
+
+fn system_menu_title() -> impl Bundle {
+    (
+        Text::new("Menu"),
+        TextFont {
+            font_size: 21.,
+            ..default()
+        }
+    )
+}
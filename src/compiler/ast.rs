@@ -5,7 +5,7 @@ use bevy::prelude::*;
 use std::collections::HashMap;
 
 use crate::{
-    actor::{ActorOperation, controller::{ActorDirection, ActorPosition, ActorType, AnimationPosition, CharacterPosition, SpawnInfo}}, background::controller::{BackgroundDirection, BackgroundOperation}, chat::controller::{GuiChangeTarget, GuiImageMode}
+    actor::{ActorOperation, controller::{ActorDirection, ActorOffset, ActorPosition, ActorType, AnimationPosition, CharacterPosition, DepthTarget, Easing, LayerChange, SpawnInfo, Tween}}, background::controller::{BackgroundDirection, BackgroundOperation}, chat::controller::{GuiChangeTarget, GuiImageMode, PresentationMode}
 };
 
 #[derive(Parser)]
@@ -31,9 +31,31 @@ pub(crate) trait Evaluate {
 pub(crate) enum Expr {
     Number(f64),
     String(String),
+    Variable(String),
     Add { lhs: Box<Expr>, rhs: Box<Expr> }
 }
 
+impl Expr {
+    /// Replaces every [Expr::Variable] with the value currently bound in `variables`.
+    /// Variables that were never set read as an empty string, so a flag checked before it is set doesn't stop the game.
+    pub(crate) fn resolve(&self, variables: &HashMap<String, String>) -> Expr {
+        match self {
+            Expr::String(_) | Expr::Number(_) => self.clone(),
+            Expr::Variable(name) => {
+                let value = variables.get(name).cloned().unwrap_or_else(|| {
+                    warn!("Variable '${}' is read before being set, it reads as empty", name);
+                    String::new()
+                });
+                Expr::String(value)
+            },
+            Expr::Add { lhs, rhs } => Expr::Add {
+                lhs: Box::new(lhs.resolve(variables)),
+                rhs: Box::new(rhs.resolve(variables)),
+            }
+        }
+    }
+}
+
 impl Evaluate for Expr {
     fn evaluate_into_string(&self) -> Result<String> {
         let evaluated = self.evaluate()
@@ -44,6 +66,7 @@ impl Evaluate for Expr {
     fn evaluate(&self) -> Result<Expr> {
         match self {
             Expr::String(_) | Expr::Number(_) => Ok(self.clone()),
+            Expr::Variable(name) => bail!("Variable '${}' must be resolved before evaluation", name),
             Expr::Add { lhs, rhs } => {
                 let left = lhs.evaluate().context("Failed to evaluate left side of addition")?;
                 let right = rhs.evaluate().context("Failed to evaluate right side of addition")?;
@@ -78,6 +101,7 @@ pub(crate) fn expr_to_string(expr: &Expr) -> Result<String> {
     match expr {
         Expr::String(s) => Ok(s.clone()),
         Expr::Number(n) => Ok(n.to_string()),
+        Expr::Variable(name) => bail!("Variable '${}' must be resolved before evaluation", name),
         Expr::Add { .. } => {
             let evaluated = expr.evaluate()?;
             expr_to_string(&evaluated)
@@ -94,7 +118,8 @@ pub(crate) struct Act {
 
 #[derive(Debug, Clone)]
 pub(crate) enum CodeStatement {
-    Log { exprs: Vec<Expr> }
+    Log { exprs: Vec<Expr> },
+    Set { variable: String, expr: Expr },
 }
 
 #[derive(Debug, Clone)]
@@ -103,6 +128,12 @@ pub(crate) enum StageCommand {
     GUIChange { gui_target: GuiChangeTarget, sprite_expr: Box<Expr>, image_mode: GuiImageMode },
     SceneChange { scene_expr: Box<Expr> },
     ActChange { act_expr: Box<Expr> },
+    ModeChange { mode: PresentationMode },
+    PageClear,
+    /// Whether the character's lines are shown in speech bubbles or in the textbox
+    SpeechChange { character: String, bubbles: bool },
+    /// Overrides the auto-focus setting until the scene ends
+    FocusChange { enabled: bool },
     CharacterChange { character: String, operation: ActorOperation },
     AnimationChange { animation: String, operation: ActorOperation },
 }
@@ -111,6 +142,8 @@ pub(crate) enum StageCommand {
 pub(crate) enum TextItem {
     Dialogue(Dialogue),
     InfoText(InfoText),
+    /// Lines said at once, one per character
+    Together(Vec<Dialogue>),
 }
 
 #[derive(Debug, Clone)]
@@ -160,6 +193,7 @@ pub(crate) fn build_expression(pair: pest::iterators::Pair<Rule>) -> Result<Expr
                 let s = &s[1..s.len()-1];
                 Ok(Expr::String(s.to_string()))
             },
+            Rule::variable => Ok(Expr::Variable(primary.as_str().trim_start_matches('$').to_owned())),
             Rule::expr => build_expression(primary),
             other => bail!("Unexpected primary expr: {other:?}"),
         })
@@ -176,26 +210,33 @@ pub(crate) fn build_expression(pair: pest::iterators::Pair<Rule>) -> Result<Expr
         .context("Failed to parse expression")
 }
 
-fn build_actor_spawn_directive(r#type: ActorType, actor: &str, action: &str, mut action_iter: pest::iterators::Pairs<'_, Rule>) -> Result<StageCommand> {
+fn build_actor_spawn_directive(r#type: ActorType, actor: &str, action: &str, action_iter: pest::iterators::Pairs<'_, Rule>) -> Result<StageCommand> {
+    // Timing pairs close the directive
+    let (directives, tween_pairs): (Vec<_>, Vec<_>) = action_iter
+        .partition(|pair| !matches!(pair.as_rule(), Rule::duration | Rule::easing | Rule::no_wait));
+    let fading = matches!(action, "fade in" | "fade out");
+    ensure!(fading || tween_pairs.is_empty(), "Only fades take a duration, found {:?}", action);
+    let tween = build_tween(tween_pairs.into_iter())?;
+
     let operation = match action {
         "appears" | "fade in" => {
             let mut spawn_info = SpawnInfo {
-                fading: action == "fade in",
+                fading,
+                fade: tween,
                 ..Default::default()
             };
-            while let Some(directive) = action_iter.next() {
+            for directive in directives {
                 match directive.as_rule() {
                     Rule::emotion_name => { spawn_info.emotion = Some(directive.as_str().to_owned()); },
+                    Rule::actor_outfit => {
+                        let outfit = directive.into_inner().next().context("Missing outfit")?;
+                        spawn_info.outfit = Some(outfit.as_str().trim_matches('"').to_owned());
+                    },
                     Rule::character_position => {
-                        let position = match CharacterPosition::try_from(directive.as_str()) {
-                            Ok(pos) => pos,
-                            Err(e) => bail!(e)
-                        };
-                        spawn_info.position = Some(ActorPosition::Character(position));
+                        spawn_info.position = Some(ActorPosition::Character(build_character_position(directive)?));
                     }
                     Rule::animation_position => {
-                        let position = AnimationPosition::try_from(directive.as_str())?;
-                        spawn_info.position = Some(ActorPosition::Animation(position));
+                        spawn_info.position = Some(ActorPosition::Animation(build_animation_position(directive)?));
                     },
                     Rule::actor_direction_directive => {
                         let mut pair_iter = directive.into_inner();
@@ -214,7 +255,7 @@ fn build_actor_spawn_directive(r#type: ActorType, actor: &str, action: &str, mut
             }
             ActorOperation::Spawn(spawn_info)
         },
-        "disappears" | "fade out" => ActorOperation::Despawn(action == "fade out"),
+        "disappears" | "fade out" => ActorOperation::Despawn(fading.then_some(tween)),
         other => bail!("Unexpected actor spawn operation: {:?}", other)
     };
     
@@ -244,37 +285,133 @@ fn build_actor_direction_directive(r#type: ActorType, actor: &str, action: Pair<
     }
 }
 
-fn build_actor_movement_directive(actor: &str, action: &str, mut action_iter: pest::iterators::Pairs<'_, Rule>) -> Result<StageCommand> {
+fn build_actor_movement_directive(r#type: ActorType, actor: &str, action: &str, mut action_iter: pest::iterators::Pairs<'_, Rule>) -> Result<StageCommand> {
     match action {
         "moves" => {
             match action_iter.peek() {
                 Some(n) if n.as_rule() == Rule::character_position => {
                     let position_pair = action_iter.next().context("Expected actor position")?;
-                    ensure!(position_pair.as_rule() == Rule::character_position,
-                        "Expected character position, found {:?}", position_pair.as_rule());
-
-                    match CharacterPosition::try_from(position_pair.as_str()) {
-                        Ok(pos) => { return Ok(StageCommand::CharacterChange { character: actor.to_string(), operation: ActorOperation::Move(ActorPosition::Character(pos)) }) },
-                        Err(e) => bail!(e)
-                    }
+                    let position = build_character_position(position_pair)?;
+                    let tween = build_tween(action_iter)?;
+                    Ok(StageCommand::CharacterChange { character: actor.to_string(), operation: ActorOperation::Move(ActorPosition::Character(position), tween) })
                 },
                 Some(n) if n.as_rule() == Rule::animation_position => {
                     let position_pair = action_iter.next().context("Expected actor position")?;
-                    ensure!(position_pair.as_rule() == Rule::animation_position,
-                        "Expected animation position, found {:?}", position_pair.as_rule());
-
-                    match AnimationPosition::try_from(position_pair.as_str()) {
-                        Ok(pos) => { return Ok(StageCommand::AnimationChange { animation: actor.to_string(), operation: ActorOperation::Move(ActorPosition::Animation(pos)) }) },
-                        Err(e) => bail!(e)
+                    let position = build_animation_position(position_pair)?;
+                    let tween = build_tween(action_iter)?;
+                    Ok(StageCommand::AnimationChange { animation: actor.to_string(), operation: ActorOperation::Move(ActorPosition::Animation(position), tween) })
+                },
+                Some(n) if n.as_rule() == Rule::depth_target => {
+                    let target = build_depth_target(action_iter.next().context("Expected depth target")?)?;
+                    match r#type {
+                        ActorType::Character => Ok(StageCommand::CharacterChange { character: actor.to_string(), operation: ActorOperation::Reorder(target) }),
+                        ActorType::Animation => Ok(StageCommand::AnimationChange { animation: actor.to_string(), operation: ActorOperation::Reorder(target) })
                     }
-                }
-                _ => { bail!("Animation move directive needs position arguments"); }
+                },
+                Some(n) if n.as_rule() == Rule::actor_offset => {
+                    let mut offset_iter = action_iter.next().context("Expected actor offset")?.into_inner();
+                    let direction = offset_iter.next().context("Offset missing direction")?;
+                    let distance = build_percentage(offset_iter.next().context("Offset missing distance")?)?;
+                    let (x, y) = match direction.as_str() {
+                        "left" => (-distance, 0.),
+                        "right" => (distance, 0.),
+                        "up" => (0., distance),
+                        "down" => (0., -distance),
+                        other => bail!("Unhandled offset direction {:?}", other)
+                    };
+                    let tween = build_tween(action_iter)?;
+                    match r#type {
+                        ActorType::Character => Ok(StageCommand::CharacterChange { character: actor.to_string(), operation: ActorOperation::MoveBy(ActorOffset::Character(x, y), tween) }),
+                        ActorType::Animation => Ok(StageCommand::AnimationChange { animation: actor.to_string(), operation: ActorOperation::MoveBy(ActorOffset::Animation(x, y), tween) })
+                    }
+                },
+                _ => { bail!("Move directive needs position arguments"); }
             }
         }
         other => bail!("Unexpected action in Actor Direction Directive command: {:?}", other)
     }
 }
 
+fn build_depth_target(pair: Pair<'_, Rule>) -> Result<DepthTarget> {
+    let target = pair.into_inner().next().context("Depth target is empty")?;
+    let reference = |target: Pair<'_, Rule>| -> Result<String> {
+        let name = target.into_inner().next().context("Depth target missing actor")?;
+        Ok(name.as_str().trim_matches('"').to_owned())
+    };
+    Ok(match target.as_rule() {
+        Rule::depth_front => DepthTarget::Front,
+        Rule::depth_back => DepthTarget::Back,
+        Rule::depth_layer => {
+            let layer = target.into_inner().next().context("Layer missing number")?;
+            DepthTarget::Layer(layer.as_str().parse().context("Layer must be a whole number")?)
+        },
+        Rule::depth_behind => DepthTarget::Behind(reference(target)?),
+        Rule::depth_in_front_of => DepthTarget::InFrontOf(reference(target)?),
+        Rule::depth_above_textbox => DepthTarget::AboveTextbox,
+        other => bail!("Unexpected rule in depth target {:?}", other)
+    })
+}
+
+fn build_layer_change(character: &str, mut action_iter: pest::iterators::Pairs<'_, Rule>) -> Result<StageCommand> {
+    let layer = action_iter.next().context("Layer change missing layer")?.as_str();
+    let change_pair = action_iter.next().context("Layer change missing state")?;
+    let change = match change_pair.as_rule() {
+        Rule::layer_toggle if change_pair.as_str() == "on" => LayerChange::Show,
+        Rule::layer_toggle => LayerChange::Hide,
+        Rule::layer_variant => LayerChange::Variant(change_pair.as_str().to_owned()),
+        other => bail!("Unexpected rule in layer change {:?}", other)
+    };
+    Ok(StageCommand::CharacterChange { character: character.to_string(), operation: ActorOperation::LayerChange(layer.to_owned(), change) })
+}
+
+fn build_character_position(pair: Pair<'_, Rule>) -> Result<CharacterPosition> {
+    ensure!(pair.as_rule() == Rule::character_position,
+        "Expected character position, found {:?}", pair.as_rule());
+    match pair.clone().into_inner().next() {
+        Some(inner) if inner.as_rule() == Rule::position_percentage => Ok(CharacterPosition::Percentage(build_percentage(inner)?)),
+        Some(inner) if inner.as_rule() == Rule::string => Ok(CharacterPosition::Named(inner.as_str().trim_matches('"').to_owned())),
+        _ => CharacterPosition::try_from(pair.as_str()).map_err(|e| anyhow::anyhow!(e.to_string()))
+    }
+}
+
+fn build_animation_position(pair: Pair<'_, Rule>) -> Result<AnimationPosition> {
+    ensure!(pair.as_rule() == Rule::animation_position,
+        "Expected animation position, found {:?}", pair.as_rule());
+    let mut inner = pair.clone().into_inner();
+    match inner.next() {
+        Some(x) if x.as_rule() == Rule::position_percentage => {
+            let y = inner.next().context("Animation position missing vertical percentage")?;
+            Ok(AnimationPosition::Percentage(build_percentage(x)?, build_percentage(y)?))
+        },
+        Some(inner) if inner.as_rule() == Rule::string => Ok(AnimationPosition::Named(inner.as_str().trim_matches('"').to_owned())),
+        _ => Ok(AnimationPosition::try_from(pair.as_str())?)
+    }
+}
+
+/// Timing pairs left at the end of a transition command
+fn build_tween<'a>(pairs: impl Iterator<Item = Pair<'a, Rule>>) -> Result<Tween> {
+    let mut tween = Tween::default();
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::duration => {
+                tween.duration = Some(pair.as_str().trim_end_matches('s').parse::<f32>()
+                    .context(format!("Invalid duration {:?}", pair.as_str()))?);
+            },
+            Rule::easing => tween.easing = Easing::try_from(pair.as_str())?,
+            Rule::no_wait => tween.blocking = false,
+            other => bail!("Unexpected rule in transition timing {:?}", other)
+        }
+    }
+    Ok(tween)
+}
+
+fn build_percentage(pair: Pair<'_, Rule>) -> Result<f32> {
+    ensure!(pair.as_rule() == Rule::position_percentage,
+        "Expected percentage, found {:?}", pair.as_rule());
+    pair.as_str().trim_end_matches('%').parse::<f32>()
+        .context(format!("Invalid percentage {:?}", pair.as_str()))
+}
+
 pub(crate) fn build_stage_command(pair: Pair<Rule>) -> Result<Statement> {
     ensure!(pair.as_rule() == Rule::stage_command,
         "Expected stage rule, found {:?}", pair.as_rule());
@@ -337,6 +474,8 @@ pub(crate) fn build_stage_command(pair: Pair<Rule>) -> Result<Statement> {
             let gui_target = match gui_element_pair.as_str() {
                 "textbox" => GuiChangeTarget::TextBoxBackground,
                 "namebox" => GuiChangeTarget::NameBoxBackground,
+                "bubble" => GuiChangeTarget::BubbleBackground,
+                "theme" => GuiChangeTarget::Theme,
                 other => bail!("Unknown GUI element: {}", other)
             };
 
@@ -372,6 +511,37 @@ pub(crate) fn build_stage_command(pair: Pair<Rule>) -> Result<Statement> {
                 .context("Failed to build expression for act change")?;
             StageCommand::ActChange { act_expr: Box::new(expr) }
         },
+        Rule::mode_change => {
+            let mode_pair = command_pair.into_inner().next()
+                .context("Mode change missing presentation mode")?;
+            let mode = match mode_pair.as_str() {
+                "NVL" => PresentationMode::Nvl,
+                "ADV" => PresentationMode::Adv,
+                other => bail!("Unknown presentation mode: {}", other)
+            };
+            StageCommand::ModeChange { mode }
+        },
+        Rule::page_clear => StageCommand::PageClear,
+        Rule::focus_change => {
+            let toggle = command_pair.into_inner().next()
+                .context("Focus change missing toggle")?;
+            StageCommand::FocusChange { enabled: toggle.as_str() == "on" }
+        },
+        Rule::speech_change => {
+            let mut inner_rules = command_pair.into_inner();
+            let character = inner_rules.next()
+                .context("Speech change missing character identifier")?
+                .as_str()
+                .to_owned();
+            let presentation_pair = inner_rules.next()
+                .context("Speech change missing presentation")?;
+            let bubbles = match presentation_pair.as_str() {
+                "bubbles" => true,
+                "textbox" => false,
+                other => bail!("Unknown speech presentation: {}", other)
+            };
+            StageCommand::SpeechChange { character, bubbles }
+        },
         Rule::character_change => {
             let mut inner_rules = command_pair.into_inner();
             let character = inner_rules.next()
@@ -385,7 +555,18 @@ pub(crate) fn build_stage_command(pair: Pair<Rule>) -> Result<Statement> {
             match action.as_rule() {
                 Rule::actor_spawn_directive         => { build_actor_spawn_directive(ActorType::Character, &character, action.as_str(), action_iter)? }
                 Rule::actor_direction_directive     => { build_actor_direction_directive(ActorType::Character, &character, action)? },
-                Rule::actor_movement_directive      => { build_actor_movement_directive(&character, action.as_str(), action_iter)? },
+                Rule::actor_movement_directive      => { build_actor_movement_directive(ActorType::Character, &character, action.as_str(), action_iter)? },
+                Rule::outfit_directive              => {
+                    let outfit = action_iter.next().context("Outfit change missing outfit")?;
+                    StageCommand::CharacterChange { character, operation: ActorOperation::OutfitChange(outfit.as_str().trim_matches('"').to_owned()) }
+                },
+                Rule::zoom_directive                => {
+                    let zoom: f32 = action_iter.next().context("Zoom missing factor")?.as_str().parse().context("Zoom factor must be a number")?;
+                    ensure!(zoom > 0., "Zoom factor must be above 0, found {}", zoom);
+                    let tween = build_tween(action_iter)?;
+                    StageCommand::CharacterChange { character, operation: ActorOperation::Zoom(zoom, tween) }
+                },
+                Rule::layer_directive               => { build_layer_change(&character, action_iter)? },
                 other => { bail!("Unexpected rule in character_action {:?}", other); }
             }
         },
@@ -409,7 +590,7 @@ pub(crate) fn build_stage_command(pair: Pair<Rule>) -> Result<Statement> {
             
             match directive.as_rule() {
                 Rule::actor_spawn_directive     => build_actor_spawn_directive(ActorType::Animation, &animation, directive.as_str(), inner_rules)?,
-                Rule::actor_movement_directive  => build_actor_movement_directive(&animation, directive.as_str(), inner_rules)?,
+                Rule::actor_movement_directive  => build_actor_movement_directive(ActorType::Animation, &animation, directive.as_str(), inner_rules)?,
                 Rule::actor_direction_directive => build_actor_direction_directive(ActorType::Animation, &animation, directive)?,
                 other => { return Err(anyhow::anyhow!("Unexpected directive! {:?}", other).into()); }
             }
@@ -437,6 +618,19 @@ pub fn build_code_statement(code_pair: Pair<Rule>) -> Result<Statement> {
             }
             CodeStatement::Log { exprs }
         },
+        Rule::set => {
+            let mut inner = statement_pair.into_inner();
+            let variable = inner.next()
+                .context("Set statement missing variable")?
+                .as_str()
+                .trim_start_matches('$')
+                .to_owned();
+            let expr_pair = inner.next()
+                .context("Set statement missing expression")?;
+            let expr = build_expression(expr_pair)
+                .context("Failed to build expression for set statement")?;
+            CodeStatement::Set { variable, expr }
+        },
         other => bail!("Unexpected rule in code statement: {:?}", other)
     };
 
@@ -458,15 +652,17 @@ pub fn build_dialogue(pair: Pair<Rule>) -> Result<Vec<Statement>> {
         Some(n) if n.as_rule() == Rule::dialogue_emotion_change => {
             let emotion_pair = inner_rules.next()
                 .context("Expected emotion pair")?;
-            let emotion_name_pair = emotion_pair.into_inner().next()
+            let mut emotion_iter = emotion_pair.into_inner();
+            let emotion_name_pair = emotion_iter.next()
                 .context("Emotion change missing emotion name")?;
 
             ensure!(emotion_name_pair.as_rule() == Rule::emotion_name,
                 "Expected emotion name, found {:?}", emotion_name_pair.as_rule());
+            let crossfade = build_tween(emotion_iter)?.duration;
 
             Some(Statement::Stage(StageCommand::CharacterChange {
                 character: character.clone(),
-                operation: ActorOperation::EmotionChange(emotion_name_pair.as_str().to_owned())
+                operation: ActorOperation::EmotionChange(emotion_name_pair.as_str().to_owned(), crossfade)
             }))
         },
         _ => None
@@ -518,6 +714,31 @@ pub fn build_dialogue(pair: Pair<Rule>) -> Result<Vec<Statement>> {
     Ok(statements)
 }
 
+pub fn build_together(pair: Pair<Rule>) -> Result<Statement> {
+    ensure!(pair.as_rule() == Rule::together,
+        "Expected together, found {:?}", pair.as_rule());
+
+    let mut lines = Vec::new();
+    for line_pair in pair.into_inner() {
+        ensure!(line_pair.as_rule() == Rule::together_line,
+            "Expected together line, found {:?}", line_pair.as_rule());
+
+        let mut inner_rules = line_pair.into_inner();
+        let character = inner_rules.next()
+            .context("Together line missing character identifier")?
+            .as_str()
+            .to_owned();
+        let dialogue_text_pair = inner_rules.next()
+            .context("Together line missing dialogue text")?;
+        let dialogue = build_expression(dialogue_text_pair)
+            .context("Failed to build expression for together line")?;
+
+        lines.push(Dialogue { character, dialogue });
+    }
+
+    Ok(Statement::TextItem(TextItem::Together(lines)))
+}
+
 pub fn build_infotext(pair: Pair<Rule>) -> Result<Statement> {
     let mut pairs = pair.into_inner();
     let narrator_pair = pairs.next()
@@ -568,6 +789,7 @@ pub fn build_scenes(pair: Pair<Rule>) -> Result<Act> {
                                 .context("No text item rule found")?;
                             match text_item.as_rule() {
                                 Rule::infotext => build_infotext(text_item)?,
+                                Rule::together => build_together(text_item)?,
                                 Rule::dialogue => {
                                     let mut inner_statements = build_dialogue(text_item.clone())?;
                                     statements.extend(inner_statements.drain(..));
@@ -592,3 +814,264 @@ pub fn build_scenes(pair: Pair<Rule>) -> Result<Act> {
     act.entrypoint = first_scene_id.context("No scenes found in act")?;
     Ok(act)
 }
+
+#[cfg(test)]
+mod tests {
+    use pest::Parser;
+    use super::*;
+
+    /// Stage command written as it would be in a script
+    fn stage_command(source: &str) -> Result<StageCommand> {
+        let pair = SabiParser::parse(Rule::stage_command, source)?.next().context("Stage command is empty")?;
+        match build_stage_command(pair)? {
+            Statement::Stage(command) => Ok(command),
+            other => bail!("Expected a stage command, found {:?}", other)
+        }
+    }
+
+    /// Operation run by a stage command written as it would be in a script
+    fn actor_operation(source: &str) -> Result<ActorOperation> {
+        let pair = SabiParser::parse(Rule::stage_command, source)?.next().context("Stage command is empty")?;
+        match build_stage_command(pair)? {
+            Statement::Stage(StageCommand::CharacterChange { operation, .. }) => Ok(operation),
+            Statement::Stage(StageCommand::AnimationChange { operation, .. }) => Ok(operation),
+            other => bail!("Expected an actor command, found {:?}", other)
+        }
+    }
+
+    #[test]
+    fn mode_switches_between_nvl_and_adv() {
+        assert!(matches!(stage_command("(Mode NVL)").unwrap(), StageCommand::ModeChange { mode: PresentationMode::Nvl }));
+        assert!(matches!(stage_command("(Mode ADV)").unwrap(), StageCommand::ModeChange { mode: PresentationMode::Adv }));
+        assert!(stage_command("(Mode VN)").is_err());
+    }
+
+    #[test]
+    fn page_clears() {
+        assert!(matches!(stage_command("(Page clears)").unwrap(), StageCommand::PageClear));
+    }
+
+    #[test]
+    fn together_lines_keep_every_speaker() {
+        let pair = SabiParser::parse(Rule::together, "Together: Nayu \"Hi\" & Kiyomi \"Hello\"").unwrap().next().unwrap();
+        let Statement::TextItem(TextItem::Together(lines)) = build_together(pair).unwrap() else {
+            panic!("Expected a together line");
+        };
+        let lines: Vec<(String, String)> = lines.iter()
+            .map(|line| (line.character.clone(), line.dialogue.resolve(&HashMap::new()).evaluate_into_string().unwrap()))
+            .collect();
+        assert_eq!(lines, [("Nayu".into(), "Hi".into()), ("Kiyomi".into(), "Hello".into())]);
+
+        // A single speaker is a plain dialogue line
+        assert!(SabiParser::parse(Rule::together, "Together: Nayu \"Hi\"").is_err());
+    }
+
+    #[test]
+    fn characters_speak_in_bubbles_or_in_the_textbox() {
+        assert!(matches!(
+            stage_command("(Nayu speaks in bubbles)").unwrap(),
+            StageCommand::SpeechChange { character, bubbles: true } if character == "Nayu"
+        ));
+        assert!(matches!(stage_command("(Nayu speaks in textbox)").unwrap(), StageCommand::SpeechChange { bubbles: false, .. }));
+    }
+
+    #[test]
+    fn character_moves_to_percentage_and_named_positions() {
+        assert_eq!(
+            actor_operation("(Nayu moves to 30%)").unwrap(),
+            ActorOperation::Move(ActorPosition::Character(CharacterPosition::Percentage(30.)), Tween::default())
+        );
+        assert_eq!(
+            actor_operation("(Nayu moves to \"window\" over 1s)").unwrap(),
+            ActorOperation::Move(
+                ActorPosition::Character(CharacterPosition::Named("window".into())),
+                Tween { duration: Some(1.), ..default() }
+            )
+        );
+    }
+
+    #[test]
+    fn animation_moves_to_percentage_coordinates() {
+        assert_eq!(
+            actor_operation("(Animated \"sparkles\" moves to 20% 75.5%)").unwrap(),
+            ActorOperation::Move(ActorPosition::Animation(AnimationPosition::Percentage(20., 75.5)), Tween::default())
+        );
+        assert_eq!(
+            actor_operation("(Animated \"sparkles\" moves top)").unwrap(),
+            ActorOperation::Move(ActorPosition::Animation(AnimationPosition::Top), Tween::default())
+        );
+    }
+
+    #[test]
+    fn actors_move_by_offsets() {
+        assert_eq!(
+            actor_operation("(Nayu moves left by 10% without waiting)").unwrap(),
+            ActorOperation::MoveBy(ActorOffset::Character(-10., 0.), Tween { blocking: false, ..default() })
+        );
+        assert_eq!(
+            actor_operation("(Animated \"sparkles\" moves down by 12.5% ease-in)").unwrap(),
+            ActorOperation::MoveBy(ActorOffset::Animation(0., -12.5), Tween { easing: Easing::EaseIn, ..default() })
+        );
+    }
+
+    #[test]
+    fn characters_move_up_and_down() {
+        assert_eq!(
+            actor_operation("(Nayu moves up by 5%)").unwrap(),
+            ActorOperation::MoveBy(ActorOffset::Character(0., 5.), Tween::default())
+        );
+    }
+
+    #[test]
+    fn zooms_read_their_factor() {
+        assert_eq!(
+            actor_operation("(Nayu zooms to 1.3 over 0.5s)").unwrap(),
+            ActorOperation::Zoom(1.3, Tween { duration: Some(0.5), ..default() })
+        );
+        assert!(actor_operation("(Nayu zooms to 0)").is_err());
+    }
+
+    #[test]
+    fn offsets_need_a_percentage() {
+        assert!(actor_operation("(Nayu moves left by 10)").is_err());
+    }
+
+    #[test]
+    fn moves_read_their_timing() {
+        assert_eq!(
+            actor_operation("(Nayu moves to left over 0.5s bounce without waiting)").unwrap(),
+            ActorOperation::Move(
+                ActorPosition::Character(CharacterPosition::Left),
+                Tween { duration: Some(0.5), easing: Easing::Bounce, blocking: false }
+            )
+        );
+    }
+
+    #[test]
+    fn fades_read_their_timing() {
+        assert_eq!(
+            actor_operation("(Nayu fade out 2s ease-out)").unwrap(),
+            ActorOperation::Despawn(Some(Tween { duration: Some(2.), easing: Easing::EaseOut, ..default() }))
+        );
+        assert_eq!(actor_operation("(Nayu disappears)").unwrap(), ActorOperation::Despawn(None));
+
+        let ActorOperation::Spawn(info) = actor_operation("(Nayu fade in left happy over 1.5s)").unwrap() else {
+            panic!("Expected a spawn");
+        };
+        assert!(info.fading);
+        assert_eq!(info.emotion.as_deref(), Some("happy"));
+        assert_eq!(info.fade, Tween { duration: Some(1.5), ..default() });
+    }
+
+    #[test]
+    fn only_fades_take_a_duration() {
+        assert!(actor_operation("(Nayu appears left over 1s)").is_err());
+    }
+
+    #[test]
+    fn dialogue_emotion_sets_its_crossfade() {
+        let emotion_change = |source: &str| {
+            let pair = SabiParser::parse(Rule::dialogue, source).unwrap().next().unwrap();
+            match build_dialogue(pair).unwrap().remove(0) {
+                Statement::Stage(StageCommand::CharacterChange { operation, .. }) => operation,
+                other => panic!("Expected an emotion change, found {:?}", other),
+            }
+        };
+        assert_eq!(emotion_change("Nayu: (happy over 0.5s) \"Hi\""), ActorOperation::EmotionChange("happy".into(), Some(0.5)));
+        assert_eq!(emotion_change("Nayu: (happy) \"Hi\""), ActorOperation::EmotionChange("happy".into(), None));
+    }
+
+    #[test]
+    fn layer_changes_toggle_or_switch_the_layer() {
+        assert_eq!(actor_operation("(Nayu layer blush on)").unwrap(), ActorOperation::LayerChange("blush".into(), LayerChange::Show));
+        assert_eq!(actor_operation("(Nayu layer blush off)").unwrap(), ActorOperation::LayerChange("blush".into(), LayerChange::Hide));
+        assert_eq!(
+            actor_operation("(Nayu layer face smiling)").unwrap(),
+            ActorOperation::LayerChange("face".into(), LayerChange::Variant("smiling".into()))
+        );
+        assert_eq!(
+            actor_operation("(Nayu layer face online)").unwrap(),
+            ActorOperation::LayerChange("face".into(), LayerChange::Variant("online".into()))
+        );
+    }
+
+    #[test]
+    fn misspelled_commands_are_not_layer_changes() {
+        assert!(actor_operation("(Nayu apears left)").is_err());
+        assert!(actor_operation("(Nayu looking lft)").is_err());
+        assert!(actor_operation("(Nayu blush on)").is_err());
+    }
+
+    #[test]
+    fn outfits_are_worn_on_spawn_or_changed() {
+        assert_eq!(actor_operation("(Nayu wears \"casual\")").unwrap(), ActorOperation::OutfitChange("casual".into()));
+
+        let ActorOperation::Spawn(info) = actor_operation("(Nayu appears left wearing \"school\" happy)").unwrap() else {
+            panic!("Expected a spawn");
+        };
+        assert_eq!(info.outfit.as_deref(), Some("school"));
+        assert_eq!(info.emotion.as_deref(), Some("happy"));
+    }
+
+    #[test]
+    fn focus_toggles_on_and_off() {
+        assert!(matches!(stage_command("(Focus on)").unwrap(), StageCommand::FocusChange { enabled: true }));
+        assert!(matches!(stage_command("(Focus off)").unwrap(), StageCommand::FocusChange { enabled: false }));
+        assert!(stage_command("(Focus maybe)").is_err());
+    }
+
+    #[test]
+    fn actors_move_between_depths() {
+        assert_eq!(actor_operation("(Nayu moves to front)").unwrap(), ActorOperation::Reorder(DepthTarget::Front));
+        assert_eq!(actor_operation("(Nayu moves to back)").unwrap(), ActorOperation::Reorder(DepthTarget::Back));
+        assert_eq!(actor_operation("(Nayu moves to layer -2)").unwrap(), ActorOperation::Reorder(DepthTarget::Layer(-2)));
+        assert_eq!(actor_operation("(Nayu moves behind Kiyomi)").unwrap(), ActorOperation::Reorder(DepthTarget::Behind("Kiyomi".into())));
+        assert_eq!(
+            actor_operation("(Animated \"rain\" moves in front of Nayu)").unwrap(),
+            ActorOperation::Reorder(DepthTarget::InFrontOf("Nayu".into()))
+        );
+        assert_eq!(
+            actor_operation("(Nayu moves behind \"rain\")").unwrap(),
+            ActorOperation::Reorder(DepthTarget::Behind("rain".into()))
+        );
+        assert_eq!(actor_operation("(Animated \"rain\" moves above the textbox)").unwrap(), ActorOperation::Reorder(DepthTarget::AboveTextbox));
+        assert_eq!(actor_operation("(Animated \"rain\" moves above textbox)").unwrap(), ActorOperation::Reorder(DepthTarget::AboveTextbox));
+    }
+
+    #[test]
+    fn layers_must_be_whole_numbers() {
+        assert!(actor_operation("(Nayu moves to layer 1.5)").is_err());
+    }
+
+    fn expression(source: &str) -> Result<Expr> {
+        let pair = SabiParser::parse(Rule::expr, source)?.next().context("Expression is empty")?;
+        build_expression(pair)
+    }
+
+    #[test]
+    fn set_assigns_an_expression_to_a_variable() {
+        let pair = SabiParser::parse(Rule::code, "{set $route = \"na\" + \"yu\"}").unwrap().next().unwrap();
+        let Statement::Code(CodeStatement::Set { variable, expr }) = build_code_statement(pair).unwrap() else {
+            panic!("Expected a set statement");
+        };
+        assert_eq!(variable, "route");
+        assert_eq!(expr.resolve(&HashMap::new()).evaluate_into_string().unwrap(), "nayu");
+    }
+
+    #[test]
+    fn variables_resolve_to_their_value() {
+        let expr = expression("\"Hello \" + $player_name").unwrap();
+        assert!(matches!(&expr, Expr::Add { rhs, .. } if matches!(rhs.as_ref(), Expr::Variable(name) if name == "player_name")));
+
+        let variables = HashMap::from([("player_name".to_owned(), "Nayu".to_owned())]);
+        assert_eq!(expr.resolve(&variables).evaluate_into_string().unwrap(), "Hello Nayu");
+    }
+
+    #[test]
+    fn unset_variables_read_as_empty() {
+        let expr = expression("\"Route: \" + $route").unwrap();
+        assert_eq!(expr.resolve(&HashMap::new()).evaluate_into_string().unwrap(), "Route: ");
+        // Only resolved expressions can be evaluated
+        assert!(expr.evaluate().is_err());
+    }
+}
@@ -1,5 +1,5 @@
-use crate::chat::controller::InfoTextMessage;
-use crate::{BackgroundChangeMessage, CharacterSayMessage, GUIChangeMessage, ActorChangeMessage, VisualNovelState};
+use crate::chat::controller::{BubbleLine, InfoTextMessage, PresentationChangeMessage, PresentationMode, SpeechBubbleMessage};
+use crate::{BackgroundChangeMessage, CharacterSayMessage, GUIChangeMessage, ActorChangeMessage, HistoryItem, VisualNovelState};
 use crate::compiler::ast::{CodeStatement, Dialogue, Evaluate, InfoText, StageCommand, Statement, TextItem};
 use bevy::prelude::*;
 use anyhow::{Context, Result};
@@ -15,7 +15,7 @@ pub struct ActChangeMessage {
     pub act_id: String
 }
 
-pub struct InvokeContext<'l, 'a, 'b, 'd, 'e, 'f, 'g, 'h, 'i> {
+pub struct InvokeContext<'l, 'a, 'b, 'd, 'e, 'f, 'g, 'h, 'i, 'j, 'k> {
     pub game_state: &'l mut ResMut<'a, VisualNovelState>,
     pub character_say_message: &'l mut MessageWriter<'b, CharacterSayMessage>,
     pub background_change_message: &'l mut MessageWriter<'d, BackgroundChangeMessage>,
@@ -24,20 +24,31 @@ pub struct InvokeContext<'l, 'a, 'b, 'd, 'e, 'f, 'g, 'h, 'i> {
     pub act_change_message: &'l mut MessageWriter<'g, ActChangeMessage>,
     pub actor_change_message: &'l mut MessageWriter<'h, ActorChangeMessage>,
     pub info_text_message: &'l mut MessageWriter<'i, InfoTextMessage>,
+    pub presentation_change_message: &'l mut MessageWriter<'j, PresentationChangeMessage>,
+    pub speech_bubble_message: &'l mut MessageWriter<'k, SpeechBubbleMessage>,
 }
 pub trait Invoke {
     fn invoke ( &self, ctx: InvokeContext ) -> Result<()>;
 }
 impl Invoke for Dialogue {
     fn invoke( &self, ctx: InvokeContext ) -> Result<()> {
-        let dialogue = self.dialogue.evaluate_into_string()
+        let dialogue = self.dialogue.resolve(&ctx.game_state.variables).evaluate_into_string()
             .context("...while evaluating Dialogue expression")?;
         info!("Invoking Dialogue::Say");
+        ctx.game_state.push_history(HistoryItem::Lines(vec![format!("{}: {}", self.character, dialogue)]));
+        ctx.game_state.stage.record_line(Some(&self.character), &dialogue);
 
-        ctx.character_say_message.write(CharacterSayMessage {
-            name: self.character.to_owned(),
-            message: dialogue
-        });
+        // The NVL page has no room for bubbles
+        if ctx.game_state.stage.mode == PresentationMode::Adv && ctx.game_state.stage.bubbles.contains(&self.character) {
+            ctx.speech_bubble_message.write(SpeechBubbleMessage {
+                lines: vec![BubbleLine { name: self.character.to_owned(), text: dialogue }]
+            });
+        } else {
+            ctx.character_say_message.write(CharacterSayMessage {
+                name: self.character.to_owned(),
+                message: dialogue
+            });
+        }
 
         ctx.game_state.blocking = true;
 
@@ -46,23 +57,49 @@ impl Invoke for Dialogue {
 }
 impl Invoke for InfoText {
     fn invoke ( &self, ctx: InvokeContext ) -> Result<()> {
-        let text = self.infotext.evaluate_into_string()
+        let text = self.infotext.resolve(&ctx.game_state.variables).evaluate_into_string()
             .context("...while evaluating InfoText expression")?;
         info!("Invoking InfoText");
-        
-        
-        // This is needed to prevent remaining stuck during a rewind process:
-        // if the user goes backwards to a infotext statement, it will cause
-        // the game_state to block and the vn commands to disappear, making it
-        // impossible to go backwards beyond the infotext.
-        if ctx.game_state.rewinding == 0 {
-            ctx.info_text_message.write(InfoTextMessage {
-                text,
-            });
-        
-            ctx.game_state.blocking = true;
+        ctx.game_state.push_history(HistoryItem::Lines(vec![text.clone()]));
+        ctx.game_state.stage.record_line(None, &text);
+
+        ctx.info_text_message.write(InfoTextMessage {
+            text,
+        });
+
+        ctx.game_state.blocking = true;
+
+        Ok(())
+    }
+}
+impl Invoke for Vec<Dialogue> {
+    fn invoke( &self, ctx: InvokeContext ) -> Result<()> {
+        let mut lines = Vec::new();
+        for line in self {
+            let text = line.dialogue.resolve(&ctx.game_state.variables).evaluate_into_string()
+                .context("...while evaluating Together expression")?;
+            ctx.game_state.stage.record_line(Some(&line.character), &text);
+            lines.push(BubbleLine { name: line.character.to_owned(), text });
         }
-        
+        info!("Invoking Together for {} characters", lines.len());
+        ctx.game_state.push_history(HistoryItem::Lines(
+            lines.iter().map(|line| format!("{}: {}", line.name, line.text)).collect()
+        ));
+
+        if ctx.game_state.stage.mode == PresentationMode::Nvl {
+            // Lines are put on the page one after the other
+            for line in lines {
+                ctx.character_say_message.write(CharacterSayMessage {
+                    name: line.name,
+                    message: line.text
+                });
+            }
+        } else {
+            ctx.speech_bubble_message.write(SpeechBubbleMessage { lines });
+        }
+
+        ctx.game_state.blocking = true;
+
         Ok(())
     }
 }
@@ -71,17 +108,19 @@ impl Invoke for StageCommand {
         match self {
             StageCommand::BackgroundChange { operation } => {
                 info!("Invoking StageCommand::BackgroundChange to {:?}", operation);
+                ctx.game_state.stage.record_background(operation);
                 ctx.background_change_message.write(BackgroundChangeMessage {
                     operation: operation.clone(),
                 });
             },
             StageCommand::GUIChange { gui_target, sprite_expr, image_mode } => {
                 let gui_target = gui_target.clone();
-                let sprite_id = sprite_expr.evaluate_into_string()
+                let sprite_id = sprite_expr.resolve(&ctx.game_state.variables).evaluate_into_string()
                     .context("...while evaluating GUIChange sprite expression")?;
                 let image_mode = image_mode.clone();
                 
                 info!("Invoking StageCommand::GUIChange to {:?}'s {}", gui_target, sprite_id);
+                ctx.game_state.stage.record_gui(&gui_target, &sprite_id, &image_mode);
                 ctx.gui_change_message.write(GUIChangeMessage {
                     gui_target,
                     sprite_id,
@@ -89,7 +128,7 @@ impl Invoke for StageCommand {
                 });
             },
             StageCommand::SceneChange { scene_expr } => {
-                let scene_id = scene_expr.evaluate_into_string()
+                let scene_id = scene_expr.resolve(&ctx.game_state.variables).evaluate_into_string()
                     .context("...while evaluating SceneChange expression")?;
                 
                 info!("Invoking StageCommand::SceneChange to {}", scene_id);
@@ -98,7 +137,7 @@ impl Invoke for StageCommand {
                 });
             },
             StageCommand::ActChange { act_expr } => {
-                let act_id = act_expr.evaluate_into_string()
+                let act_id = act_expr.resolve(&ctx.game_state.variables).evaluate_into_string()
                     .context("...while evaluating ActChange expression")?;
                 
                 info!("Invoking StageCommand::ActChange to {}", act_id);
@@ -106,8 +145,27 @@ impl Invoke for StageCommand {
                     act_id
                 });
             },
+            StageCommand::ModeChange { mode } => {
+                info!("Invoking StageCommand::ModeChange to {:?}", mode);
+                ctx.game_state.stage.record_mode(*mode);
+                ctx.presentation_change_message.write(PresentationChangeMessage::Mode(*mode));
+            },
+            StageCommand::PageClear => {
+                info!("Invoking StageCommand::PageClear");
+                ctx.game_state.stage.page.clear();
+                ctx.presentation_change_message.write(PresentationChangeMessage::PageClear);
+            },
+            StageCommand::SpeechChange { character, bubbles } => {
+                info!("Invoking StageCommand::SpeechChange of {} to {}", character, if *bubbles { "bubbles" } else { "textbox" });
+                ctx.game_state.stage.record_speech(character, *bubbles);
+            },
+            StageCommand::FocusChange { enabled } => {
+                info!("Invoking StageCommand::FocusChange to {}", enabled);
+                ctx.game_state.stage.focus = Some(*enabled);
+            },
             StageCommand::CharacterChange { character, operation } => {
                 info!("Invoking StageCommand::CharacterChange to {} of type {:?}", character, operation);
+                ctx.game_state.stage.record_actor(character, operation);
                 let message = ActorChangeMessage {
                     name: character.clone(),
                     operation: operation.clone()
@@ -116,6 +174,7 @@ impl Invoke for StageCommand {
             },
             StageCommand::AnimationChange { animation, operation } => {
                 info!("Invoking StageCommand::AnimationChange to {} of type {:?}", animation, operation);
+                ctx.game_state.stage.record_actor(animation, operation);
                 let message = ActorChangeMessage {
                     name: animation.clone(),
                     operation: operation.clone()
@@ -128,13 +187,13 @@ impl Invoke for StageCommand {
     }
 }
 impl Invoke for CodeStatement {
-    fn invoke( &self, _ctx: InvokeContext ) -> Result<()> {
+    fn invoke( &self, ctx: InvokeContext ) -> Result<()> {
         match self {
             CodeStatement::Log { exprs } => {
                 let mut log_parts: Vec<String> = Vec::new();
 
                 for expr in exprs {
-                    let part = expr.evaluate_into_string()
+                    let part = expr.resolve(&ctx.game_state.variables).evaluate_into_string()
                         .context("...while evaluating Log expression")?;
                     log_parts.push(part);
                 }
@@ -142,6 +201,15 @@ impl Invoke for CodeStatement {
                 let log_message = log_parts.join(" ");
                 println!("[ Log ] {}", log_message);
 
+                Ok(())
+            },
+            CodeStatement::Set { variable, expr } => {
+                let value = expr.resolve(&ctx.game_state.variables).evaluate_into_string()
+                    .context("...while evaluating Set expression")?;
+
+                info!("Invoking CodeStatement::Set ${} = {}", variable, value);
+                ctx.game_state.variables.insert(variable.clone(), value);
+
                 Ok(())
             },
         }
@@ -155,7 +223,9 @@ impl Invoke for Statement {
                     TextItem::Dialogue(dialogue) => dialogue.invoke(ctx)
                         .context("...while invoking Dialogue statement")?,
                     TextItem::InfoText(infotext) => infotext.invoke(ctx)
-                        .context("...while invoking InfoText statement")?, 
+                        .context("...while invoking InfoText statement")?,
+                    TextItem::Together(lines) => lines.invoke(ctx)
+                        .context("...while invoking Together statement")?,
                 }
             }
             Statement::Stage(stage) => stage.invoke(ctx)
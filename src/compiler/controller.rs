@@ -1,13 +1,16 @@
-use crate::actor::ActorChangeMessage;
-use crate::chat::controller::InfoTextMessage;
-use crate::compiler::ast::Statement;
+use crate::actor::{ActorChangeMessage, ActorOperation, controller::{NamedPositions, SpawnInfo}};
+use crate::chat::controller::{InfoTextMessage, PresentationChangeMessage, SpeechBubbleMessage};
+use crate::compiler::ast::{StageCommand, Statement, TextItem};
 use crate::compiler::calling::{Invoke, InvokeContext, SceneChangeMessage, ActChangeMessage};
+use crate::compiler::rollback::{Snapshot, StageRestoreMessage, StageState};
 use crate::{Cursor, HistoryItem, SabiEnd, ast};
-use crate::{BackgroundChangeMessage, CharacterSayMessage, GUIChangeMessage, SabiStart, ScriptId, VisualNovelState};
+use crate::{BackgroundChangeMessage, CharacterSayMessage, GUIChangeMessage, SabiResume, SabiStart, ScriptId, VisualNovelState};
+use crate::save::SaveData;
 
 use std::collections::HashMap;
 use std::path::PathBuf;
 use bevy::asset::{LoadState, LoadedFolder};
+use bevy::ecs::system::SystemParam;
 use bevy::color::palettes::css::{BLACK, WHITE};
 use bevy::prelude::*;
 use anyhow::{Context, Result};
@@ -23,6 +26,15 @@ pub enum SabiState {
     Running,
 }
 
+/// Suspends the script and every animation while the system menu is open
+#[derive(SubStates, Debug, Default, Clone, Copy, Hash, Eq, PartialEq)]
+#[source(SabiState = SabiState::Running)]
+pub(crate) enum PauseState {
+    #[default]
+    Playing,
+    Paused,
+}
+
 #[derive(Resource, Default)]
 struct ControllersReady {
     pub background_controller: bool,
@@ -67,42 +79,60 @@ struct HandleToScriptsFolder(Handle<LoadedFolder>);
 #[derive(Resource, Default)]
 struct ScriptsResource(ScriptsMap);
 type ScriptsMap = HashMap<ScriptId, Handle<ast::Act>>;
+/// Inserted once the acts of the scripts folder are in the [ScriptsResource]
 #[derive(Resource)]
-struct CurrentScript(pub ScriptId);
+struct ScriptsRegistered;
+#[derive(Resource)]
+pub(crate) struct CurrentScript(pub ScriptId);
 
 pub struct Compiler;
 impl Plugin for Compiler {
     fn build(&self, app: &mut App) {
         app
             .init_state::<SabiState>()
+            .add_sub_state::<PauseState>()
             .init_resource::<ControllersReady>()
             .init_resource::<ScriptsResource>()
             .add_message::<ControllerReadyMessage>()
             .add_message::<ControllersSetStateMessage>()
             .add_message::<SceneChangeMessage>()
             .add_message::<ActChangeMessage>()
+            .add_message::<StageRestoreMessage>()
             .add_message::<SabiStart>()
+            .add_message::<SabiResume>()
             .add_message::<SabiEnd>()
+            .add_systems(Startup, import_scripts_folder)
+            .add_systems(Update, register_scripts.run_if(not(resource_exists::<ScriptsRegistered>)))
             .add_systems(OnEnter(SabiState::Idle), (clean_states, propagate_state).chain())
             .add_systems(Update, check_start.run_if(in_state(SabiState::Idle)))
             .add_systems(OnExit(SabiState::Idle), spawn_ui_root)
-            .add_systems(OnEnter(SabiState::WaitingForControllers),
-                (
-                    propagate_state,
-                    import_scripts_folder
-                ).chain())
+            .add_systems(OnEnter(SabiState::WaitingForControllers), propagate_state)
             .add_systems(Update, check_states.run_if(in_state(SabiState::WaitingForControllers)))
             .add_systems(OnEnter(SabiState::Running), trigger_running_controllers)
-            .add_systems(Update, (run, handle_scene_changes, handle_act_changes).run_if(in_state(SabiState::Running)));
+            .add_systems(Update, (handle_rewind, run, handle_scene_changes, handle_act_changes).chain().run_if(in_state(PauseState::Playing)));
     }
 }
 fn clean_states(
     mut controllers_state: ResMut<ControllersReady>,
+    mut game_state: ResMut<VisualNovelState>,
 ) {
     controllers_state.reset();
+    game_state.stage = StageState::default();
+    game_state.snapshots.clear();
+    game_state.rewinding = None;
+    game_state.roll_forward.clear();
+    game_state.history_stash.clear();
+
+    // Anything written while replaying is discarded, the player's own state is put back
+    if game_state.replay.take().is_some() {
+        let (variables, history) = game_state.replay_stash.take().unwrap_or_default();
+        game_state.variables = variables;
+        game_state.history = history;
+    }
 }
 fn trigger_running_controllers(
     mut msg_writer: MessageWriter<ControllersSetStateMessage>,
+    mut scene_change_writer: MessageWriter<SceneChangeMessage>,
     mut visual_novel_state: ResMut<VisualNovelState>,
     current_script: Res<CurrentScript>,
     scripts_resource: Res<ScriptsResource>,
@@ -114,12 +144,33 @@ fn trigger_running_controllers(
         .context("Could not find script element")?;
 
     visual_novel_state.act = Box::new(act.clone());
-    visual_novel_state.statements = Cursor::new(act.scenes.get(&act.entrypoint)
-        .context("Error retrieving act entrypoint")?
-        .statements.clone());
+
+    if visual_novel_state.rewinding.is_some() {
+        // Resumed saves already carry their history, the saved scene is loaded by `handle_rewind`
+        *visual_novel_state.scene = ast::Scene::default();
+        visual_novel_state.statements = Cursor::default();
+        visual_novel_state.blocking = false;
+        msg_writer.write(ControllersSetStateMessage(SabiState::Running));
+        return Ok(());
+    }
+
     visual_novel_state.history.push(HistoryItem::Descriptor(format!("Act: {}\n", act.name)));
-    visual_novel_state.history.push(HistoryItem::Descriptor(format!("Scene: {}\n", act.entrypoint)));
-    visual_novel_state.blocking = false;
+
+    if let Some(replay) = visual_novel_state.replay.clone() {
+        // The replayed scene is loaded by `handle_scene_changes`,
+        //  so block until it has been processed
+        *visual_novel_state.scene = ast::Scene::default();
+        visual_novel_state.statements = Cursor::default();
+        visual_novel_state.blocking = true;
+        scene_change_writer.write(SceneChangeMessage { scene_id: replay.scene });
+    } else {
+        let entrypoint = act.scenes.get(&act.entrypoint)
+            .context("Error retrieving act entrypoint")?;
+        visual_novel_state.scene = entrypoint.clone();
+        visual_novel_state.statements = Cursor::new(entrypoint.statements.clone());
+        visual_novel_state.history.push(HistoryItem::Descriptor(format!("Scene: {}\n", act.entrypoint)));
+        visual_novel_state.blocking = false;
+    }
 
     msg_writer.write(ControllersSetStateMessage(SabiState::Running));
     Ok(())
@@ -133,10 +184,38 @@ fn propagate_state(
 fn check_start(
     mut commands: Commands,
     mut state: ResMut<NextState<SabiState>>,
-    mut msg_reader: MessageReader<SabiStart>
+    mut msg_reader: MessageReader<SabiStart>,
+    mut resume_reader: MessageReader<SabiResume>,
+    mut game_state: ResMut<VisualNovelState>,
 ) {
+    let mut script_id = None;
     for msg in msg_reader.read() {
-        let script_id = msg.0.clone();
+        if let Some(replay) = &msg.replay {
+            info!("Replaying scene '{}' of {:?}", replay.scene, msg.script_id);
+            let variables = std::mem::replace(&mut game_state.variables, replay.variables.clone());
+            let history = std::mem::take(&mut game_state.history);
+            // A replay started from another one keeps the player's state it put aside
+            if game_state.replay_stash.is_none() {
+                game_state.replay_stash = Some((variables, history));
+            }
+        }
+        game_state.replay = msg.replay.clone();
+        script_id = Some(msg.script_id.clone());
+    }
+    for msg in resume_reader.read() {
+        let save = &msg.0;
+        info!("Resuming {:?} from scene '{}'", save.script_id, save.snapshot.scene);
+        game_state.history = save.history.iter().cloned().map(HistoryItem::Descriptor).collect();
+        game_state.read_lines.extend(save.read_lines.iter().cloned());
+        // Restored by `handle_rewind` once every controller is running
+        game_state.rewinding = Some(Snapshot {
+            history_len: game_state.history.len(),
+            ..save.snapshot.clone()
+        });
+        script_id = Some(save.script_id.clone());
+    }
+
+    if let Some(script_id) = script_id {
         commands.insert_resource(CurrentScript(script_id));
         state.set(SabiState::WaitingForControllers);
     }
@@ -213,37 +292,48 @@ fn define_script_entry(
     };
     Ok((script_id, handle))
 }
-fn check_states(
-    mut msg_controller_reader: MessageReader<ControllerReadyMessage>,
-    mut controllers_state: ResMut<ControllersReady>,
-    mut sabi_state: ResMut<NextState<SabiState>>,
+/// Fills the [ScriptsResource] as soon as the scripts folder is loaded, so saves can be checked against it from the title screen.
+fn register_scripts(
+    mut commands: Commands,
     asset_server: Res<AssetServer>,
     folder_handle: Res<HandleToScriptsFolder>,
     loaded_folders: Res<Assets<LoadedFolder>>,
     mut scripts_resource: ResMut<ScriptsResource>,
 ) -> Result<(), BevyError> {
-    if !controllers_state.compiler_controller {
-        if let Some(state) = asset_server.get_load_state(folder_handle.0.id()) {
-            match state {
-                LoadState::Loaded => {
-                    if let Some(loaded_folder) = loaded_folders.get(folder_handle.0.id()) {
-                        for handle in &loaded_folder.handles {
-                            let (script_id, entry) = define_script_entry(handle.clone().typed())?;
-                            scripts_resource.0.insert(script_id, entry);
-                        }
-                        info!("Resource complete: {:?}", scripts_resource.0);
-                        controllers_state.compiler_controller = true;
-                    } else {
-                        return Err(anyhow::anyhow!("Could not find script file loaded folder!").into());
+    if let Some(state) = asset_server.get_load_state(folder_handle.0.id()) {
+        match state {
+            LoadState::Loaded => {
+                if let Some(loaded_folder) = loaded_folders.get(folder_handle.0.id()) {
+                    for handle in &loaded_folder.handles {
+                        let (script_id, entry) = define_script_entry(handle.clone().typed())?;
+                        scripts_resource.0.insert(script_id, entry);
                     }
+                    info!("Resource complete: {:?}", scripts_resource.0);
+                    commands.insert_resource(ScriptsRegistered);
+                } else {
+                    return Err(anyhow::anyhow!("Could not find script file loaded folder!").into());
                 }
-                LoadState::Failed(e) => {
-                    return Err(anyhow::anyhow!("Error loading scripts assets: {}", e.to_string()).into());
-                }
-                _ => {}
             }
+            LoadState::Failed(e) => {
+                return Err(anyhow::anyhow!("Error loading scripts assets: {}", e).into());
+            }
+            _ => {}
         }
     }
+    Ok(())
+}
+fn check_states(
+    mut msg_controller_reader: MessageReader<ControllerReadyMessage>,
+    mut controllers_state: ResMut<ControllersReady>,
+    mut sabi_state: ResMut<NextState<SabiState>>,
+    scripts_registered: Option<Res<ScriptsRegistered>>,
+    scripts_resource: Res<ScriptsResource>,
+    acts: Res<Assets<ast::Act>>,
+    named_positions: Res<NamedPositions>,
+) -> Result<(), BevyError> {
+    if scripts_registered.is_some() {
+        controllers_state.compiler_controller = true;
+    }
 
     for event in msg_controller_reader.read() {
         let controller = match event.0 {
@@ -254,11 +344,34 @@ fn check_states(
         *controller = true;
     }
     if controllers_state.all_ready() {
+        // Named positions are only known once the characters are loaded
+        for handle in scripts_resource.0.values() {
+            let act = acts.get(handle.id())
+                .context("Could not find script element")?;
+            validate_named_positions(act, &named_positions)?;
+        }
         sabi_state.set(SabiState::Running);
     }
     Ok(())
 }
-fn run<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> (
+fn validate_named_positions(act: &ast::Act, named_positions: &NamedPositions) -> Result<(), BevyError> {
+    for scene in act.scenes.values() {
+        for statement in &scene.statements {
+            let Statement::Stage(StageCommand::CharacterChange { operation, .. } | StageCommand::AnimationChange { operation, .. }) = statement else {
+                continue;
+            };
+            let position = match operation {
+                ActorOperation::Spawn(SpawnInfo { position: Some(position), .. }) | ActorOperation::Move(position, _) => position,
+                _ => continue,
+            };
+            if let Err(e) = position.validate(named_positions) {
+                return Err(anyhow::anyhow!("{} in scene '{}' of act '{}'", e, scene.name, act.name).into());
+            }
+        }
+    }
+    Ok(())
+}
+fn run<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j> (
     mut game_state: ResMut<'a, VisualNovelState>,
     mut character_say_message: MessageWriter<'b, CharacterSayMessage>,
     mut background_change_message: MessageWriter<'c, BackgroundChangeMessage>,
@@ -267,6 +380,8 @@ fn run<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> (
     mut act_change_message: MessageWriter<'f, ActChangeMessage>,
     mut character_change_message: MessageWriter<'g, ActorChangeMessage>,
     mut info_text_message: MessageWriter<'h, InfoTextMessage>,
+    mut presentation_change_message: MessageWriter<'i, PresentationChangeMessage>,
+    mut speech_bubble_message: MessageWriter<'j, SpeechBubbleMessage>,
 
     mut state: ResMut<NextState<SabiState>>,
     mut ev_controller_writer: MessageWriter<ControllersSetStateMessage>,
@@ -277,29 +392,11 @@ fn run<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> (
         return Ok(());
     }
 
-    let next_statement = if game_state.rewinding > 0 {
-        info!("rewinding {}", game_state.rewinding);
-        game_state.rewinding -= 1;
-        let next_statement = match game_state.statements.prev() {
-            Some(Statement::TextItem(item)) => Some(Statement::TextItem(item)),
-            Some(Statement::Stage(_)) => {
-                game_state.statements.find_previous()
-            },
-            // todo: Statement::Code currently not handled
-            // is history field needed?
-            _ => { None }
-        };
-        if let Some(_) = &next_statement {
-            let _ = game_state.history.pop();
-        }
-        next_statement
-    } else {
-        let next_statement = game_state.statements.next();
-        if let Some(stm) = &next_statement {
-            game_state.history.push(HistoryItem::Statement(stm.clone()));
-        }
-        next_statement
-    };
+    let next_statement = game_state.statements.next();
+    // The line itself is added to the history by its invocation, once its variables are resolved
+    if let Some(Statement::TextItem(item)) = &next_statement {
+        game_state.push_snapshot(matches!(item, TextItem::Dialogue(_) | TextItem::Together(_)));
+    }
 
     if let Some(statement) = next_statement {
         statement.invoke(InvokeContext {
@@ -311,6 +408,8 @@ fn run<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> (
                 act_change_message: &mut act_change_message,
                 actor_change_message: &mut character_change_message,
                 info_text_message: &mut info_text_message,
+                presentation_change_message: &mut presentation_change_message,
+                speech_bubble_message: &mut speech_bubble_message,
             })
             .context("Failed to invoke statement")?;
     } else {
@@ -322,11 +421,78 @@ fn run<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> (
 
     Ok(())
 }
+/// Acts registered from the scripts folder, used to check a save before the running script is torn down for it.
+#[derive(SystemParam)]
+pub(crate) struct LoadedScripts<'w> {
+    scripts_resource: Res<'w, ScriptsResource>,
+    acts: Res<'w, Assets<ast::Act>>,
+}
+
+impl LoadedScripts<'_> {
+    /// Checks that the act and scene a save was made in are still in the scripts, which may have changed since it was written.
+    pub(crate) fn check_save(&self, save: &SaveData) -> Result<()> {
+        // The saved script is started from its own act before rolling over to the act of the snapshot
+        self.act(&save.script_id)?;
+        let act = self.act(&ScriptId { chapter: save.script_id.chapter.clone(), act: save.snapshot.act.clone() })?;
+        if !act.scenes.contains_key(&save.snapshot.scene) {
+            return Err(anyhow::anyhow!("Scene '{}' not found in act '{}'", save.snapshot.scene, save.snapshot.act));
+        }
+        Ok(())
+    }
+
+    fn act(&self, script_id: &ScriptId) -> Result<&ast::Act> {
+        self.scripts_resource.0.get(script_id)
+            .and_then(|handle| self.acts.get(handle))
+            .context(format!("Act '{}' of chapter '{}' not found", script_id.act, script_id.chapter))
+    }
+}
+/// Rolls the runtime back to the [crate::compiler::rollback::Snapshot] requested by
+/// [VisualNovelState::set_rewind] or [VisualNovelState::set_roll_forward], across scene and act boundaries.
+fn handle_rewind(
+    mut game_state: ResMut<VisualNovelState>,
+    mut current_script: ResMut<CurrentScript>,
+    scripts_resource: Res<ScriptsResource>,
+    scripts_assets: Res<Assets<ast::Act>>,
+    mut stage_restore_message: MessageWriter<StageRestoreMessage>,
+) -> Result<(), BevyError> {
+    let Some(snapshot) = game_state.rewinding.take() else {
+        return Ok(());
+    };
+
+    if snapshot.act != game_state.act.name {
+        current_script.0.act = snapshot.act.clone();
+        let act_handle = scripts_resource.0.get(&current_script.0).context(format!("Could not find act handle for {}", current_script.0.act))?;
+        let act = scripts_assets.get(act_handle).context(format!("Could not find act {:?}", act_handle))?;
+        *game_state.act = act.clone();
+    }
+    let scene = game_state.act.scenes.get(&snapshot.scene)
+        .context(format!("Scene '{}' not found in act '{}'", snapshot.scene, snapshot.act))?
+        .clone();
+
+    game_state.statements = Cursor::new(scene.statements.clone());
+    game_state.statements.seek(snapshot.position);
+    game_state.scene = scene;
+    game_state.restore_history(snapshot.history_len);
+    game_state.variables = snapshot.variables.clone();
+    game_state.stage = snapshot.stage.clone();
+    game_state.blocking = false;
+    // Running the restored statement again consumes it from the roll-forward stack
+    game_state.roll_forward.push(snapshot.clone());
+    stage_restore_message.write(StageRestoreMessage(snapshot.stage));
+    info!("[ Rewound to statement {} of scene '{}' ]", snapshot.position, snapshot.scene);
+
+    Ok(())
+}
 fn handle_scene_changes(
     mut scene_change_messages: MessageReader<SceneChangeMessage>,
     mut game_state: ResMut<VisualNovelState>,
 ) -> Result<(), BevyError> {
     for msg in scene_change_messages.read() {
+        if game_state.replay.is_some() && !game_state.scene.name.is_empty() {
+            end_replay(&mut game_state);
+            continue;
+        }
+
         let new_scene = game_state.act.scenes.get(&msg.scene_id)
             .context(format!("Scene '{}' not found in current act", msg.scene_id))?
             .clone();
@@ -334,7 +500,9 @@ fn handle_scene_changes(
         info!("Changing to scene: {}", msg.scene_id);
         game_state.scene = new_scene.clone();
         game_state.statements = Cursor::new(game_state.scene.statements.clone());
-        game_state.history.push(HistoryItem::Descriptor(format!("Scene {}", new_scene.name)));
+        // Focus overrides only last for the scene that set them
+        game_state.stage.focus = None;
+        game_state.push_history(HistoryItem::Descriptor(format!("Scene {}", new_scene.name)));
         game_state.blocking = false;
         info!("[ Scene changed to '{}' ]", msg.scene_id);
     }
@@ -349,6 +517,11 @@ fn handle_act_changes(
     scripts_assets: Res<Assets<ast::Act>>,
 ) -> Result<(), BevyError> {
     for msg in act_change_messages.read() {
+        if game_state.replay.is_some() {
+            end_replay(&mut game_state);
+            continue;
+        }
+
         current_script.0.act = msg.act_id.clone();
         let act_handle = scripts_resource.0.get(&current_script.0).context(format!("Could not find act handle for {}", current_script.0.act))?;
         let act = scripts_assets.get(act_handle).context(format!("Could not find act {:?}", act_handle))?;
@@ -362,10 +535,17 @@ fn handle_act_changes(
         game_state.act = Box::new(act.clone());
         game_state.scene = entrypoint_scene;
         game_state.statements = Cursor::new(game_state.scene.statements.clone());
-        game_state.history.push(HistoryItem::Descriptor(format!("Act {}", act.name)));
+        game_state.push_history(HistoryItem::Descriptor(format!("Act {}", act.name)));
         game_state.blocking = false;
         info!("[ Act changed to '{}' ]", msg.act_id);
     }
 
     Ok(())
 }
+/// Replays stop at the `CURTAIN` of the replayed scene: emptying the cursor
+/// lets `run` end the script and hand control back to the caller.
+fn end_replay(game_state: &mut VisualNovelState) {
+    info!("[ Replay of '{}' reached CURTAIN ]", game_state.scene.name);
+    game_state.statements = Cursor::default();
+    game_state.blocking = false;
+}
@@ -1,5 +1,6 @@
 pub mod controller;
 pub mod ast;
 pub mod calling;
+pub mod rollback;
 
 pub use controller::Compiler;
\ No newline at end of file
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::actor::ActorOperation;
+use crate::actor::controller::{ActorOffset, SpawnInfo};
+use crate::background::controller::BackgroundOperation;
+use crate::chat::controller::{GuiChangeTarget, GuiImageMode, PresentationMode};
+
+/* Messages */
+/// Message used to instruct every controller to rebuild the stage from a [StageState],
+/// skipping any transition or animation.
+#[derive(Message)]
+pub(crate) struct StageRestoreMessage(pub StageState);
+
+/* Custom Types */
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) enum BackgroundState {
+    /// No background operation has been run yet
+    #[default]
+    Unset,
+    Image(String),
+    /// The background has been dissolved or slid out of the screen
+    Cleared,
+}
+
+/// Line accumulated on the NVL page, narration has no speaker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct PageLine {
+    pub name: Option<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ActorState {
+    pub name: String,
+    pub info: SpawnInfo,
+}
+
+/// Logical description of what is on screen, kept up to date as stage commands are invoked.
+/// Transitions are recorded by their final result.
+/// Fields missing from older saves read as their default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct StageState {
+    pub background: BackgroundState,
+    pub textbox: Option<(String, GuiImageMode)>,
+    pub namebox: Option<String>,
+    /// Actors on stage, in spawn order
+    pub actors: Vec<ActorState>,
+    pub mode: PresentationMode,
+    /// Lines on the NVL page, the newest one last
+    pub page: Vec<PageLine>,
+    /// Characters speaking in bubbles instead of the textbox
+    pub bubbles: Vec<String>,
+    /// Skin of the speech bubbles
+    pub bubble: Option<(String, GuiImageMode)>,
+    pub theme: Option<String>,
+    /// Auto-focus set by the scene, the player's setting is used when unset
+    pub focus: Option<bool>,
+    /// Outfit and emotion set by the script for each character, on stage or not.
+    /// Characters left out wear what their config file sets
+    pub outfits: HashMap<String, String>,
+    pub emotions: HashMap<String, String>,
+}
+
+impl StageState {
+    pub(crate) fn record_background(&mut self, operation: &BackgroundOperation) {
+        self.background = match operation {
+            BackgroundOperation::ChangeTo(target) => BackgroundState::Image(target.clone()),
+            BackgroundOperation::DissolveTo(Some(target)) => BackgroundState::Image(target.clone()),
+            BackgroundOperation::DissolveTo(None) | BackgroundOperation::SlideTo(_) => BackgroundState::Cleared,
+        };
+    }
+
+    pub(crate) fn record_gui(&mut self, target: &GuiChangeTarget, sprite_id: &str, image_mode: &GuiImageMode) {
+        match target {
+            GuiChangeTarget::TextBoxBackground => self.textbox = Some((sprite_id.to_owned(), image_mode.clone())),
+            GuiChangeTarget::NameBoxBackground => self.namebox = Some(sprite_id.to_owned()),
+            GuiChangeTarget::BubbleBackground => self.bubble = Some((sprite_id.to_owned(), image_mode.clone())),
+            GuiChangeTarget::Theme => self.theme = Some(sprite_id.to_owned()),
+        }
+    }
+
+    /// Switching modes always starts from an empty page.
+    pub(crate) fn record_mode(&mut self, mode: PresentationMode) {
+        self.mode = mode;
+        self.page.clear();
+    }
+
+    /// Lines only accumulate while in NVL mode.
+    pub(crate) fn record_line(&mut self, name: Option<&str>, text: &str) {
+        if self.mode == PresentationMode::Nvl {
+            self.page.push(PageLine { name: name.map(str::to_owned), text: text.to_owned() });
+        }
+    }
+
+    pub(crate) fn record_speech(&mut self, character: &str, bubbles: bool) {
+        self.bubbles.retain(|c| c != character);
+        if bubbles {
+            self.bubbles.push(character.to_owned());
+        }
+    }
+
+    pub(crate) fn record_actor(&mut self, name: &str, operation: &ActorOperation) {
+        if let ActorOperation::Spawn(info) = operation {
+            if let Some(outfit) = &info.outfit {
+                self.outfits.insert(name.to_owned(), outfit.clone());
+            }
+            if let Some(emotion) = &info.emotion {
+                self.emotions.insert(name.to_owned(), emotion.clone());
+            }
+            self.actors.retain(|a| a.name != name);
+            self.actors.push(ActorState {
+                name: name.to_owned(),
+                info: SpawnInfo { fading: false, ..info.clone() },
+            });
+            return;
+        }
+
+        if let ActorOperation::Despawn(_) = operation {
+            self.actors.retain(|a| a.name != name);
+            return;
+        }
+
+        // An outfit changed off stage is worn the next time the character appears
+        if let ActorOperation::OutfitChange(outfit) = operation {
+            self.outfits.insert(name.to_owned(), outfit.clone());
+        }
+
+        if let ActorOperation::Reorder(target) = operation {
+            let others = self.actors.iter()
+                .filter(|a| a.name != name)
+                .map(|a| (a.name.as_str(), a.info.depth));
+            // Invalid targets fail on stage too, the actor keeps its depth
+            let Ok(depth) = target.resolve(others) else {
+                return;
+            };
+            if let Some(actor) = self.actors.iter_mut().find(|a| a.name == name) {
+                actor.info.depth = depth;
+            }
+            return;
+        }
+
+        let Some(actor) = self.actors.iter_mut().find(|a| a.name == name) else {
+            return;
+        };
+        match operation {
+            ActorOperation::EmotionChange(emotion, _) => {
+                self.emotions.insert(name.to_owned(), emotion.clone());
+                actor.info.emotion = Some(emotion.clone());
+            },
+            ActorOperation::OutfitChange(outfit) => actor.info.outfit = Some(outfit.clone()),
+            ActorOperation::Look(direction) => actor.info.direction = direction.clone(),
+            ActorOperation::Move(position, _) => actor.info.position = Some(position.clone()),
+            ActorOperation::MoveBy(offset, _) => {
+                if let ActorOffset::Character(_, y) = offset {
+                    actor.info.elevation += y;
+                }
+                actor.info.position = Some(offset.moved_from(actor.info.position.take()));
+            },
+            ActorOperation::Zoom(zoom, _) => actor.info.zoom = Some(*zoom),
+            ActorOperation::LayerChange(layer, change) => actor.info.layers.entry(layer.clone()).or_default().apply(change),
+            ActorOperation::Spawn(_) | ActorOperation::Despawn(_) | ActorOperation::Reorder(_) => {}
+        }
+    }
+}
+
+/// State captured when a blocking statement is reached, used to roll the runtime back to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    pub act: String,
+    pub scene: String,
+    /// Cursor position of the blocking statement
+    pub position: i32,
+    /// Length of the history before the blocking statement was pushed
+    pub history_len: usize,
+    pub is_dialogue: bool,
+    pub variables: HashMap<String, String>,
+    pub stage: StageState,
+}
+
+impl Snapshot {
+    /// Whether both snapshots were taken at the same statement with the same variables.
+    pub(crate) fn same_point(&self, other: &Snapshot) -> bool {
+        self.act == other.act
+            && self.scene == other.scene
+            && self.position == other.position
+            && self.variables == other.variables
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::controller::{ActorDepth, ActorDirection, ActorOffset, ActorPosition, CharacterPosition, DepthTarget, LayerChange, Tween};
+
+    fn spawn(state: &mut StageState, name: &str, info: SpawnInfo) {
+        state.record_actor(name, &ActorOperation::Spawn(info));
+    }
+
+    fn actor<'a>(state: &'a StageState, name: &str) -> &'a SpawnInfo {
+        &state.actors.iter().find(|a| a.name == name).expect("actor is on stage").info
+    }
+
+    #[test]
+    fn spawn_replaces_the_previous_entry() {
+        let mut state = StageState::default();
+        spawn(&mut state, "Nayu", SpawnInfo { emotion: Some("happy".into()), fading: true, ..default() });
+        spawn(&mut state, "Kiyomi", SpawnInfo::default());
+        spawn(&mut state, "Nayu", SpawnInfo { emotion: Some("sad".into()), ..default() });
+
+        let names: Vec<&str> = state.actors.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["Kiyomi", "Nayu"]);
+        assert_eq!(actor(&state, "Nayu").emotion.as_deref(), Some("sad"));
+        assert!(!actor(&state, "Nayu").fading);
+        assert_eq!(state.emotions["Nayu"], "sad");
+    }
+
+    #[test]
+    fn despawn_removes_the_actor() {
+        let mut state = StageState::default();
+        spawn(&mut state, "Nayu", SpawnInfo::default());
+        state.record_actor("Nayu", &ActorOperation::Despawn(None));
+
+        assert!(state.actors.is_empty());
+    }
+
+    #[test]
+    fn changes_apply_to_the_actor_on_stage() {
+        let mut state = StageState::default();
+        spawn(&mut state, "Nayu", SpawnInfo::default());
+        state.record_actor("Nayu", &ActorOperation::EmotionChange("happy".into(), None));
+        state.record_actor("Nayu", &ActorOperation::Look(ActorDirection::Left));
+        state.record_actor("Nayu", &ActorOperation::Move(ActorPosition::Character(CharacterPosition::Left), Tween::default()));
+        state.record_actor("Nayu", &ActorOperation::OutfitChange("casual".into()));
+        state.record_actor("Nayu", &ActorOperation::LayerChange("blush".into(), LayerChange::Show));
+
+        let info = actor(&state, "Nayu");
+        assert_eq!(info.emotion.as_deref(), Some("happy"));
+        assert_eq!(info.outfit.as_deref(), Some("casual"));
+        assert_eq!(info.direction, ActorDirection::Left);
+        assert_eq!(info.position, Some(ActorPosition::Character(CharacterPosition::Left)));
+        assert!(info.layers["blush"].visible);
+        assert_eq!(state.emotions["Nayu"], "happy");
+        assert_eq!(state.outfits["Nayu"], "casual");
+    }
+
+    #[test]
+    fn outfit_is_kept_off_stage() {
+        let mut state = StageState::default();
+        state.record_actor("Nayu", &ActorOperation::OutfitChange("casual".into()));
+        state.record_actor("Nayu", &ActorOperation::EmotionChange("happy".into(), None));
+
+        assert!(state.actors.is_empty());
+        assert_eq!(state.outfits["Nayu"], "casual");
+        assert!(!state.emotions.contains_key("Nayu"));
+    }
+
+    #[test]
+    fn move_by_offsets_from_the_current_position() {
+        let mut state = StageState::default();
+        spawn(&mut state, "Nayu", SpawnInfo { position: Some(ActorPosition::Character(CharacterPosition::Left)), ..default() });
+        state.record_actor("Nayu", &ActorOperation::MoveBy(ActorOffset::Character(10., 0.), Tween::default()));
+        state.record_actor("Nayu", &ActorOperation::MoveBy(ActorOffset::Character(-4., 0.), Tween::default()));
+
+        assert_eq!(actor(&state, "Nayu").position, Some(ActorPosition::Character(CharacterPosition::Offset(Box::new(CharacterPosition::Left), 6.))));
+    }
+
+    #[test]
+    fn vertical_moves_and_zoom_are_kept() {
+        let mut state = StageState::default();
+        spawn(&mut state, "Nayu", SpawnInfo::default());
+        state.record_actor("Nayu", &ActorOperation::MoveBy(ActorOffset::Character(0., 5.), Tween::default()));
+        state.record_actor("Nayu", &ActorOperation::MoveBy(ActorOffset::Character(0., -2.), Tween::default()));
+        state.record_actor("Nayu", &ActorOperation::Zoom(1.3, Tween::default()));
+
+        let info = actor(&state, "Nayu");
+        assert_eq!(info.elevation, 3.);
+        assert_eq!(info.zoom, Some(1.3));
+    }
+
+    #[test]
+    fn reorder_resolves_against_the_other_actors() {
+        let mut state = StageState::default();
+        spawn(&mut state, "Nayu", SpawnInfo { depth: ActorDepth::Layer(2), ..default() });
+        spawn(&mut state, "Kiyomi", SpawnInfo::default());
+        state.record_actor("Kiyomi", &ActorOperation::Reorder(DepthTarget::InFrontOf("Nayu".into())));
+
+        assert_eq!(actor(&state, "Kiyomi").depth, ActorDepth::Layer(3));
+    }
+
+    #[test]
+    fn reorder_against_a_missing_actor_keeps_the_depth() {
+        let mut state = StageState::default();
+        spawn(&mut state, "Kiyomi", SpawnInfo { depth: ActorDepth::Layer(1), ..default() });
+        state.record_actor("Kiyomi", &ActorOperation::Reorder(DepthTarget::Behind("Nayu".into())));
+
+        assert_eq!(actor(&state, "Kiyomi").depth, ActorDepth::Layer(1));
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use bevy::{input::mouse::{MouseScrollUnit, MouseWheel}, prelude::*};
+
+use crate::compiler::controller::SabiState;
+
+/* Messages */
+/// Message sent every time one of the bindings of an [InputAction] is pressed.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct InputActionMessage(pub InputAction);
+
+/* Custom Types */
+/// Actions the player can trigger while a script is running.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum InputAction {
+    /// Completes the current line or moves to the next one
+    Advance,
+    Rewind,
+    RollForward,
+    /// Opens or closes the history panel
+    History,
+    HideUi,
+    /// Toggles skip mode
+    Skip,
+    /// Toggles auto-advance mode
+    Auto,
+    QuickSave,
+    QuickLoad,
+    /// Opens or closes the system menu, pausing the script
+    Pause,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+    Gamepad(GamepadButton),
+}
+
+/// Pixels scrolled by touchpads and smooth-scrolling wheels that count as one wheel notch
+const WHEEL_PIXELS_PER_NOTCH: f32 = 100.;
+
+/// Wheel scroll not yet adding up to a full notch, positive upwards.
+#[derive(Debug, Default)]
+pub(crate) struct WheelAccumulator(f32);
+
+impl WheelAccumulator {
+    /// Adds a scroll measured in notches and returns the full notches crossed, positive upwards.
+    /// Scrolling the other way drops what was left of the previous direction.
+    fn scroll(&mut self, notches: f32) -> i32 {
+        if self.0 * notches < 0. {
+            self.0 = 0.;
+        }
+        self.0 += notches;
+        let crossed = self.0.trunc();
+        self.0 -= crossed;
+        crossed as i32
+    }
+}
+
+/* Resources */
+/// Bindings for every [InputAction]. Host games can rebind actions by mutating this resource.
+#[derive(Resource, Debug, Clone)]
+pub struct InputMap(HashMap<InputAction, Vec<InputBinding>>);
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use InputBinding::*;
+        Self(HashMap::from([
+            (InputAction::Advance,     vec![Key(KeyCode::Space), Key(KeyCode::Enter), Key(KeyCode::NumpadEnter), Gamepad(GamepadButton::South)]),
+            (InputAction::Rewind,      vec![WheelUp, Key(KeyCode::PageUp), Gamepad(GamepadButton::LeftTrigger)]),
+            (InputAction::RollForward, vec![WheelDown, Key(KeyCode::PageDown), Gamepad(GamepadButton::RightTrigger)]),
+            (InputAction::History,     vec![Key(KeyCode::KeyL), Gamepad(GamepadButton::North)]),
+            (InputAction::HideUi,      vec![Key(KeyCode::KeyH), Mouse(MouseButton::Right), Gamepad(GamepadButton::West)]),
+            (InputAction::Skip,        vec![Key(KeyCode::Tab), Gamepad(GamepadButton::RightTrigger2)]),
+            (InputAction::Auto,        vec![Key(KeyCode::KeyA), Gamepad(GamepadButton::LeftTrigger2)]),
+            (InputAction::QuickSave,   vec![Key(KeyCode::F5)]),
+            (InputAction::QuickLoad,   vec![Key(KeyCode::F9)]),
+            (InputAction::Pause,       vec![Key(KeyCode::Escape), Gamepad(GamepadButton::Start)]),
+        ]))
+    }
+}
+
+impl InputMap {
+    /// Adds a binding to an action, keeping the existing ones.
+    pub fn bind(&mut self, action: InputAction, binding: InputBinding) -> &mut Self {
+        let bindings = self.0.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        self
+    }
+
+    /// Replaces every binding of an action.
+    pub fn rebind(&mut self, action: InputAction, bindings: Vec<InputBinding>) -> &mut Self {
+        self.0.insert(action, bindings);
+        self
+    }
+
+    pub fn unbind(&mut self, action: InputAction, binding: InputBinding) -> &mut Self {
+        if let Some(bindings) = self.0.get_mut(&action) {
+            bindings.retain(|b| *b != binding);
+        }
+        self
+    }
+
+    pub fn bindings(&self, action: InputAction) -> &[InputBinding] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+pub(crate) struct InputController;
+impl Plugin for InputController {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputMap>()
+            .add_message::<InputActionMessage>()
+            .add_systems(Update, read_input_actions.run_if(in_state(SabiState::Running)));
+    }
+}
+/// Translates pressed bindings into [InputActionMessage]s
+pub(crate) fn read_input_actions(
+    input_map: Res<InputMap>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut wheel_messages: MessageReader<MouseWheel>,
+    mut wheel_accumulator: Local<WheelAccumulator>,
+    gamepads: Query<&Gamepad>,
+    mut action_writer: MessageWriter<InputActionMessage>,
+) {
+    let mut notches = 0;
+    for wheel in wheel_messages.read() {
+        notches += wheel_accumulator.scroll(match wheel.unit {
+            MouseScrollUnit::Line => wheel.y,
+            MouseScrollUnit::Pixel => wheel.y / WHEEL_PIXELS_PER_NOTCH,
+        });
+    }
+    // Wheel actions fire at most once a frame, like the other bindings
+    let (wheel_up, wheel_down) = (notches > 0, notches < 0);
+
+    for (action, bindings) in &input_map.0 {
+        let triggered = bindings.iter().any(|binding| match binding {
+            InputBinding::Key(key) => keys.just_pressed(*key),
+            InputBinding::Mouse(button) => mouse_buttons.just_pressed(*button),
+            InputBinding::WheelUp => wheel_up,
+            InputBinding::WheelDown => wheel_down,
+            InputBinding::Gamepad(button) => gamepads.iter().any(|gamepad| gamepad.just_pressed(*button)),
+        });
+        if triggered {
+            action_writer.write(InputActionMessage(*action));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_adds_a_binding_once() {
+        let mut input_map = InputMap::default();
+        input_map.bind(InputAction::Advance, InputBinding::Mouse(MouseButton::Left))
+            .bind(InputAction::Advance, InputBinding::Key(KeyCode::Space));
+
+        assert_eq!(input_map.bindings(InputAction::Advance), [
+            InputBinding::Key(KeyCode::Space),
+            InputBinding::Key(KeyCode::Enter),
+            InputBinding::Key(KeyCode::NumpadEnter),
+            InputBinding::Gamepad(GamepadButton::South),
+            InputBinding::Mouse(MouseButton::Left),
+        ]);
+    }
+
+    #[test]
+    fn rebind_replaces_every_binding() {
+        let mut input_map = InputMap::default();
+        input_map.rebind(InputAction::QuickSave, vec![InputBinding::Key(KeyCode::F6), InputBinding::Gamepad(GamepadButton::Select)]);
+
+        assert_eq!(input_map.bindings(InputAction::QuickSave), [InputBinding::Key(KeyCode::F6), InputBinding::Gamepad(GamepadButton::Select)]);
+    }
+
+    #[test]
+    fn unbind_removes_only_the_given_binding() {
+        let mut input_map = InputMap::default();
+        input_map.unbind(InputAction::Rewind, InputBinding::WheelUp)
+            .unbind(InputAction::Rewind, InputBinding::WheelDown);
+
+        assert_eq!(input_map.bindings(InputAction::Rewind), [InputBinding::Key(KeyCode::PageUp), InputBinding::Gamepad(GamepadButton::LeftTrigger)]);
+    }
+
+    #[test]
+    fn wheel_fires_once_per_notch() {
+        let mut wheel = WheelAccumulator::default();
+        assert_eq!(wheel.scroll(1.), 1);
+        assert_eq!(wheel.scroll(-2.), -2);
+
+        // Pixel scrolls add up until they reach a notch
+        let pixels = 40. / WHEEL_PIXELS_PER_NOTCH;
+        assert_eq!(wheel.scroll(pixels), 0);
+        assert_eq!(wheel.scroll(pixels), 0);
+        assert_eq!(wheel.scroll(pixels), 1);
+
+        // Turning back drops the leftover scroll of the other direction
+        assert_eq!(wheel.scroll(-pixels), 0);
+        assert_eq!(wheel.scroll(-pixels), 0);
+        assert_eq!(wheel.scroll(-pixels), -1);
+    }
+
+    #[test]
+    fn actions_without_bindings_have_none() {
+        let mut input_map = InputMap::default();
+        input_map.rebind(InputAction::Skip, Vec::new());
+        input_map.unbind(InputAction::Skip, InputBinding::Key(KeyCode::Tab));
+
+        assert!(input_map.bindings(InputAction::Skip).is_empty());
+    }
+}
@@ -0,0 +1,7 @@
+pub(crate) mod controller;
+
+pub(crate) use controller::InputController;
+pub use controller::InputAction;
+pub use controller::InputActionMessage;
+pub use controller::InputBinding;
+pub use controller::InputMap;
@@ -2,36 +2,37 @@ mod background;
 mod actor;
 mod chat;
 mod compiler;
+mod input;
 mod loader;
+mod save;
+mod settings;
+mod title;
 
 use crate::background::*;
 use crate::actor::controller::ActorConfig;
 use crate::actor::controller::AnimationConfig;
+use crate::actor::controller::PositionsConfig;
 use crate::actor::*;
 use crate::chat::*;
-use crate::compiler::ast::Evaluate;
-use crate::compiler::ast::Statement;
-use crate::compiler::ast::TextItem;
+use crate::chat::controller::{ChatLayout, GuiSlicing, GuiTheme};
+use crate::compiler::rollback::{Snapshot, StageState};
 use crate::compiler::*;
-use crate::loader::ActorJsonLoader;
+use crate::input::InputController;
+use crate::loader::JsonLoader;
 use crate::loader::PestLoader;
+use crate::save::{SaveController, SaveData};
+use crate::settings::SettingsController;
+use crate::title::TitleController;
 
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
 use bevy::prelude::*;
 use bevy::ecs::error::ErrorContext;
+use serde::{Deserialize, Serialize};
 
-pub(crate) trait VariantKind {
-    fn kind(&self) -> usize;
-}
-
-impl VariantKind for ast::Statement {
-    fn kind(&self) -> usize {
-        match self {
-            Statement::TextItem(_) => 1,
-            Statement::Stage(_)    => 2,
-            Statement::Code(_)     => 3,
-        }
-    }
-}
+pub use crate::input::{InputAction, InputActionMessage, InputBinding, InputMap};
+pub use crate::settings::{AudioChannel, Settings};
+pub use crate::title::{GalleryEntry, TitleEntry, TitleLayout, TitleScreen};
 
 #[derive(Default)]
 pub(crate) struct Cursor<T> {
@@ -61,37 +62,19 @@ impl<T> Cursor<T> {
         self.data.get(self.pos as usize).cloned()
     }
 
-    pub(crate) fn prev(&mut self) -> Option<T>
-    where
-        T: Clone
-    {
-        if self.pos == 0 { return None; }
-        self.pos -= 1;
-        self.data.get(self.pos as usize).cloned()
+    pub(crate) fn position(&self) -> i32 {
+        self.pos
     }
 
-    pub(crate) fn find_previous(&self) -> Option<T>
-    where
-        T: Clone + VariantKind
-    {
-        if let Some(item) = self.data.get(self.pos as usize) {
-            let current_kind = item.kind();
-            let mut idx: i32 = self.pos as i32 - 1;
-            while idx >= 0 {
-                if let Some(back_item) = self.data.get(idx as usize) {
-                    if back_item.kind() == current_kind {
-                        return Some(back_item.clone());
-                    }
-                } else { return None; }
-                idx -= 1;
-            }
-            None
-        } else {
-            None
-        }
+    /// Moves the cursor so that the next call to [Cursor::next] returns the item at `position`.
+    pub(crate) fn seek(&mut self, position: i32) {
+        self.pos = position - 1;
     }
 }
 
+/// Blocking statements the player can rewind to, the oldest snapshots are dropped past it
+const SNAPSHOT_LIMIT: usize = 500;
+
 /// Resource containing main [Act] state and related runtime data for the Visual Novel.
 /// Player-designated constants are passe by the [UserDefinedConstants] resource.
 #[derive(Resource, Default)]
@@ -103,87 +86,245 @@ pub(crate) struct VisualNovelState {
     pub scene: Box<ast::Scene>,
     pub statements: Cursor<ast::Statement>,
     blocking: bool,
-    pub rewinding: usize,
     pub history: Vec<HistoryItem>,
+    pub stage: StageState,
+    pub snapshots: Vec<Snapshot>,
+    /// Snapshot the runtime has to roll back to before running the next statement
+    pub rewinding: Option<Snapshot>,
+    /// Snapshots left behind by rewinding, the nearest one on top
+    pub roll_forward: Vec<Snapshot>,
+    /// History items left behind by rewinding, in the order they were first reached
+    pub history_stash: VecDeque<HistoryItem>,
+    pub variables: HashMap<String, String>,
+    /// Set while a single scene is being replayed, see [SceneReplay].
+    pub replay: Option<SceneReplay>,
+    /// Variables and history of the player, put aside while a scene is replayed
+    pub replay_stash: Option<(HashMap<String, String>, Vec<HistoryItem>)>,
+    /// Text statements reached at least once, by act, scene and cursor position
+    read_lines: HashSet<(String, String, i32)>,
+    /// Whether the line currently on screen had already been read before
+    pub current_line_read: bool,
 }
 
 pub(crate) enum HistoryItem {
-    Statement(ast::Statement),
     Descriptor(String),
+    /// Text lines as they were shown, resolved with the variables of that moment
+    Lines(Vec<String>),
 }
 
 impl VisualNovelState {
     pub fn set_rewind(&mut self) {
-        let search_slice = &self.history[..self.history.len() - 1];
-        let last_d = search_slice.iter().rposition(|s| {
-            if let HistoryItem::Statement(stm) = s {
-                matches!(stm, Statement::TextItem(TextItem::Dialogue(_)))
-            } else {
-                false
-            }
-        });
-        if let Some(index) = last_d {
-            self.rewinding = self.history.len() - (index + 1);
+        // The last snapshot belongs to the line currently on screen
+        let Some((_, previous)) = self.snapshots.split_last() else { return; };
+        if let Some(index) = previous.iter().rposition(|s| s.is_dialogue) {
+            let mut removed = self.snapshots.split_off(index);
+            // Nearest snapshots end up on top of the roll-forward stack
+            self.roll_forward.extend(removed.drain(1..).rev());
+            self.rewinding = removed.pop();
             self.blocking = false;
         }
     }
 
-    pub fn history_summary(&self) -> Result<Vec<String>> {
+    pub fn set_roll_forward(&mut self) {
+        let Some(index) = self.roll_forward.iter().rposition(|s| s.is_dialogue) else { return; };
+        let mut skipped = self.roll_forward.split_off(index);
+        let target = skipped.remove(0);
+        // Statements skipped over are pushed back in the order they were first reached
+        self.snapshots.extend(skipped.into_iter().rev());
+        self.rewinding = Some(target);
+        self.blocking = false;
+    }
+
+    pub fn push_history(&mut self, item: HistoryItem) {
+        // The same item is reached again while rolling forward
+        self.history_stash.pop_front();
+        self.history.push(item);
+    }
+
+    /// Moves history items between the history and its stash until `len` items are left in the history.
+    pub fn restore_history(&mut self, len: usize) {
+        if len <= self.history.len() {
+            for item in self.history.split_off(len).into_iter().rev() {
+                self.history_stash.push_front(item);
+            }
+        } else {
+            let count = (len - self.history.len()).min(self.history_stash.len());
+            self.history.extend(self.history_stash.drain(..count));
+        }
+    }
+
+    /// Captures the current runtime state for the blocking statement the cursor points at.
+    pub fn push_snapshot(&mut self, is_dialogue: bool) {
+        let snapshot = Snapshot {
+            act: self.act.name.clone(),
+            scene: self.scene.name.clone(),
+            position: self.statements.position(),
+            history_len: self.history.len(),
+            is_dialogue,
+            variables: self.variables.clone(),
+            stage: self.stage.clone(),
+        };
+        let line = (snapshot.act.clone(), snapshot.scene.clone(), snapshot.position);
+        // Lines seen in a gallery replay are not marked as read by the player
+        self.current_line_read = if self.replay.is_some() {
+            self.read_lines.contains(&line)
+        } else {
+            !self.read_lines.insert(line)
+        };
+
+        // Reaching the next rolled back point keeps the roll-forward stack,
+        //  anything else means the player took a different path
+        match self.roll_forward.last() {
+            Some(next) if next.same_point(&snapshot) => { self.roll_forward.pop(); },
+            Some(_) => {
+                self.roll_forward.clear();
+                self.history_stash.clear();
+            },
+            None => {}
+        }
+        self.snapshots.push(snapshot);
+        if self.snapshots.len() > SNAPSHOT_LIMIT {
+            let excess = self.snapshots.len() - SNAPSHOT_LIMIT;
+            self.snapshots.drain(..excess);
+        }
+    }
+
+    pub fn history_summary(&self) -> Vec<String> {
+        self.summarize(&self.history)
+    }
+
+    /// Text lines of the given history items.
+    pub(crate) fn summarize(&self, items: &[HistoryItem]) -> Vec<String> {
         let mut text: Vec<String> = Vec::new();
 
-        for statement in &self.history {
-            match statement {
-                HistoryItem::Statement(s) => {
-                    if let Statement::TextItem(t) = s {
-                        match t {
-                            TextItem::Dialogue(d) => {
-                                text.push(d.character.clone() + format!(": {}\n", d.dialogue.evaluate_into_string()?).as_str());
-                            },
-                            TextItem::InfoText(i) => {
-                                text.push(i.infotext.evaluate_into_string()? + "\n");
-                            }
-                        }
-                    }
-                }
+        for item in items {
+            match item {
                 HistoryItem::Descriptor(s) => {
                     text.push(s.clone() + "\n");
+                },
+                HistoryItem::Lines(lines) => {
+                    text.extend(lines.iter().map(|line| line.clone() + "\n"));
                 }
             }
         }
 
-        Ok(text)
+        text
     }
 }
 
 #[derive(Resource, Default)]
 pub struct UserDefinedConstants {
     pub playername: String,
+    /// Languages the player can pick from in the settings panel
+    pub languages: Vec<String>,
+    /// Folder the player's settings and saves are written to, the per-user data folder named after the game executable when unset.
+    /// It is read on startup, so insert this resource before adding the [SabiPlugin] to set it.
+    pub data_dir: Option<PathBuf>,
+    /// Theme from the GUI folder skinning the title screen, and games until their script changes it.
+    /// The widgets keep the look they are built with when unset.
+    pub gui_theme: Option<String>,
+}
+
+impl UserDefinedConstants {
+    pub(crate) fn data_dir(&self) -> PathBuf {
+        self.data_dir.clone().unwrap_or_else(default_data_dir)
+    }
+}
+
+/// Per-user data folder of the platform, named after the game executable, or the working directory if there is none
+fn default_data_dir() -> PathBuf {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+    let game = std::env::current_exe().ok()
+        .and_then(|exe| exe.file_stem().map(|stem| stem.to_owned()))
+        .unwrap_or_else(|| "sabi".into());
+    base.map(|base| base.join(game)).unwrap_or_else(|| PathBuf::from("."))
 }
 
 fn sabi_error_handler ( err: BevyError, ctx: ErrorContext ) {
     panic!("Bevy error: {err:?}\nContext: {ctx:?}")
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ScriptId {
     pub chapter: String,
     pub act: String,
 }
 
+/// Scene replayed by a [SabiStart], a single [ast::Scene] of the started [ast::Act].
+/// The scene runs in an isolated state seeded with `variables`, and the runtime
+/// returns to the caller with [SabiEnd] when its `CURTAIN` is reached.
+#[derive(Debug, Clone, Default)]
+pub struct SceneReplay {
+    pub scene: String,
+    pub variables: HashMap<String, String>,
+}
+
+impl SceneReplay {
+    pub fn new(scene: impl Into<String>) -> Self {
+        Self { scene: scene.into(), ..default() }
+    }
+
+    pub fn with_variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+}
+
+/// Starts a script from the entrypoint of its act, or replays a single scene of it.
+#[derive(Message)]
+pub struct SabiStart {
+    pub script_id: ScriptId,
+    /// Scene run instead of the act's entrypoint, the player's variables and history are put back once it ends
+    pub replay: Option<SceneReplay>,
+}
+
+impl SabiStart {
+    pub fn new(script_id: ScriptId) -> Self {
+        Self { script_id, replay: None }
+    }
+
+    pub fn replay(mut self, replay: SceneReplay) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+}
+
+/// Starts a script from the line a save was taken at instead of its entrypoint
 #[derive(Message)]
-pub struct SabiStart(pub ScriptId);
+pub(crate) struct SabiResume(pub SaveData);
 #[derive(Message)]
 pub struct SabiEnd;
 
+/// Registers the JSON assets, each read by the loader of its extension.
+/// Bevy picks the loader of the longest matching extension, so only files matching no other one are read as an [ActorConfig].
+pub(crate) fn init_json_assets(app: &mut App) -> &mut App {
+    app.init_asset::<ActorConfig>()
+        .init_asset::<CharacterConfig>()
+        .init_asset::<AnimationConfig>()
+        .register_asset_loader(JsonLoader::<ActorConfig>::new(&["json"]))
+        .init_asset::<PositionsConfig>()
+        .register_asset_loader(JsonLoader::<PositionsConfig>::new(&["positions.json"]))
+        .init_asset::<GuiSlicing>()
+        .register_asset_loader(JsonLoader::<GuiSlicing>::new(&["slice.json"]))
+        .init_asset::<GuiTheme>()
+        .register_asset_loader(JsonLoader::<GuiTheme>::new(&["theme.json"]))
+        .init_asset::<ChatLayout>()
+        .register_asset_loader(JsonLoader::<ChatLayout>::new(&["layout.json"]))
+}
+
 pub struct SabiPlugin;
 impl Plugin for SabiPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<UserDefinedConstants>()
+        init_json_assets(app)
+            .init_resource::<UserDefinedConstants>()
             .init_resource::<VisualNovelState>()
-            .init_asset::<ActorConfig>()
-            .init_asset::<CharacterConfig>()
-            .init_asset::<AnimationConfig>()
-            .init_asset_loader::<ActorJsonLoader>()
             .init_asset::<ast::Act>()
             .init_asset_loader::<PestLoader>()
             .set_error_handler(sabi_error_handler)
@@ -191,7 +332,166 @@ impl Plugin for SabiPlugin {
                 Compiler,
                 BackgroundController,
                 CharacterController,
-                ChatController
+                ChatController,
+                InputController,
+                SaveController,
+                SettingsController,
+                TitleController
             ));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the blocking statement at `position` the way the runtime does, snapshot first
+    fn reach(state: &mut VisualNovelState, position: i32, is_dialogue: bool) {
+        state.statements.pos = position;
+        state.push_snapshot(is_dialogue);
+        state.push_history(HistoryItem::Descriptor(format!("line {}", position)));
+    }
+
+    /// Rolls back to the snapshot set by a rewind or a roll-forward and runs its statement again, see `handle_rewind`
+    fn restore(state: &mut VisualNovelState) {
+        let snapshot = state.rewinding.take().expect("a snapshot to restore");
+        state.restore_history(snapshot.history_len);
+        state.roll_forward.push(snapshot.clone());
+        reach(state, snapshot.position, snapshot.is_dialogue);
+    }
+
+    fn lines(items: &[HistoryItem]) -> Vec<String> {
+        items.iter().map(|item| match item {
+            HistoryItem::Descriptor(text) => text.clone(),
+            HistoryItem::Lines(lines) => lines.join("\n"),
+        }).collect()
+    }
+
+    fn positions(snapshots: &[Snapshot]) -> Vec<i32> {
+        snapshots.iter().map(|s| s.position).collect()
+    }
+
+    #[test]
+    fn replayed_lines_are_not_marked_read() {
+        let mut state = VisualNovelState::default();
+        reach(&mut state, 0, true);
+        state.replay = Some(SceneReplay::default());
+        reach(&mut state, 0, true);
+        assert!(state.current_line_read);
+        reach(&mut state, 1, true);
+        assert!(!state.current_line_read);
+
+        state.replay = None;
+        reach(&mut state, 1, true);
+        assert!(!state.current_line_read);
+    }
+
+    #[test]
+    fn roll_forward_returns_to_the_line_rewound_from() {
+        let mut state = VisualNovelState::default();
+        for position in 0..3 {
+            reach(&mut state, position, true);
+        }
+
+        state.set_rewind();
+        restore(&mut state);
+        assert_eq!(lines(&state.history), ["line 0", "line 1"]);
+        assert_eq!(positions(&state.snapshots), [0, 1]);
+        assert_eq!(positions(&state.roll_forward), [2]);
+        assert_eq!(lines(state.history_stash.make_contiguous()), ["line 2"]);
+
+        state.set_roll_forward();
+        restore(&mut state);
+        assert_eq!(lines(&state.history), ["line 0", "line 1", "line 2"]);
+        assert_eq!(positions(&state.snapshots), [0, 1, 2]);
+        assert!(state.roll_forward.is_empty());
+        assert!(state.history_stash.is_empty());
+    }
+
+    #[test]
+    fn rewind_skips_statements_that_are_not_dialogue() {
+        let mut state = VisualNovelState::default();
+        reach(&mut state, 0, true);
+        reach(&mut state, 1, false);
+        reach(&mut state, 2, true);
+
+        state.set_rewind();
+        assert_eq!(state.rewinding.as_ref().map(|s| s.position), Some(0));
+        restore(&mut state);
+        // The nearest snapshot is on top of the stack
+        assert_eq!(positions(&state.roll_forward), [2, 1]);
+        assert_eq!(lines(&state.history), ["line 0"]);
+
+        state.set_roll_forward();
+        assert_eq!(state.rewinding.as_ref().map(|s| s.position), Some(2));
+        restore(&mut state);
+        assert_eq!(lines(&state.history), ["line 0", "line 1", "line 2"]);
+        assert_eq!(positions(&state.snapshots), [0, 1, 2]);
+    }
+
+    #[test]
+    fn rewind_stops_at_the_first_line() {
+        let mut state = VisualNovelState::default();
+        reach(&mut state, 0, true);
+
+        state.set_rewind();
+        assert!(state.rewinding.is_none());
+        state.set_roll_forward();
+        assert!(state.rewinding.is_none());
+    }
+
+    #[test]
+    fn taking_another_path_discards_the_roll_forward() {
+        let mut state = VisualNovelState::default();
+        for position in 0..3 {
+            reach(&mut state, position, true);
+        }
+        state.set_rewind();
+        restore(&mut state);
+
+        state.variables.insert(String::from("choice"), String::from("left"));
+        reach(&mut state, 2, true);
+        assert!(state.roll_forward.is_empty());
+        assert!(state.history_stash.is_empty());
+        assert_eq!(lines(&state.history), ["line 0", "line 1", "line 2"]);
+
+        state.set_roll_forward();
+        assert!(state.rewinding.is_none());
+    }
+
+    #[test]
+    fn restore_history_moves_items_both_ways() {
+        let mut state = VisualNovelState::default();
+        for position in 0..4 {
+            state.push_history(HistoryItem::Descriptor(format!("line {}", position)));
+        }
+
+        state.restore_history(2);
+        assert_eq!(lines(&state.history), ["line 0", "line 1"]);
+        assert_eq!(lines(state.history_stash.make_contiguous()), ["line 2", "line 3"]);
+
+        state.restore_history(1);
+        assert_eq!(lines(&state.history), ["line 0"]);
+        assert_eq!(lines(state.history_stash.make_contiguous()), ["line 1", "line 2", "line 3"]);
+
+        state.restore_history(3);
+        assert_eq!(lines(&state.history), ["line 0", "line 1", "line 2"]);
+        assert_eq!(lines(state.history_stash.make_contiguous()), ["line 3"]);
+
+        // Only the stashed items can be brought back
+        state.restore_history(10);
+        assert_eq!(lines(&state.history), ["line 0", "line 1", "line 2", "line 3"]);
+        assert!(state.history_stash.is_empty());
+    }
+
+    #[test]
+    fn summary_keeps_lines_as_they_were_shown() {
+        let mut state = VisualNovelState::default();
+        state.push_history(HistoryItem::Descriptor("Scene: school".into()));
+        state.push_history(HistoryItem::Lines(vec!["Nayu: Hi".into()]));
+        // Variables set or dropped later, e.g. by a rollback, don't change what was said
+        state.variables.clear();
+
+        assert_eq!(state.history_summary(), ["Scene: school\n", "Nayu: Hi\n"]);
+    }
+}
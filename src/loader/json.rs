@@ -1,23 +1,34 @@
-use bevy::asset::AssetLoader;
-use thiserror::Error;
+use std::marker::PhantomData;
 
-use crate::actor::{CharacterConfig, controller::{ActorConfig, AnimationConfig}};
+use bevy::asset::{Asset, AssetLoader};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
 
 #[derive(Debug, Error)]
-pub enum ActorJsonError {
+pub(crate) enum JsonLoaderError {
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
     #[error("JSON parse error: {0}")]
     Serde(#[from] serde_json::Error),
 }
 
-/// Custom asset loader to parse characters configuration.
-#[derive(Default)]
-pub struct ActorJsonLoader;
-impl AssetLoader for ActorJsonLoader {
-    type Asset = ActorConfig;
+/// Asset loader parsing JSON files into `T`, registered once per asset type with the extensions it reads,
+/// e.g. `JsonLoader::<GuiSlicing>::new(&["slice.json"])`.
+pub(crate) struct JsonLoader<T> {
+    extensions: &'static [&'static str],
+    asset: PhantomData<fn() -> T>,
+}
+
+impl<T> JsonLoader<T> {
+    pub(crate) fn new(extensions: &'static [&'static str]) -> Self {
+        JsonLoader { extensions, asset: PhantomData }
+    }
+}
+
+impl<T: Asset + DeserializeOwned> AssetLoader for JsonLoader<T> {
+    type Asset = T;
     type Settings = ();
-    type Error = ActorJsonError;
+    type Error = JsonLoaderError;
 
     fn load(
             &self,
@@ -28,16 +39,61 @@ impl AssetLoader for ActorJsonLoader {
         Box::pin(async move {
             let mut bytes = Vec::new();
             reader.read_to_end(&mut bytes).await?;
-            if let Ok(parsed) = serde_json::from_slice::<CharacterConfig>(&bytes) {
-                Ok(ActorConfig::Character(parsed))
-            } else {
-                let parsed = serde_json::from_slice::<AnimationConfig>(&bytes)?;
-                Ok(ActorConfig::Animation(parsed.clone()))
-            }
+            Ok(serde_json::from_slice::<T>(&bytes)?)
         })
     }
 
     fn extensions(&self) -> &[&str] {
-        &["json"]
+        self.extensions
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::type_name;
+    use bevy::{prelude::*, tasks::block_on};
+    use crate::actor::controller::ActorConfig;
+
+    #[test]
+    fn character_files_are_read_as_characters() {
+        let config: ActorConfig = serde_json::from_str(r#"{
+            "name": "Nayu", "outfit": "uniform", "emotion": "neutral", "description": "",
+            "outfits": ["uniform"], "emotions": ["neutral", "happy"]
+        }"#).unwrap();
+
+        let ActorConfig::Character(character) = config else { panic!("expected a character, found {:?}", config) };
+        assert_eq!(character.name, "Nayu");
+        assert_eq!(character.emotions, ["neutral", "happy"]);
+    }
+
+    #[test]
+    fn other_files_are_read_as_animations() {
+        let config: ActorConfig = serde_json::from_str(r#"{
+            "name": "sparkles", "width": 64, "height": 64, "fps": 12,
+            "rows": 2, "columns": 4, "start_index": 0, "end_index": 7
+        }"#).unwrap();
+
+        let ActorConfig::Animation(animation) = config else { panic!("expected an animation, found {:?}", config) };
+        assert_eq!(animation.name, "sparkles");
+        assert_eq!(animation.end_index, 7);
+    }
+
+    #[test]
+    fn files_describing_neither_are_rejected() {
+        assert!(serde_json::from_str::<ActorConfig>(r#"{ "name": "Nayu" }"#).is_err());
+    }
+
+    #[test]
+    fn actor_configs_are_only_read_from_plain_json_files() {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()));
+        crate::init_json_assets(&mut app);
+        let asset_server = app.world().resource::<AssetServer>();
+        let loaded_as = |path: &'static str| block_on(asset_server.get_path_asset_loader(path)).unwrap().asset_type_name();
+
+        assert_eq!(loaded_as("characters/nayu/character.json"), type_name::<ActorConfig>());
+        for path in ["ui/TEXTBOX.slice.json", "ui/school.theme.json", "ui/chat.layout.json", "characters/stage.positions.json"] {
+            assert_ne!(loaded_as(path), type_name::<ActorConfig>(), "{} is read as an actor config", path);
+        }
+    }
+}
@@ -12,11 +12,18 @@ pub(crate) enum PestLoaderError {
     #[error("Conversion error: {0}")]
     Conversion(#[from] std::string::FromUtf8Error),
     #[error("Parsing error: {0}")]
-    Parse(#[from] pest::error::Error<Rule>),
+    Parse(Box<pest::error::Error<Rule>>),
     #[error("Syntax error: {0}")]
     Syntax(#[from] anyhow::Error)
 }
 
+// Boxed, parsing errors are far larger than the other variants
+impl From<pest::error::Error<Rule>> for PestLoaderError {
+    fn from(error: pest::error::Error<Rule>) -> Self {
+        Self::Parse(Box::new(error))
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct PestLoader;
 impl AssetLoader for PestLoader {
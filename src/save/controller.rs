@@ -0,0 +1,254 @@
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+use anyhow::Context;
+use bevy::{asset::RenderAssetUsages, image::{CompressedImageFormats, ImageSampler, ImageType}, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{ScriptId, UserDefinedConstants, VisualNovelState, compiler::rollback::Snapshot};
+
+/// Directory the save slots are written to, inside [UserDefinedConstants::data_dir]
+const SAVES_DIR: &str = "saves";
+pub(crate) const SLOTS_PER_PAGE: usize = 6;
+pub(crate) const SLOT_PAGES: usize = 5;
+/// Slot written by the quick save binding, past the last page of the slot browser
+pub(crate) const QUICK_SLOT: usize = SLOTS_PER_PAGE * SLOT_PAGES;
+
+/* Custom Types */
+/// Everything needed to resume a script from the line that was on screen when it was saved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SaveData {
+    pub script_id: ScriptId,
+    pub snapshot: Snapshot,
+    /// History text up to the saved line, restored as descriptors
+    pub history: Vec<String>,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub last_line: String,
+    /// Lines read by the player, kept so skip mode still stops at unread text after a load
+    #[serde(default)]
+    pub read_lines: HashSet<(String, String, i32)>,
+}
+
+impl SaveData {
+    /// Captures the line currently on screen, `None` if no line has been reached yet.
+    pub(crate) fn capture(game_state: &VisualNovelState, script_id: &ScriptId) -> Result<Option<Self>, BevyError> {
+        let Some(snapshot) = game_state.snapshots.last() else {
+            return Ok(None);
+        };
+        let history_len = snapshot.history_len.min(game_state.history.len());
+        let history = game_state.summarize(&game_state.history[..history_len])
+            .iter()
+            .map(|line| line.trim_end().to_owned())
+            .collect();
+        let last_line = game_state.summarize(&game_state.history[history_len..])
+            .last()
+            .map(|line| line.trim_end().to_owned())
+            .unwrap_or_default();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .context("System clock is set before the Unix epoch")?
+            .as_secs();
+
+        Ok(Some(Self {
+            script_id: script_id.clone(),
+            snapshot: snapshot.clone(),
+            history,
+            timestamp,
+            last_line,
+            read_lines: game_state.read_lines.clone(),
+        }))
+    }
+
+    pub(crate) fn location(&self) -> String {
+        format!("{} / {} / {}", self.script_id.chapter, self.snapshot.act, self.snapshot.scene)
+    }
+
+    pub(crate) fn date(&self) -> String {
+        format_timestamp(self.timestamp)
+    }
+}
+
+pub(crate) struct SaveSlot {
+    pub data: SaveData,
+    pub thumbnail: Option<Handle<Image>>,
+}
+
+/* Resources */
+/// Capture of the scene taken when the system menu opens, written next to the slot it is saved to
+#[derive(Resource)]
+pub(crate) struct SceneCapture(pub Image);
+
+/// Save slots found on disk, by slot index, and the directory they are kept in.
+#[derive(Resource, Default)]
+pub(crate) struct SaveSlots(pub HashMap<usize, SaveSlot>, PathBuf);
+
+impl SaveSlots {
+    /// Most recently written save, resumed by the title screen's `Continue`.
+    pub(crate) fn latest(&self) -> Option<&SaveData> {
+        self.0.values()
+            .map(|slot| &slot.data)
+            .max_by_key(|data| data.timestamp)
+    }
+
+    /// Whether any slot shown by the slot browser holds a save, the quick save slot is not listed there.
+    pub(crate) fn has_browsable(&self) -> bool {
+        self.0.keys().any(|slot| *slot < QUICK_SLOT)
+    }
+
+    /// Writes a save to disk along with its thumbnail, replacing anything already in the slot.
+    /// A save that can't be written is only lost itself, so failures are logged instead of stopping the game.
+    pub(crate) fn write(&mut self, slot: usize, data: SaveData, capture: Option<&SceneCapture>, images: &mut Assets<Image>) {
+        let path = self.slot_path(slot, "json");
+        let written = serde_json::to_string_pretty(&data)
+            .context("Could not serialize save data")
+            .and_then(|contents| std::fs::create_dir_all(&self.1)
+                .and_then(|_| std::fs::write(&path, contents))
+                .context(format!("Could not write save file '{}'", path.display())));
+        if let Err(e) = written {
+            warn!("Save slot {} was not saved: {:#}", slot, e);
+            return;
+        }
+
+        let thumbnail_path = self.slot_path(slot, "png");
+        let thumbnail = match capture {
+            Some(SceneCapture(image)) => match write_thumbnail(image, &thumbnail_path) {
+                Ok(()) => load_thumbnail(&thumbnail_path, images),
+                Err(e) => {
+                    warn!("Save slot {} was saved without a thumbnail: {:#}", slot, e);
+                    let _ = std::fs::remove_file(&thumbnail_path);
+                    None
+                }
+            },
+            None => {
+                // A thumbnail left behind by the previous save of this slot would not match it anymore
+                let _ = std::fs::remove_file(&thumbnail_path);
+                None
+            }
+        };
+
+        info!("[ Saved to slot {} ]", slot);
+        self.0.insert(slot, SaveSlot { data, thumbnail });
+    }
+
+    /// Deletes a save from disk, a failure is logged and leaves the slot as it was.
+    pub(crate) fn delete(&mut self, slot: usize) {
+        let path = self.slot_path(slot, "json");
+        match std::fs::remove_file(&path) {
+            // Already gone from disk, e.g. removed by hand while the game was running
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+            Err(e) => {
+                warn!("Save slot {} was not deleted, could not remove '{}': {}", slot, path.display(), e);
+                return;
+            },
+            Ok(()) => {},
+        }
+        let _ = std::fs::remove_file(self.slot_path(slot, "png"));
+
+        info!("[ Deleted save slot {} ]", slot);
+        self.0.remove(&slot);
+    }
+
+    fn slot_path(&self, slot: usize, extension: &str) -> PathBuf {
+        self.1.join(format!("slot_{}.{}", slot, extension))
+    }
+}
+
+pub(crate) struct SaveController;
+impl Plugin for SaveController {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveSlots>()
+            .add_systems(Startup, load_slots);
+    }
+}
+fn write_thumbnail(image: &Image, path: &Path) -> anyhow::Result<()> {
+    // The alpha channel is dropped, it holds brightness values when HDR is enabled
+    image.clone().try_into_dynamic()
+        .context("Could not convert the scene capture")?
+        .to_rgb8()
+        .save(path)
+        .context(format!("Could not write thumbnail '{}'", path.display()))
+}
+/// Thumbnails live outside the assets folder, so they are decoded here instead of going through the [AssetServer]
+fn load_thumbnail(path: &Path, images: &mut Assets<Image>) -> Option<Handle<Image>> {
+    let bytes = std::fs::read(path).ok()?;
+    match Image::from_buffer(
+        &bytes,
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        true,
+        ImageSampler::Default,
+        RenderAssetUsages::default(),
+    ) {
+        Ok(image) => Some(images.add(image)),
+        Err(e) => {
+            warn!("Could not decode thumbnail '{}': {}", path.display(), e);
+            None
+        }
+    }
+}
+/// Without a saves directory the game still runs, saving then reports the error when it is attempted
+fn load_slots(
+    mut save_slots: ResMut<SaveSlots>,
+    mut images: ResMut<Assets<Image>>,
+    user_defined_constants: Res<UserDefinedConstants>,
+) {
+    save_slots.1 = user_defined_constants.data_dir().join(SAVES_DIR);
+    if let Err(e) = std::fs::create_dir_all(&save_slots.1) {
+        warn!("Could not create saves directory '{}': {}", save_slots.1.display(), e);
+        return;
+    }
+
+    for slot in 0..=QUICK_SLOT {
+        let path = save_slots.slot_path(slot, "json");
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        // A broken slot is left out instead of taking every other save down with it
+        let data: SaveData = match serde_json::from_str(&contents) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Save slot '{}' is malformed: {}", path.display(), e);
+                continue;
+            }
+        };
+        let thumbnail = load_thumbnail(&save_slots.slot_path(slot, "png"), &mut images);
+        save_slots.0.insert(slot, SaveSlot { data, thumbnail });
+    }
+    info!("{} save slots loaded from {}", save_slots.0.len(), save_slots.1.display());
+}
+/// Formats seconds since the Unix epoch as a `YYYY-MM-DD HH:MM` UTC date.
+fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let minutes = timestamp % 86_400 / 60;
+
+    // Civil date from a day count, after http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_are_formatted_as_utc_dates() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(946_684_740), "1999-12-31 23:59");
+        assert_eq!(format_timestamp(1_735_689_599), "2024-12-31 23:59");
+    }
+
+    #[test]
+    fn leap_days_follow_the_gregorian_rules() {
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00");
+        // 2100 is not a leap year, the day after February 28th is March 1st
+        assert_eq!(format_timestamp(4_107_587_400), "2100-03-01 12:30");
+        assert_eq!(format_timestamp(4_107_587_400 - 86_400), "2100-02-28 12:30");
+    }
+}
@@ -0,0 +1,7 @@
+pub(crate) mod controller;
+
+pub(crate) use controller::SaveController;
+pub(crate) use controller::SaveData;
+pub(crate) use controller::SaveSlot;
+pub(crate) use controller::SaveSlots;
+pub(crate) use controller::SceneCapture;
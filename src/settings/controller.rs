@@ -0,0 +1,309 @@
+use std::path::Path;
+use anyhow::Context;
+use bevy::{audio::{AudioSink, AudioSinkPlayback, PlaybackSettings, Volume}, prelude::*, window::{MonitorSelection, PrimaryWindow, WindowMode}};
+use serde::{Deserialize, Serialize};
+
+use crate::UserDefinedConstants;
+
+/// Config file the player preferences are persisted to, inside [UserDefinedConstants::data_dir]
+const SETTINGS_FILE: &str = "settings.json";
+
+/* Components */
+/// Volume setting an audio entity of the game follows, on top of the volume in its [PlaybackSettings].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioChannel {
+    Music,
+    Sfx,
+    Voice,
+}
+
+/* Custom Types */
+/// Preferences exposed by the settings panel.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub(crate) enum SettingKind {
+    TextSpeed,
+    AutoAdvanceDelay,
+    MusicVolume,
+    SfxVolume,
+    VoiceVolume,
+    SkipUnread,
+    TextboxOpacity,
+    AutoFocus,
+    Fullscreen,
+    Language,
+}
+
+impl SettingKind {
+    pub(crate) const ALL: [SettingKind; 10] = [
+        SettingKind::TextSpeed,
+        SettingKind::AutoAdvanceDelay,
+        SettingKind::MusicVolume,
+        SettingKind::SfxVolume,
+        SettingKind::VoiceVolume,
+        SettingKind::SkipUnread,
+        SettingKind::TextboxOpacity,
+        SettingKind::AutoFocus,
+        SettingKind::Fullscreen,
+        SettingKind::Language,
+    ];
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            SettingKind::TextSpeed => "Text speed",
+            SettingKind::AutoAdvanceDelay => "Auto-advance delay",
+            SettingKind::MusicVolume => "Music volume",
+            SettingKind::SfxVolume => "SFX volume",
+            SettingKind::VoiceVolume => "Voice volume",
+            SettingKind::SkipUnread => "Skip unread text",
+            SettingKind::TextboxOpacity => "Textbox opacity",
+            SettingKind::AutoFocus => "Focus speaker",
+            SettingKind::Fullscreen => "Fullscreen",
+            SettingKind::Language => "Language",
+        }
+    }
+}
+
+/* Resources */
+/// Player preferences, loaded from the config file on startup and saved back on every change.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Characters revealed per second in the textbox, the infotext reveals at half this rate
+    pub text_speed: f32,
+    /// Seconds a completed line stays on screen in auto mode
+    pub auto_advance_delay: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub voice_volume: f32,
+    /// Whether skip mode keeps going through lines that were never read
+    pub skip_unread: bool,
+    pub textbox_opacity: f32,
+    /// Whether the other characters on stage are dimmed while one of them speaks
+    pub auto_focus: bool,
+    pub fullscreen: bool,
+    /// One of `UserDefinedConstants::languages`, read by the game to pick its localized assets
+    pub language: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            text_speed: 50.,
+            auto_advance_delay: 1.5,
+            music_volume: 1.,
+            sfx_volume: 1.,
+            voice_volume: 1.,
+            skip_unread: false,
+            textbox_opacity: 1.,
+            auto_focus: true,
+            fullscreen: false,
+            language: String::from("en"),
+        }
+    }
+}
+
+impl Settings {
+    pub(crate) fn volume(&self, channel: AudioChannel) -> f32 {
+        match channel {
+            AudioChannel::Music => self.music_volume,
+            AudioChannel::Sfx => self.sfx_volume,
+            AudioChannel::Voice => self.voice_volume,
+        }
+    }
+
+    pub(crate) fn infotext_speed(&self) -> f32 {
+        self.text_speed / 2.
+    }
+
+    /// Moves a setting one step up or down, toggles and languages wrap around.
+    pub(crate) fn step(&mut self, kind: SettingKind, up: bool, languages: &[String]) {
+        let sign = if up { 1. } else { -1. };
+        let step = |value: f32, amount: f32, min: f32, max: f32| {
+            ((value + sign * amount).clamp(min, max) * 100.).round() / 100.
+        };
+        match kind {
+            SettingKind::TextSpeed => self.text_speed = step(self.text_speed, 10., 10., 200.),
+            SettingKind::AutoAdvanceDelay => self.auto_advance_delay = step(self.auto_advance_delay, 0.5, 0., 10.),
+            SettingKind::MusicVolume => self.music_volume = step(self.music_volume, 0.1, 0., 1.),
+            SettingKind::SfxVolume => self.sfx_volume = step(self.sfx_volume, 0.1, 0., 1.),
+            SettingKind::VoiceVolume => self.voice_volume = step(self.voice_volume, 0.1, 0., 1.),
+            SettingKind::TextboxOpacity => self.textbox_opacity = step(self.textbox_opacity, 0.1, 0., 1.),
+            SettingKind::SkipUnread => self.skip_unread = !self.skip_unread,
+            SettingKind::AutoFocus => self.auto_focus = !self.auto_focus,
+            SettingKind::Fullscreen => self.fullscreen = !self.fullscreen,
+            SettingKind::Language => {
+                if languages.is_empty() {
+                    return;
+                }
+                let current = languages.iter().position(|l| *l == self.language);
+                let next = match (current, up) {
+                    (None, _) => 0,
+                    (Some(i), true) => (i + 1) % languages.len(),
+                    (Some(i), false) => (i + languages.len() - 1) % languages.len(),
+                };
+                self.language = languages[next].clone();
+            }
+        }
+    }
+
+    pub(crate) fn display(&self, kind: SettingKind) -> String {
+        let toggle = |value: bool| String::from(if value { "On" } else { "Off" });
+        match kind {
+            SettingKind::TextSpeed => format!("{:.0} cps", self.text_speed),
+            SettingKind::AutoAdvanceDelay => format!("{:.1}s", self.auto_advance_delay),
+            SettingKind::MusicVolume => format!("{:.0}%", self.music_volume * 100.),
+            SettingKind::SfxVolume => format!("{:.0}%", self.sfx_volume * 100.),
+            SettingKind::VoiceVolume => format!("{:.0}%", self.voice_volume * 100.),
+            SettingKind::TextboxOpacity => format!("{:.0}%", self.textbox_opacity * 100.),
+            SettingKind::SkipUnread => toggle(self.skip_unread),
+            SettingKind::AutoFocus => toggle(self.auto_focus),
+            SettingKind::Fullscreen => toggle(self.fullscreen),
+            SettingKind::Language => self.language.clone(),
+        }
+    }
+}
+
+pub(crate) struct SettingsController;
+impl Plugin for SettingsController {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>()
+            .add_systems(Startup, load_settings)
+            .add_systems(Update, apply_window_mode.run_if(resource_changed::<Settings>))
+            // The settings loaded on startup are already on disk
+            .add_systems(Update, save_settings.run_if(resource_changed::<Settings>.and(not(resource_added::<Settings>))))
+            .add_systems(Update, apply_audio_volumes);
+    }
+}
+/// A missing or unreadable settings file leaves the defaults in place instead of stopping the game
+fn load_settings(
+    mut settings: ResMut<Settings>,
+    user_defined_constants: Res<UserDefinedConstants>,
+) {
+    let path = user_defined_constants.data_dir().join(SETTINGS_FILE);
+    if !path.exists() {
+        return;
+    }
+    match read_settings(&path, &user_defined_constants) {
+        Ok(loaded) => {
+            *settings = loaded;
+            info!("settings loaded from {}", path.display());
+        },
+        Err(e) => warn!("Using the default settings: {:#}", e),
+    }
+}
+fn read_settings(path: &Path, user_defined_constants: &UserDefinedConstants) -> anyhow::Result<Settings> {
+    let contents = std::fs::read_to_string(path)
+        .context(format!("Could not read settings file '{}'", path.display()))?;
+    let mut loaded: Settings = serde_json::from_str(&contents)
+        .context(format!("Settings file '{}' is malformed", path.display()))?;
+    // Languages removed by the game since the last run fall back to the default one
+    if !user_defined_constants.languages.is_empty() && !user_defined_constants.languages.contains(&loaded.language) {
+        loaded.language = user_defined_constants.languages[0].clone();
+    }
+    Ok(loaded)
+}
+/// Failing to persist the preferences only costs them on the next run, so it is not fatal
+fn save_settings(
+    settings: Res<Settings>,
+    user_defined_constants: Res<UserDefinedConstants>,
+) {
+    let data_dir = user_defined_constants.data_dir();
+    let path = data_dir.join(SETTINGS_FILE);
+    let written = serde_json::to_string_pretty(&*settings)
+        .context("Could not serialize settings")
+        .and_then(|contents| std::fs::create_dir_all(&data_dir)
+            .and_then(|_| std::fs::write(&path, contents))
+            .context(format!("Could not write settings file '{}'", path.display())));
+    if let Err(e) = written {
+        warn!("Settings were not saved: {:#}", e);
+    }
+}
+/// Keeps the sinks of [AudioChannel] entities at their channel volume, for new sounds and on every settings change
+fn apply_audio_volumes(
+    settings: Res<Settings>,
+    mut q_sinks: Query<(&mut AudioSink, &AudioChannel, Option<&PlaybackSettings>)>,
+) {
+    for (mut sink, channel, playback) in &mut q_sinks {
+        if !settings.is_changed() && !sink.is_added() {
+            continue;
+        }
+        let base = playback.map(|playback| playback.volume).unwrap_or(Volume::Linear(1.));
+        sink.set_volume(base * Volume::Linear(settings.volume(*channel)));
+    }
+}
+fn apply_window_mode(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let mode = if settings.fullscreen {
+        WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+    } else {
+        WindowMode::Windowed
+    };
+    for mut window in &mut windows {
+        if window.mode != mode {
+            window.mode = mode;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_are_clamped_to_the_setting_range() {
+        let mut settings = Settings { text_speed: 195., music_volume: 0.05, ..default() };
+        settings.step(SettingKind::TextSpeed, true, &[]);
+        settings.step(SettingKind::MusicVolume, false, &[]);
+        assert_eq!(settings.text_speed, 200.);
+        assert_eq!(settings.music_volume, 0.);
+
+        settings.step(SettingKind::TextSpeed, true, &[]);
+        settings.step(SettingKind::MusicVolume, false, &[]);
+        assert_eq!(settings.text_speed, 200.);
+        assert_eq!(settings.music_volume, 0.);
+    }
+
+    #[test]
+    fn steps_are_rounded_to_hundredths() {
+        let mut settings = Settings { textbox_opacity: 0., ..default() };
+        for _ in 0..3 {
+            settings.step(SettingKind::TextboxOpacity, true, &[]);
+        }
+        assert_eq!(settings.textbox_opacity, 0.3);
+        assert_eq!(settings.display(SettingKind::TextboxOpacity), "30%");
+    }
+
+    #[test]
+    fn toggles_flip_either_way() {
+        let mut settings = Settings::default();
+        settings.step(SettingKind::Fullscreen, false, &[]);
+        assert!(settings.fullscreen);
+        settings.step(SettingKind::Fullscreen, true, &[]);
+        assert!(!settings.fullscreen);
+    }
+
+    #[test]
+    fn languages_wrap_around() {
+        let languages = [String::from("en"), String::from("fr"), String::from("ja")];
+        let mut settings = Settings::default();
+
+        settings.step(SettingKind::Language, false, &languages);
+        assert_eq!(settings.language, "ja");
+        settings.step(SettingKind::Language, true, &languages);
+        assert_eq!(settings.language, "en");
+        settings.step(SettingKind::Language, true, &languages);
+        assert_eq!(settings.language, "fr");
+    }
+
+    #[test]
+    fn unknown_language_goes_to_the_first_one() {
+        let mut settings = Settings { language: String::from("de"), ..default() };
+        settings.step(SettingKind::Language, true, &[]);
+        assert_eq!(settings.language, "de");
+
+        settings.step(SettingKind::Language, false, &[String::from("en"), String::from("fr")]);
+        assert_eq!(settings.language, "en");
+    }
+}
@@ -0,0 +1,6 @@
+pub(crate) mod controller;
+
+pub(crate) use controller::SettingsController;
+pub(crate) use controller::SettingKind;
+pub use controller::Settings;
+pub use controller::AudioChannel;
@@ -0,0 +1,305 @@
+use anyhow::Context;
+use bevy::prelude::*;
+use bevy_ui_widgets::Activate;
+
+use crate::{
+    SabiResume, SabiStart, SceneReplay, ScriptId, UserDefinedConstants, VisualNovelState,
+    chat::{controller::{SaveLoadPanel, SlotBrowser, SlotBrowserMode, UiButtons, close_slot_browser}, ui::settings::settings_panel},
+    compiler::controller::{LoadedScripts, SabiState},
+    save::SaveSlots,
+    settings::Settings,
+    title::ui::{gallery_menu, main_menu, title_root}
+};
+
+/* States */
+#[derive(States, Debug, Default, Clone, Copy, Hash, Eq, PartialEq)]
+pub(crate) enum TitleState {
+    /// No title screen is configured or a script is running
+    #[default]
+    Hidden,
+    Main,
+    Gallery,
+    Settings,
+    /// The slot browser is open to load a save
+    Load,
+}
+
+/* Components */
+#[derive(Component)]
+pub(crate) struct TitleRoot;
+#[derive(Component, Clone, Debug, PartialEq)]
+pub(crate) enum TitleButtons {
+    Entry(TitleEntry),
+    /// Replays the gallery entry at this index
+    Replay(usize),
+    Back,
+}
+
+/* Custom Types */
+/// Entries of the main menu, shown in the order they are listed in [TitleScreen::entries].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleEntry {
+    NewGame,
+    /// Resumes the latest save
+    Continue,
+    Load,
+    Settings,
+    Gallery,
+    Quit,
+}
+
+impl TitleEntry {
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            TitleEntry::NewGame => "New Game",
+            TitleEntry::Continue => "Continue",
+            TitleEntry::Load => "Load",
+            TitleEntry::Settings => "Settings",
+            TitleEntry::Gallery => "Gallery",
+            TitleEntry::Quit => "Quit",
+        }
+    }
+}
+
+/// Scene unlocked in the gallery, replayed in isolation with a [SceneReplay].
+#[derive(Debug, Clone)]
+pub struct GalleryEntry {
+    pub label: String,
+    pub script_id: ScriptId,
+    pub scene: String,
+}
+
+/// Placement of the title screen elements, in percent of the window unless stated otherwise.
+#[derive(Debug, Clone)]
+pub struct TitleLayout {
+    pub title_top: f32,
+    pub title_font_size: f32,
+    pub menu_left: f32,
+    pub menu_top: f32,
+    pub button_font_size: f32,
+    /// Space between menu entries, in pixels
+    pub button_gap: f32,
+}
+
+impl Default for TitleLayout {
+    fn default() -> Self {
+        Self {
+            title_top: 15.,
+            title_font_size: 64.,
+            menu_left: 10.,
+            menu_top: 45.,
+            button_font_size: 28.,
+            button_gap: 10.,
+        }
+    }
+}
+
+/* Resources */
+/// Built-in front-end shown whenever no script is running.
+/// Sabi starts straight into the host's [SabiStart] unless this resource is inserted.
+#[derive(Resource, Debug, Clone)]
+pub struct TitleScreen {
+    pub title: String,
+    /// Image asset path, e.g. `"sabi/backgrounds/main_classroom_day.png"`
+    pub background: Option<String>,
+    /// Script started by [TitleEntry::NewGame]
+    pub new_game: ScriptId,
+    pub entries: Vec<TitleEntry>,
+    pub layout: TitleLayout,
+    pub gallery: Vec<GalleryEntry>,
+}
+
+impl TitleScreen {
+    pub fn new(title: impl Into<String>, new_game: ScriptId) -> Self {
+        Self {
+            title: title.into(),
+            background: None,
+            new_game,
+            entries: vec![
+                TitleEntry::NewGame,
+                TitleEntry::Continue,
+                TitleEntry::Load,
+                TitleEntry::Settings,
+                TitleEntry::Gallery,
+                TitleEntry::Quit,
+            ],
+            layout: TitleLayout::default(),
+            gallery: Vec::new(),
+        }
+    }
+
+    pub fn with_background(mut self, path: impl Into<String>) -> Self {
+        self.background = Some(path.into());
+        self
+    }
+
+    pub fn with_layout(mut self, layout: TitleLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_gallery_entry(mut self, label: impl Into<String>, script_id: ScriptId, scene: impl Into<String>) -> Self {
+        self.gallery.push(GalleryEntry { label: label.into(), script_id, scene: scene.into() });
+        self
+    }
+}
+
+pub(crate) struct TitleController;
+impl Plugin for TitleController {
+    fn build(&self, app: &mut App) {
+        app.init_state::<TitleState>()
+            .add_systems(Update, show_title.run_if(
+                in_state(SabiState::Idle)
+                    .and(in_state(TitleState::Hidden))
+                    .and(resource_exists::<TitleScreen>)
+            ))
+            .add_systems(OnExit(SabiState::Idle), hide_title)
+            .add_systems(OnEnter(TitleState::Main), spawn_main_menu)
+            .add_systems(OnEnter(TitleState::Gallery), spawn_gallery)
+            .add_systems(OnEnter(TitleState::Settings), spawn_settings)
+            .add_observer(title_button_clicked)
+            .add_observer(settings_button_clicked)
+            .add_observer(load_button_clicked);
+    }
+}
+fn show_title(
+    mut commands: Commands,
+    title_screen: Res<TitleScreen>,
+    asset_server: Res<AssetServer>,
+    mut title_state: ResMut<NextState<TitleState>>,
+    // Only peeked at, `check_start` reads them with its own reader
+    start_reader: MessageReader<SabiStart>,
+    resume_reader: MessageReader<SabiResume>,
+) {
+    // A script about to start leaves Idle on the next frame, showing the title meanwhile would flash it
+    if !start_reader.is_empty() || !resume_reader.is_empty() {
+        return;
+    }
+    commands.spawn(title_root(&title_screen, &asset_server));
+    title_state.set(TitleState::Main);
+}
+fn hide_title(mut title_state: ResMut<NextState<TitleState>>) {
+    title_state.set(TitleState::Hidden);
+}
+fn spawn_main_menu(
+    mut commands: Commands,
+    title_screen: Res<TitleScreen>,
+    save_slots: Res<SaveSlots>,
+    asset_server: Res<AssetServer>,
+    title_root: Single<Entity, With<TitleRoot>>,
+) {
+    let menu = commands.spawn(main_menu(&title_screen, !save_slots.0.is_empty(), save_slots.has_browsable(), &asset_server)).id();
+    commands.entity(*title_root).add_child(menu);
+}
+fn spawn_gallery(
+    mut commands: Commands,
+    title_screen: Res<TitleScreen>,
+    asset_server: Res<AssetServer>,
+    title_root: Single<Entity, With<TitleRoot>>,
+) {
+    let menu = commands.spawn(gallery_menu(&title_screen, &asset_server)).id();
+    commands.entity(*title_root).add_child(menu);
+}
+fn spawn_settings(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    title_root: Single<Entity, With<TitleRoot>>,
+) -> Result<(), BevyError> {
+    let panel = commands.spawn((
+        settings_panel(None, &settings, &asset_server)?,
+        DespawnOnExit(TitleState::Settings),
+    )).id();
+    commands.entity(*title_root).add_child(panel);
+    Ok(())
+}
+#[allow(clippy::too_many_arguments)]
+fn title_button_clicked(
+    trigger: On<Activate>,
+    mut commands: Commands,
+    q_buttons: Query<&TitleButtons>,
+    title_root: Single<Entity, With<TitleRoot>>,
+    title_screen: Option<Res<TitleScreen>>,
+    save_slots: Res<SaveSlots>,
+    mut title_state: ResMut<NextState<TitleState>>,
+    mut game_state: ResMut<VisualNovelState>,
+    mut start_writer: MessageWriter<SabiStart>,
+    mut resume_writer: MessageWriter<SabiResume>,
+    mut exit_writer: MessageWriter<AppExit>,
+    loaded_scripts: LoadedScripts,
+) -> Result<(), BevyError> {
+    let Ok(button) = q_buttons.get(trigger.entity) else {
+        return Ok(());
+    };
+    let title_screen = title_screen.context("Title button clicked without a TitleScreen")?;
+
+    match button {
+        TitleButtons::Entry(TitleEntry::NewGame) => {
+            info!("[ New game ]");
+            game_state.history.clear();
+            game_state.variables.clear();
+            start_writer.write(SabiStart::new(title_screen.new_game.clone()));
+        },
+        TitleButtons::Entry(TitleEntry::Settings) => title_state.set(TitleState::Settings),
+        TitleButtons::Entry(TitleEntry::Gallery) => title_state.set(TitleState::Gallery),
+        TitleButtons::Entry(TitleEntry::Quit) => { exit_writer.write(AppExit::Success); },
+        TitleButtons::Entry(TitleEntry::Continue) => {
+            let save = save_slots.latest().context("Continue clicked without any save")?;
+            if let Err(e) = loaded_scripts.check_save(save) {
+                warn!("Latest save can't be continued: {:#}", e);
+                return Ok(());
+            }
+            info!("[ Continuing latest save ]");
+            resume_writer.write(SabiResume(save.clone()));
+        },
+        TitleButtons::Entry(TitleEntry::Load) => {
+            commands.insert_resource(SlotBrowser::new(SlotBrowserMode::Load, None, *title_root));
+            title_state.set(TitleState::Load);
+        },
+        TitleButtons::Replay(index) => {
+            let entry = title_screen.gallery.get(*index)
+                .context(format!("Gallery entry {} does not exist", index))?;
+            start_writer.write(SabiStart::new(entry.script_id.clone()).replay(SceneReplay::new(entry.scene.clone())));
+        },
+        TitleButtons::Back => title_state.set(TitleState::Main),
+    }
+    Ok(())
+}
+fn settings_button_clicked(
+    trigger: On<Activate>,
+    q_buttons: Query<&UiButtons>,
+    current_title_state: Res<State<TitleState>>,
+    mut title_state: ResMut<NextState<TitleState>>,
+    mut settings: ResMut<Settings>,
+    user_defined_constants: Res<UserDefinedConstants>,
+) {
+    if *current_title_state != TitleState::Settings {
+        return;
+    }
+    let Ok(button) = q_buttons.get(trigger.entity) else {
+        return;
+    };
+
+    match button {
+        UiButtons::ExitSettings => title_state.set(TitleState::Main),
+        UiButtons::SettingDown(kind) => settings.step(*kind, false, &user_defined_constants.languages),
+        UiButtons::SettingUp(kind) => settings.step(*kind, true, &user_defined_constants.languages),
+        _ => {}
+    }
+}
+fn load_button_clicked(
+    trigger: On<Activate>,
+    mut commands: Commands,
+    q_buttons: Query<&UiButtons>,
+    q_panel: Query<Entity, With<SaveLoadPanel>>,
+    current_title_state: Res<State<TitleState>>,
+    mut title_state: ResMut<NextState<TitleState>>,
+) {
+    if *current_title_state != TitleState::Load {
+        return;
+    }
+    if let Ok(UiButtons::ExitSaveLoad) = q_buttons.get(trigger.entity) {
+        close_slot_browser(&mut commands, &q_panel);
+        title_state.set(TitleState::Main);
+    }
+}
@@ -0,0 +1,8 @@
+pub(crate) mod controller;
+mod ui;
+
+pub(crate) use controller::TitleController;
+pub use controller::GalleryEntry;
+pub use controller::TitleEntry;
+pub use controller::TitleLayout;
+pub use controller::TitleScreen;
@@ -0,0 +1,125 @@
+use bevy::{color::palettes::css::GRAY, ecs::relationship::RelatedSpawner, prelude::*};
+use bevy_ui_widgets::Button;
+
+use crate::{
+    chat::controller::ThemedWidget,
+    title::controller::{TitleButtons, TitleEntry, TitleRoot, TitleScreen, TitleState},
+};
+
+const FONT_PATH: &str = "sabi/fonts/ALLER.ttf";
+
+pub(in crate::title) fn title_root(title_screen: &TitleScreen, asset_server: &Res<AssetServer>) -> impl Bundle {
+    let background = match &title_screen.background {
+        Some(path) => ImageNode::new(asset_server.load(path)),
+        None => ImageNode { color: Color::NONE, ..default() },
+    };
+    (
+        background,
+        Node {
+            width: percent(100.),
+            height: percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        BackgroundColor(Color::BLACK),
+        TitleRoot,
+        DespawnOnEnter(TitleState::Hidden),
+        children![
+            (
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: percent(title_screen.layout.title_top),
+                    ..default()
+                },
+                Text::new(title_screen.title.clone()),
+                TextFont {
+                    font: asset_server.load(FONT_PATH),
+                    font_size: title_screen.layout.title_font_size,
+                    ..default()
+                },
+                TextShadow::default(),
+            )
+        ]
+    )
+}
+
+pub(in crate::title) fn main_menu(title_screen: &TitleScreen, can_continue: bool, can_load: bool, asset_server: &Res<AssetServer>) -> impl Bundle {
+    let mut buttons = Vec::new();
+    for entry in &title_screen.entries {
+        let enabled = match entry {
+            TitleEntry::Continue => can_continue,
+            TitleEntry::Load => can_load,
+            TitleEntry::Gallery => !title_screen.gallery.is_empty(),
+            TitleEntry::NewGame | TitleEntry::Settings | TitleEntry::Quit => true,
+        };
+        buttons.push(menu_button(entry.label(), TitleButtons::Entry(*entry), enabled, title_screen, asset_server));
+    }
+
+    (
+        menu_container(title_screen),
+        DespawnOnExit(TitleState::Main),
+        Children::spawn(
+            SpawnWith(|parent: &mut RelatedSpawner<ChildOf>| spawn_buttons(parent, buttons))
+        ),
+    )
+}
+
+pub(in crate::title) fn gallery_menu(title_screen: &TitleScreen, asset_server: &Res<AssetServer>) -> impl Bundle {
+    let mut buttons = Vec::new();
+    for (index, entry) in title_screen.gallery.iter().enumerate() {
+        buttons.push(menu_button(&entry.label, TitleButtons::Replay(index), true, title_screen, asset_server));
+    }
+    buttons.push(menu_button("Back", TitleButtons::Back, true, title_screen, asset_server));
+
+    (
+        menu_container(title_screen),
+        DespawnOnExit(TitleState::Gallery),
+        Children::spawn(
+            SpawnWith(|parent: &mut RelatedSpawner<ChildOf>| spawn_buttons(parent, buttons))
+        ),
+    )
+}
+
+fn menu_container(title_screen: &TitleScreen) -> Node {
+    Node {
+        position_type: PositionType::Absolute,
+        left: percent(title_screen.layout.menu_left),
+        top: percent(title_screen.layout.menu_top),
+        display: Display::Flex,
+        flex_direction: FlexDirection::Column,
+        row_gap: px(title_screen.layout.button_gap),
+        ..default()
+    }
+}
+
+fn menu_button(
+    label: &str,
+    action: TitleButtons,
+    enabled: bool,
+    title_screen: &TitleScreen,
+    asset_server: &Res<AssetServer>,
+) -> (impl Bundle, Option<TitleButtons>) {
+    let text = (
+        Text::new(label),
+        TextFont {
+            font: asset_server.load(FONT_PATH),
+            font_size: title_screen.layout.button_font_size,
+            ..default()
+        },
+        TextColor(if enabled { Color::WHITE } else { GRAY.into() }),
+        TextShadow::default(),
+    );
+    // Disabled entries are drawn but never receive Activate
+    let action = enabled.then_some(action);
+    ((Node::default(), children![text]), action)
+}
+
+fn spawn_buttons(parent: &mut RelatedSpawner<ChildOf>, buttons: Vec<(impl Bundle, Option<TitleButtons>)>) {
+    for (button, action) in buttons {
+        let mut entity = parent.spawn(button);
+        // Disabled entries keep their greyed out look
+        if let Some(action) = action {
+            entity.insert((action, Button, ThemedWidget::Button));
+        }
+    }
+}